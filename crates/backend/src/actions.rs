@@ -1,12 +1,57 @@
-use std::path::Path;
+use std::{
+    io::{self, SeekFrom},
+    os::unix::fs::PermissionsExt,
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
 
-use proto::frontend::{RenameAction, Signal as FrontendSignal, SignalAction, UploadAction};
+use log::{info, warn};
+use proto::{
+    backend::{BlockPartition, DropCachesResponse},
+    frontend::{
+        ChmodAction, ChownAction, DietPiSetting, DietPiSettingAction, FileAction, MountAction,
+        PowerAction, SaveFileAction, ServiceAction, ServiceCommand, Signal as FrontendSignal,
+        SignalAction, TransferAction, UploadChunkAction,
+    },
+};
 use sysinfo::{Pid, Signal};
-use tokio::fs;
+use tokio::{
+    fs,
+    io::{AsyncSeekExt, AsyncWriteExt},
+};
 
-use crate::client::BackendContext;
+use crate::{
+    client::BackendContext,
+    getters::{self, DIETPI_TXT_PATH, read_id_names},
+    sandbox::check_path_async,
+};
+
+// Drives can only be mounted somewhere under these, so that one can't be mounted over part of the system
+const MOUNT_ROOTS: &[&str] = &["/mnt", "/media"];
+// Unmounting any of these would take down the system, or DietPi's software along with its data
+const CRITICAL_MOUNTS: &[&str] = &[
+    "/",
+    "/boot",
+    "/boot/firmware",
+    "/boot/efi",
+    "/usr",
+    "/var",
+    "/home",
+    "/root",
+    "/opt",
+    "/mnt/dietpi_userdata",
+];
+
+pub fn process_signal(mut ctx: BackendContext, action: SignalAction) -> Result<(), String> {
+    // Signalling init or the backend itself would take down the whole system or the dashboard
+    if action.pid == 1 || action.pid == std::process::id() {
+        warn!(
+            "Refusing to send signal to protected process {}",
+            action.pid
+        );
+        return Err(format!("process {} is protected", action.pid));
+    }
 
-pub fn process_signal(mut ctx: BackendContext, action: SignalAction) {
     let sys = &mut ctx.system();
 
     let signal = match action.signal {
@@ -17,35 +62,582 @@ pub fn process_signal(mut ctx: BackendContext, action: SignalAction) {
     };
 
     let Some(proc) = sys.system.process(Pid::from_u32(action.pid)) else {
-        return;
+        return Err(format!("no process with PID {}", action.pid));
     };
 
-    proc.kill_with(signal);
+    // `None` means that the signal doesn't exist on this platform, which can't happen on Linux
+    if proc.kill_with(signal) != Some(true) {
+        warn!("Failed to send {signal:?} to process {}", action.pid);
+        return Err(format!("failed to send signal to process {}", action.pid));
+    }
+
+    Ok(())
+}
+
+async fn new_file(path: String) -> io::Result<()> {
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .await?;
+
+    Ok(())
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    // Don't follow symlinks, so that a link to a parent directory can't cause an infinite loop
+    let metadata = std::fs::symlink_metadata(from)?;
+
+    if metadata.is_dir() {
+        std::fs::create_dir(to)?;
+
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else if metadata.is_symlink() {
+        std::os::unix::fs::symlink(std::fs::read_link(from)?, to)?;
+    } else {
+        std::fs::copy(from, to)?;
+    }
+
+    Ok(())
+}
+
+async fn copy(action: TransferAction) -> io::Result<()> {
+    let (from, to) = (PathBuf::from(action.from), PathBuf::from(action.to));
+
+    if to.starts_with(&from) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "can't copy a folder into itself",
+        ));
+    }
+
+    tokio::task::spawn_blocking(move || copy_recursive(&from, &to))
+        .await
+        .unwrap()
+}
+
+async fn move_path(action: TransferAction) -> io::Result<()> {
+    match fs::rename(&action.from, &action.to).await {
+        // Renaming doesn't work across filesystems, so fall back to copying and then deleting
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            let from = action.from.clone();
+            copy(action).await?;
+
+            if fs::symlink_metadata(&from).await?.is_dir() {
+                fs::remove_dir_all(from).await
+            } else {
+                fs::remove_file(from).await
+            }
+        }
+        res => res,
+    }
+}
+
+// Changing these would likely break the system, so it has to be explicitly forced
+const PROTECTED_PATHS: &[&str] = &[
+    "/", "/bin", "/boot", "/dev", "/etc", "/home", "/lib", "/proc", "/root", "/run", "/sbin",
+    "/sys", "/tmp", "/usr", "/var",
+];
+
+async fn check_protected(path: &str, force: bool) -> io::Result<()> {
+    let canonical = fs::canonicalize(path).await?;
+
+    if !force
+        && PROTECTED_PATHS
+            .iter()
+            .any(|protected| canonical == Path::new(protected))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("refusing to change protected path {}", canonical.display()),
+        ));
+    }
+
+    Ok(())
+}
+
+fn resolve_id(name: &str, db_path: &str) -> io::Result<u32> {
+    if let Ok(id) = name.parse() {
+        return Ok(id);
+    }
+
+    read_id_names(db_path)
+        .into_iter()
+        .find_map(|(id, entry)| (entry == name).then_some(id))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("unknown name {name}")))
+}
+
+async fn chmod(action: ChmodAction) -> io::Result<()> {
+    check_protected(&action.path, action.force).await?;
+
+    let permissions = std::fs::Permissions::from_mode(action.mode & 0o7777);
+    fs::set_permissions(&action.path, permissions).await?;
+
+    info!("Changed mode of {} to {:04o}", action.path, action.mode);
+
+    Ok(())
+}
+
+async fn chown(action: ChownAction) -> io::Result<()> {
+    check_protected(&action.path, action.force).await?;
+
+    let uid = action
+        .owner
+        .as_deref()
+        .map(|owner| resolve_id(owner, "/etc/passwd"))
+        .transpose()?;
+    let gid = action
+        .group
+        .as_deref()
+        .map(|group| resolve_id(group, "/etc/group"))
+        .transpose()?;
+
+    let path = action.path.clone();
+    tokio::task::spawn_blocking(move || std::os::unix::fs::chown(path, uid, gid))
+        .await
+        .unwrap()?;
+
+    info!(
+        "Changed ownership of {} to {}:{}",
+        action.path,
+        action.owner.as_deref().unwrap_or("-"),
+        action.group.as_deref().unwrap_or("-")
+    );
+
+    Ok(())
 }
 
-pub async fn new_file(path: String) {
-    let path = Path::new(&path);
-    if !path.exists() {
-        let _ = fs::write(path, []).await;
+pub fn service_control(_ctx: BackendContext, action: ServiceAction) -> Result<(), String> {
+    // Only allow units from the services list, so that arbitrary arguments can't be passed to systemctl
+    let services = getters::services_helper().ok_or("failed to get services list")?;
+    if !services
+        .services
+        .iter()
+        .any(|service| service.name == action.unit)
+    {
+        return Err(format!("unknown service {}", action.unit));
+    }
+
+    let cmd = match action.action {
+        ServiceCommand::Start => "start",
+        ServiceCommand::Stop => "stop",
+        ServiceCommand::Restart => "restart",
+        ServiceCommand::Enable => "enable",
+        ServiceCommand::Disable => "disable",
+    };
+
+    info!("Running systemctl {cmd} {}", action.unit);
+
+    let output = std::process::Command::new("systemctl")
+        .args([cmd, "--", &action.unit])
+        .output()
+        .map_err(|err| err.to_string())?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
     }
+
+    Ok(())
 }
 
-pub async fn new_folder(path: String) {
-    let _ = fs::create_dir(path).await;
+// Only partitions that were found on a drive, so that arbitrary arguments can't be passed to mount
+fn find_partition(device: &str) -> Result<BlockPartition, String> {
+    getters::block_devices_helper()
+        .into_iter()
+        .flat_map(|device| device.partitions)
+        .find(|part| part.name == device)
+        .ok_or_else(|| format!("unknown device {device}"))
 }
 
-pub async fn rename(action: RenameAction) {
-    let _ = fs::rename(action.from, action.to).await;
+fn is_mount_target_allowed(target: &Path) -> bool {
+    target.is_absolute()
+        && !target.components().any(|x| x == Component::ParentDir)
+        && MOUNT_ROOTS
+            .iter()
+            .any(|root| target.starts_with(root) && target != Path::new(root))
+        && !CRITICAL_MOUNTS.iter().any(|x| target == Path::new(x))
 }
 
-pub async fn delete_file(path: String) {
-    let _ = fs::remove_file(path).await;
+pub fn mount(_ctx: BackendContext, action: MountAction) -> Result<(), String> {
+    let part = find_partition(&action.device)?;
+
+    if !part.mnt_point.is_empty() {
+        return Err(format!(
+            "{} is already mounted at {}",
+            part.name, part.mnt_point
+        ));
+    }
+
+    let not_allowed = || {
+        format!(
+            "drives can only be mounted in a directory under {}",
+            MOUNT_ROOTS.join(" or ")
+        )
+    };
+
+    if !is_mount_target_allowed(Path::new(&action.target)) {
+        return Err(not_allowed());
+    }
+
+    std::fs::create_dir_all(&action.target)
+        .map_err(|err| format!("failed to create mount point: {err}"))?;
+
+    // Checked again once it exists, in case it goes through a symlink
+    let target = std::fs::canonicalize(&action.target)
+        .map_err(|err| format!("failed to resolve mount point: {err}"))?;
+    if !is_mount_target_allowed(&target) {
+        return Err(not_allowed());
+    }
+
+    // Mounting over files would hide them until the drive is unmounted again
+    let is_empty = std::fs::read_dir(&target)
+        .map_err(|err| format!("failed to read mount point: {err}"))?
+        .next()
+        .is_none();
+    if !is_empty {
+        return Err(format!("{} isn't empty", target.display()));
+    }
+
+    let device = format!("/dev/{}", part.name);
+
+    info!("Mounting {device} at {}", target.display());
+
+    let output = std::process::Command::new("mount")
+        .arg("--")
+        .arg(&device)
+        .arg(&target)
+        .output()
+        .map_err(|err| err.to_string())?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    Ok(())
 }
 
-pub async fn delete_folder(path: String) {
-    let _ = fs::remove_dir_all(path).await;
+pub fn unmount(_ctx: BackendContext, device: String) -> Result<(), String> {
+    let part = find_partition(&device)?;
+
+    if part.mnt_point.is_empty() {
+        return Err(format!("{device} isn't mounted"));
+    }
+
+    if CRITICAL_MOUNTS.contains(&part.mnt_point.as_str()) {
+        warn!(
+            "Refusing to unmount critical mount point {}",
+            part.mnt_point
+        );
+        return Err(format!("{} is needed by the system", part.mnt_point));
+    }
+
+    info!("Unmounting {}", part.mnt_point);
+
+    let output = std::process::Command::new("umount")
+        .args(["--", &part.mnt_point])
+        .output()
+        .map_err(|err| err.to_string())?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    Ok(())
 }
 
-pub async fn write(action: UploadAction) {
-    let _ = fs::write(action.path, action.data).await;
+pub async fn power(ctx: BackendContext, action: PowerAction) -> Result<u64, String> {
+    if !ctx.config.enable_power_actions {
+        return Err("power actions are disabled in the backend config".into());
+    }
+
+    let cmd = match action {
+        PowerAction::Reboot => "reboot",
+        PowerAction::Poweroff => "poweroff",
+        PowerAction::Halt => "halt",
+    };
+    let delay = ctx.config.power_action_delay;
+
+    info!("Running systemctl {cmd} in {delay} seconds");
+
+    // Answered straight away, since the connection drops once it happens
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(delay)).await;
+
+        match tokio::process::Command::new("systemctl")
+            .arg(cmd)
+            .output()
+            .await
+        {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => warn!(
+                "Failed to run systemctl {cmd}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(err) => warn!("Failed to run systemctl {cmd}: {err}"),
+        }
+    });
+
+    Ok(delay)
+}
+
+pub fn drop_caches(ctx: BackendContext) -> Result<DropCachesResponse, String> {
+    if !ctx.config.enable_drop_caches {
+        return Err("dropping caches is disabled in the backend config".into());
+    }
+
+    let before = getters::memory(ctx.clone()).ram_details;
+
+    info!("Dropping page cache");
+
+    // Only clean pages can be dropped, so write out everything that's dirty first
+    let status = std::process::Command::new("sync")
+        .status()
+        .map_err(|err| format!("failed to run sync: {err}"))?;
+    if !status.success() {
+        return Err(format!("sync exited with {status}"));
+    }
+
+    // 3 drops both the page cache and the dentry and inode caches
+    std::fs::write("/proc/sys/vm/drop_caches", "3")
+        .map_err(|err| format!("failed to drop caches: {err}"))?;
+
+    let after = getters::memory(ctx).ram_details;
+
+    Ok(DropCachesResponse { before, after })
+}
+
+fn run_dietpi_func(name: &str, args: &[&str]) -> Result<(), String> {
+    let output = std::process::Command::new(format!("/boot/dietpi/func/{name}"))
+        .args(args)
+        .output()
+        .map_err(|err| err.to_string())?;
+
+    if !output.status.success() {
+        // DietPi scripts print most of their errors to stdout
+        let msg = if output.stderr.is_empty() {
+            output.stdout
+        } else {
+            output.stderr
+        };
+        let msg = getters::remove_escape_codes(msg.into_iter());
+        return Err(String::from_utf8_lossy(&msg).trim().to_string());
+    }
+
+    Ok(())
+}
+
+// Replaces the value of a key in dietpi.txt, adding the key if it isn't there yet
+fn write_dietpi_txt(key: &str, value: &str) -> io::Result<()> {
+    let file = std::fs::read_to_string(DIETPI_TXT_PATH)?;
+
+    let mut found = false;
+    let mut lines: Vec<_> = file
+        .lines()
+        .map(|line| match line.split_once('=') {
+            Some((line_key, _)) if line_key.trim() == key => {
+                found = true;
+                format!("{key}={value}")
+            }
+            _ => line.to_string(),
+        })
+        .collect();
+
+    if !found {
+        lines.push(format!("{key}={value}"));
+    }
+
+    std::fs::write(DIETPI_TXT_PATH, lines.join("\n") + "\n")
+}
+
+// The swapfile is created at the given path, so it mustn't replace anything that's already there
+fn check_swap_location(location: &str, current: Option<&str>) -> Result<(), String> {
+    let path = Path::new(location);
+    let invalid = || format!("invalid swap location {location}");
+
+    if !path.is_absolute() || path.components().any(|x| x == Component::ParentDir) {
+        return Err(invalid());
+    }
+
+    let (Some(parent), Some(_)) = (path.parent(), path.file_name()) else {
+        return Err(invalid());
+    };
+
+    // Resolved first, so that a symlink can't lead somewhere else
+    let parent = std::fs::canonicalize(parent)
+        .ok()
+        .filter(|parent| parent.is_dir())
+        .ok_or_else(|| format!("{} isn't an existing directory", parent.display()))?;
+
+    // Directly in /, or somewhere under /var or a drive mounted in /mnt
+    let allowed = parent == Path::new("/")
+        || parent.starts_with("/var")
+        || (parent.starts_with("/mnt") && parent != Path::new("/mnt"));
+    if !allowed {
+        return Err(format!(
+            "swap can only be put in /, or under /var or a drive in /mnt, not {}",
+            parent.display()
+        ));
+    }
+
+    let is_current = current.is_some_and(|current| Path::new(current) == path);
+    if !is_current && path.symlink_metadata().is_ok() {
+        return Err(format!("{location} already exists"));
+    }
+
+    Ok(())
+}
+
+pub fn set_dietpi_setting(_ctx: BackendContext, action: DietPiSettingAction) -> Result<(), String> {
+    match action.setting {
+        DietPiSetting::SwapSize => {
+            let size: u32 = action
+                .value
+                .parse()
+                .map_err(|_| format!("invalid swap size {}", action.value))?;
+
+            info!("Setting swap file size to {size} MiB");
+
+            // This updates dietpi.txt too
+            run_dietpi_func("dietpi-set_swapfile", &[&size.to_string()])
+        }
+        DietPiSetting::SwapLocation => {
+            let mut settings = getters::read_dietpi_txt();
+
+            if action.value != "zram" {
+                let current = settings.get("AUTO_SETUP_SWAPFILE_LOCATION");
+                check_swap_location(&action.value, current.map(String::as_str))?;
+            }
+
+            // The size has to be given along with the location, so keep the current one
+            let size = settings
+                .remove("AUTO_SETUP_SWAPFILE_SIZE")
+                .unwrap_or_else(|| "1".into());
+
+            info!("Moving swap to {}", action.value);
+
+            run_dietpi_func("dietpi-set_swapfile", &[&size, &action.value])
+        }
+        DietPiSetting::CpuGovernor => {
+            if !getters::available_governors().contains(&action.value) {
+                return Err(format!("unknown CPU governor {}", action.value));
+            }
+
+            info!("Setting CPU governor to {}", action.value);
+
+            write_dietpi_txt("CONFIG_CPU_GOVERNOR", &action.value)
+                .map_err(|err| err.to_string())?;
+            // Applies the CPU settings from dietpi.txt
+            run_dietpi_func("dietpi-set_cpu", &[])
+        }
+    }
+}
+
+// Entries themselves are checked for anything that acts on the entry, rather than what it links to
+async fn check_file_action(ctx: &BackendContext, action: &FileAction) -> io::Result<()> {
+    match action {
+        FileAction::NewFile(path) | FileAction::NewFolder(path) => {
+            check_path_async(ctx, path, true).await
+        }
+        FileAction::Rename(action) | FileAction::Move(action) | FileAction::Copy(action) => {
+            check_path_async(ctx, &action.from, false).await?;
+            check_path_async(ctx, &action.to, false).await
+        }
+        FileAction::DeleteFile(path) | FileAction::DeleteFolder(path) => {
+            check_path_async(ctx, path, false).await
+        }
+        FileAction::Chmod(ChmodAction { path, .. })
+        | FileAction::Chown(ChownAction { path, .. }) => check_path_async(ctx, path, true).await,
+    }
+}
+
+pub async fn file_action(ctx: BackendContext, action: FileAction) -> Result<(), String> {
+    check_file_action(&ctx, &action)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let res = match action {
+        FileAction::NewFile(path) => new_file(path).await,
+        FileAction::NewFolder(path) => fs::create_dir(path).await,
+        FileAction::Rename(action) => fs::rename(action.from, action.to).await,
+        FileAction::Move(action) => move_path(action).await,
+        FileAction::Copy(action) => copy(action).await,
+        FileAction::DeleteFile(path) => fs::remove_file(path).await,
+        FileAction::DeleteFolder(path) => fs::remove_dir_all(path).await,
+        FileAction::Chmod(action) => chmod(action).await,
+        FileAction::Chown(action) => chown(action).await,
+    };
+
+    res.map_err(|err| err.to_string())
+}
+
+pub async fn save_file(ctx: BackendContext, action: SaveFileAction) -> Result<(), String> {
+    check_path_async(&ctx, &action.path, true)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    fs::write(&action.path, action.data).await.map_err(|err| {
+        warn!("Failed to save {}: {err}", action.path);
+        err.to_string()
+    })
+}
+
+// Write to a separate file until the upload is finished, so that a partial upload can't be
+// mistaken for a complete one, and so that an interrupted upload can be resumed
+fn part_path(path: &str) -> String {
+    format!("{path}.part")
+}
+
+async fn upload_chunk_helper(action: UploadChunkAction) -> io::Result<()> {
+    let part_path = part_path(&action.path);
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(action.offset == 0)
+        .open(&part_path)
+        .await?;
+
+    // Resuming past the end of what was received would leave a hole in the file
+    if action.offset > file.metadata().await?.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "upload offset is past the end of the partial file",
+        ));
+    }
+
+    file.seek(SeekFrom::Start(action.offset)).await?;
+    file.write_all(&action.data).await?;
+
+    if action.last {
+        // A resumed upload could have restarted from before the end of the partial file
+        file.set_len(action.offset + action.data.len() as u64)
+            .await?;
+        file.flush().await?;
+
+        fs::rename(part_path, action.path).await?;
+    } else {
+        file.flush().await?;
+    }
+
+    Ok(())
+}
+
+// Returns how much of an interrupted upload was already received
+pub async fn upload_status(ctx: BackendContext, path: String) -> Option<u64> {
+    check_path_async(&ctx, &path, true).await.ok()?;
+
+    let metadata = fs::metadata(part_path(&path)).await.ok()?;
+
+    Some(metadata.len())
+}
+
+pub async fn upload_chunk(ctx: BackendContext, action: UploadChunkAction) -> bool {
+    if check_path_async(&ctx, &action.path, true).await.is_err() {
+        return false;
+    }
+
+    upload_chunk_helper(action).await.is_ok()
 }
@@ -1,26 +1,42 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
 
-use anyhow::{Context, Result};
-use config::PROTOCOL_VERSION;
+use anyhow::{Context, Result, anyhow};
+use config::{APP_VERSION, PROTOCOL_VERSION};
 use proto::{
     DashboardSocket,
     backend::{ActionBackendMessage, BackendMessage, Handshake, ResponseBackendMessage},
     frontend::{ActionFrontendMessage, FrontendMessage, RequestFrontendMessage},
 };
-use sysinfo::{Components, Disks, Networks, System};
-use tokio::{fs, net::TcpStream, sync::mpsc};
+use sysinfo::{Components, CpuRefreshKind, Disks, Networks, RefreshKind, System};
+use tokio::{net::TcpStream, sync::mpsc};
 
-use crate::{SharedConfig, actions, getters};
+use crate::{
+    SharedConfig, actions, docker,
+    getters::{self, SharedSoftwareCache},
+    history::{self, SharedHistory},
+    network_usage::{self, SharedNetworkUsage},
+    streams::{self, SharedStreams},
+    terminal::{self, SharedTerminals},
+};
 
 macro_rules! getters {
     ($req:expr, $ctx:expr, {
         $( $variant:ident $(($data:ident))? => $fn:expr, )*
+    }, async {
+        $( $async_variant:ident $(($async_data:ident))? => $async_fn:expr, )*
     }) => {
         match $req {
             $( RequestFrontendMessage::$variant $(($data))? => {
                 let data = tokio::task::spawn_blocking(move || $fn($ctx $(, $data)?)).await.unwrap();
                 ResponseBackendMessage::$variant(data)
             } )*
+            $( RequestFrontendMessage::$async_variant $(($async_data))? => {
+                let data = $async_fn($ctx $(, $async_data)?).await;
+                ResponseBackendMessage::$async_variant(data)
+            } )*
         }
     };
 }
@@ -32,15 +48,21 @@ pub struct SystemComponents {
     pub components: Components,
     pub disks: Disks,
     pub networks: Networks,
+    pub disk_io: getters::DiskIoSample,
 }
 
 impl SystemComponents {
     pub fn new() -> Self {
         Self {
-            system: System::new(),
+            // Take an initial CPU sample, so that the first global and per-core usage readings
+            // have something to be compared against and don't just show 0%
+            system: System::new_with_specifics(
+                RefreshKind::nothing().with_cpu(CpuRefreshKind::nothing().with_cpu_usage()),
+            ),
             components: Components::new_with_refreshed_list(),
             disks: Disks::new_with_refreshed_list(),
             networks: Networks::new_with_refreshed_list(),
+            disk_io: getters::DiskIoSample::default(),
         }
     }
 }
@@ -49,8 +71,13 @@ impl SystemComponents {
 pub struct BackendContext {
     pub config: SharedConfig,
     pub system: SharedSystem,
+    pub streams: SharedStreams,
+    pub software: SharedSoftwareCache,
+    pub history: SharedHistory,
+    pub terminals: SharedTerminals,
+    pub network_usage: SharedNetworkUsage,
     pub socket_tx: mpsc::UnboundedSender<BackendMessage>,
-    pub term_tx: mpsc::UnboundedSender<Vec<u8>>,
+    pub start_time: SystemTime,
 }
 
 impl BackendContext {
@@ -74,14 +101,22 @@ impl<'a> BackendClient<'a> {
             .await
             .context("failed to connect to frontend")?;
 
+        let socket = DashboardSocket::new(stream, context.config.secret.0)
+            .await
+            .context("failed to set up encryption")?;
+
         Ok(Self {
-            socket: DashboardSocket::new(stream, context.config.secret.0),
+            socket,
             context,
             rx,
         })
     }
 
     pub async fn run(mut self) -> Result<()> {
+        // Any streams left over from a previous connection can't be read anymore
+        self.context.streams.lock().unwrap().clear();
+
+        self.check_version().await?;
         self.send_handshake().await?;
 
         loop {
@@ -104,16 +139,40 @@ impl<'a> BackendClient<'a> {
         }
     }
 
+    // Versions are exchanged on their own first, since messages from an incompatible version might
+    // not even be decodable
+    async fn check_version(&mut self) -> Result<()> {
+        self.socket
+            .write_frame(PROTOCOL_VERSION)
+            .await
+            .context("failed to send protocol version")?;
+
+        let version: u32 = self
+            .socket
+            .read_frame()
+            .await
+            .context("failed to read protocol version, the frontend may be outdated")?
+            .context("frontend disconnected before sending protocol version")?;
+
+        if version != PROTOCOL_VERSION {
+            return Err(anyhow!(
+                "frontend uses protocol v{version}, but this backend uses v{PROTOCOL_VERSION}, update both to the same release"
+            ));
+        }
+
+        Ok(())
+    }
+
     async fn send_handshake(&mut self) -> Result<()> {
         let nickname = self.context.config.nickname.clone();
-        let update = fs::read_to_string("/run/dietpi/.update_available")
+        let update = tokio::task::spawn_blocking(getters::update_info)
             .await
-            .ok();
+            .unwrap();
 
         let handshake = Handshake {
             nickname,
             update,
-            version: PROTOCOL_VERSION,
+            app_version: APP_VERSION.into(),
         };
 
         let msg = ActionBackendMessage::Handshake(handshake);
@@ -141,41 +200,70 @@ impl RequestHandler {
 
         match self.req {
             FrontendMessage::Request(id, req) => {
-                let resp = getters!(req, ctx, {
-                    Cpu => getters::cpu,
-                    Temp => getters::temp,
-                    Mem => getters::memory,
-                    Disk => getters::disks,
-                    NetIO => getters::network_io,
-                    Processes => getters::processes,
-                    Host => getters::host,
-                    Software => getters::software,
-                    Command(action) => getters::command,
-                    Services => getters::services,
-                    Directory(path) => getters::list_directory,
-                    Download(path) => getters::read_file,
-                    ReadConfig => getters::read_config,
-                });
-
-                let resp = BackendMessage::Response(id, resp);
+                let resp = BackendMessage::Response(id, Self::respond(req, ctx).await);
                 let _ = self.context.socket_tx.send(resp);
             }
             FrontendMessage::Action(msg) => match msg {
-                ActionFrontendMessage::Terminal(data) => {
-                    let _ = self.context.term_tx.send(data);
+                ActionFrontendMessage::Terminal(id, data) => terminal::write(ctx, id, data),
+                ActionFrontendMessage::CloseTerminal(id) => terminal::close(ctx, id),
+                ActionFrontendMessage::CloseStream(stream_id) => {
+                    streams::close_stream(ctx, stream_id)
                 }
-                ActionFrontendMessage::Signal(action) => {
-                    tokio::task::spawn_blocking(|| actions::process_signal(ctx, action))
-                        .await
-                        .unwrap()
-                }
-                ActionFrontendMessage::NewFile(path) => actions::new_file(path).await,
-                ActionFrontendMessage::NewFolder(path) => actions::new_folder(path).await,
-                ActionFrontendMessage::Rename(action) => actions::rename(action).await,
-                ActionFrontendMessage::DeleteFile(path) => actions::delete_file(path).await,
-                ActionFrontendMessage::DeleteFolder(path) => actions::delete_folder(path).await,
-                ActionFrontendMessage::Upload(action) => actions::write(action).await,
             },
         }
     }
+
+    async fn respond(req: RequestFrontendMessage, ctx: BackendContext) -> ResponseBackendMessage {
+        getters!(req, ctx, {
+            Cpu => history::cpu,
+            Temp => getters::temp,
+            History => history::history,
+            Mem => getters::memory,
+            Disk => getters::disks,
+            Mounts => getters::mounts,
+            BlockDevices => getters::block_devices,
+            Mount(action) => actions::mount,
+            Unmount(device) => actions::unmount,
+            NetIO => getters::network_io,
+            DiskIO => getters::disk_io,
+            NetworkUsage => network_usage::usage,
+            ResetNetworkUsage => network_usage::reset,
+            Processes(action) => getters::processes,
+            Signal(action) => actions::process_signal,
+            Host => getters::host,
+            Software => getters::software,
+            Command(name) => getters::command,
+            Services => getters::services,
+            ServiceControl(action) => actions::service_control,
+            DropCaches => actions::drop_caches,
+            Directory(action) => getters::list_directory,
+            Search(action) => getters::search_files,
+            Stat(path) => getters::stat,
+            Checksum(path) => getters::checksum,
+            Thumbnail(action) => getters::thumbnail,
+            ReadFile(path) => getters::read_file,
+            ReadConfig => getters::read_config,
+            DietPiSettings => getters::dietpi_settings,
+            SetDietPiSetting(action) => actions::set_dietpi_setting,
+            Terminals => terminal::list,
+        }, async {
+            Docker => docker::containers,
+            Journal(action) => streams::journal,
+            TailLog(action) => streams::tail_log,
+            SoftwareAction(action) => streams::software_action,
+            Download(path) => streams::download,
+            DownloadArchive(path) => streams::download_archive,
+            Preview(path) => streams::preview,
+            ReadStream(stream_id) => streams::read_stream,
+            SaveFile(action) => actions::save_file,
+            UploadChunk(action) => actions::upload_chunk,
+            UploadStatus(path) => actions::upload_status,
+            FileAction(action) => actions::file_action,
+            Power(action) => actions::power,
+            Ping => ping,
+        })
+    }
 }
+
+// Answered straight away, so that the frontend can measure the round trip time of the connection
+async fn ping(_ctx: BackendContext) {}
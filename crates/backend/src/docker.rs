@@ -0,0 +1,126 @@
+use bollard::{
+    API_DEFAULT_VERSION, Docker,
+    models::{ContainerCpuStats, ContainerStatsResponse, ContainerSummaryStateEnum},
+    query_parameters::{ListContainersOptionsBuilder, StatsOptionsBuilder},
+};
+use futures_util::StreamExt;
+use log::error;
+use proto::backend::{ContainerInfo, DockerResponse};
+use tokio::task::JoinSet;
+
+use crate::client::BackendContext;
+
+const DOCKER_SOCKET: &str = "/var/run/docker.sock";
+const DOCKER_TIMEOUT: u64 = 10;
+
+// Returns the CPU usage as a percentage of a single core, and the memory usage
+fn usage_from_stats(stats: ContainerStatsResponse) -> (f32, u64) {
+    let cpu = stats.cpu_stats.unwrap_or_default();
+    let precpu = stats.precpu_stats.unwrap_or_default();
+
+    let total_usage = |stats: &ContainerCpuStats| {
+        stats
+            .cpu_usage
+            .as_ref()
+            .and_then(|usage| usage.total_usage)
+            .unwrap_or(0)
+    };
+
+    // Same calculation as `docker stats`
+    let cpu_delta = total_usage(&cpu).saturating_sub(total_usage(&precpu));
+    let system_delta = cpu
+        .system_cpu_usage
+        .unwrap_or(0)
+        .saturating_sub(precpu.system_cpu_usage.unwrap_or(0));
+    let online_cpus = cpu.online_cpus.unwrap_or(1);
+
+    let cpu = if system_delta > 0 {
+        (cpu_delta as f64 / system_delta as f64 * online_cpus as f64 * 100.) as f32
+    } else {
+        0.
+    };
+
+    let memory = stats.memory_stats.unwrap_or_default();
+    // Page cache can be reclaimed, so `docker stats` doesn't count it as used
+    let cache = memory
+        .stats
+        .as_ref()
+        .and_then(|stats| {
+            stats
+                .get("inactive_file")
+                .or_else(|| stats.get("total_inactive_file"))
+                .copied()
+        })
+        .unwrap_or(0);
+    let mem = memory.usage.unwrap_or(0).saturating_sub(cache);
+
+    ((cpu * 100.).round() / 100., mem)
+}
+
+async fn container_usage(docker: Docker, id: String) -> Option<(f32, u64)> {
+    // Not using one-shot, since the CPU usage needs a previous sample to compare against
+    let options = StatsOptionsBuilder::new().stream(false).build();
+
+    let stats = docker.stats(&id, Some(options)).next().await?.ok()?;
+
+    Some(usage_from_stats(stats))
+}
+
+pub async fn containers(_ctx: BackendContext) -> Option<DockerResponse> {
+    // Most systems won't have Docker installed, so this isn't an error
+    if !tokio::fs::try_exists(DOCKER_SOCKET).await.unwrap_or(false) {
+        return None;
+    }
+
+    let docker = Docker::connect_with_unix(DOCKER_SOCKET, DOCKER_TIMEOUT, API_DEFAULT_VERSION)
+        .inspect_err(|err| error!("Failed to connect to Docker: {err}"))
+        .ok()?;
+
+    let options = ListContainersOptionsBuilder::new().all(true).build();
+    let summaries = docker
+        .list_containers(Some(options))
+        .await
+        .inspect_err(|err| error!("Failed to list Docker containers: {err}"))
+        .ok()?;
+
+    let mut containers: Vec<ContainerInfo> = summaries
+        .iter()
+        .map(|summary| ContainerInfo {
+            // Names are prefixed with a slash
+            name: summary
+                .names
+                .as_ref()
+                .and_then(|names| names.first())
+                .map(|name| name.trim_start_matches('/').to_string())
+                .unwrap_or_default(),
+            image: summary.image.clone().unwrap_or_default(),
+            state: summary
+                .state
+                .map(|state| state.to_string())
+                .unwrap_or_default(),
+            status: summary.status.clone().unwrap_or_default(),
+            cpu: 0.,
+            mem: 0,
+        })
+        .collect();
+
+    // Getting stats takes about a second per container, so get them all at once
+    let mut tasks = JoinSet::new();
+    for (idx, summary) in summaries.into_iter().enumerate() {
+        if let (Some(id), Some(ContainerSummaryStateEnum::RUNNING)) = (summary.id, summary.state) {
+            let docker = docker.clone();
+            tasks.spawn(async move { (idx, container_usage(docker, id).await) });
+        }
+    }
+
+    while let Some(res) = tasks.join_next().await {
+        if let Ok((idx, Some((cpu, mem)))) = res {
+            containers[idx].cpu = cpu;
+            containers[idx].mem = mem;
+        }
+    }
+
+    containers.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Some(DockerResponse { containers })
+}
@@ -1,20 +1,65 @@
 use std::os::unix::fs::MetadataExt;
-use std::{fs, path::PathBuf, process::Command};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::PathBuf,
+    process::Command,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
 
-use mime_guess::mime;
+use image::{ImageFormat, ImageReader, Limits};
+use mime_guess::{Mime, mime};
 use proto::backend::FileKind;
 use proto::{
     backend::{
-        CommandResponse, CpuResponse, DirectoryItemInfo, DirectoryResponse, DiskInfo, DiskResponse,
-        HostResponse, MemResponse, NetworkResponse, ProcessInfo, ProcessResponse, ProcessStatus,
-        ServiceInfo, ServiceResponse, ServiceStatus, SoftwareInfo, SoftwareResponse, TempResponse,
-        UsageData,
+        BlockDevice, BlockPartition, ChecksumResponse, CommandResponse, CpuResponse,
+        DietPiSettingsResponse, DirectoryItemInfo, DirectoryResponse, DiskInfo, DiskIoInfo,
+        DiskIoResponse, DiskResponse, HostResponse, InterfaceAddresses, InterfaceInfo, MemResponse,
+        NetworkResponse, ProcessInfo, ProcessResponse, ProcessStatus, RamDetails, SearchResponse,
+        SensorInfo, ServiceInfo, ServiceResponse, ServiceStatus, SoftwareInfo, SoftwareResponse,
+        SwapDevice, TempResponse, ThrottleFlags, UpdateInfo, UsageData,
+    },
+    frontend::{
+        DirectoryAction, DirectorySort, ProcessAction, ProcessSort, SearchAction, ThumbnailAction,
     },
-    frontend::CommandAction,
 };
-use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+use ring::digest;
+use sysinfo::{
+    Disk, NetworkData, Networks, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind,
+};
 
-use crate::client::BackendContext;
+use crate::{client::BackendContext, sandbox::check_path};
+
+const SOFTWARE_CACHE_TTL: Duration = Duration::from_secs(60);
+const MAX_SEARCH_RESULTS: usize = 500;
+const MAX_SEARCH_DEPTH: usize = 32;
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(10);
+const CHECKSUM_CHUNK_SIZE: usize = 64 * 1024;
+// Anything bigger is impractical to edit in the browser, so it has to be downloaded instead
+const MAX_OPEN_SIZE: u64 = 4 * 1024 * 1024;
+// Larger images have to be downloaded instead
+const MAX_PREVIEW_SIZE: u64 = 32 * 1024 * 1024;
+// A small file can still claim huge dimensions, so decoding is capped separately
+const MAX_DECODE_ALLOC: u64 = 128 * 1024 * 1024;
+const MAX_THUMBNAIL_SIZE: u32 = 1024;
+// Formats that browsers show inline and that thumbnails can be made from. SVGs are left out, since
+// they can contain scripts.
+const PREVIEW_FORMATS: &[&str] = &["png", "jpeg", "gif", "webp", "bmp"];
+pub const DIETPI_TXT_PATH: &str = "/boot/dietpi.txt";
+// /proc/diskstats always counts in 512 byte sectors, regardless of the device's actual sector size
+const DISKSTATS_SECTOR_SIZE: u64 = 512;
+
+pub type SharedSoftwareCache = Arc<Mutex<Option<(Instant, SoftwareResponse)>>>;
+
+// The counters from the previous disk I/O request, which the next one calculates its rates against
+#[derive(Default)]
+pub struct DiskIoSample {
+    taken: Option<Instant>,
+    // Sectors read and written, by device name
+    counters: HashMap<String, (u64, u64)>,
+}
 
 fn round_to_2(num: f32) -> f32 {
     (num * 100.).round() / 100.
@@ -49,7 +94,17 @@ pub fn temp(mut ctx: BackendContext) -> TempResponse {
         .and_then(|x| x.temperature())
         .map(round_to_2);
 
-    TempResponse { temp }
+    let sensors = components
+        .iter()
+        .filter_map(|x| {
+            Some(SensorInfo {
+                label: x.label().into(),
+                temp: round_to_2(x.temperature()?),
+            })
+        })
+        .collect();
+
+    TempResponse { temp, sensors }
 }
 
 pub fn memory(mut ctx: BackendContext) -> MemResponse {
@@ -68,7 +123,58 @@ pub fn memory(mut ctx: BackendContext) -> MemResponse {
         total: sys.total_swap(),
     };
 
-    MemResponse { ram, swap }
+    let (buffers, cached) = read_meminfo_cache();
+
+    let ram_details = RamDetails {
+        available: sys.available_memory(),
+        free: sys.free_memory(),
+        buffers,
+        cached,
+    };
+
+    MemResponse {
+        ram,
+        swap,
+        ram_details,
+    }
+}
+
+// sysinfo doesn't expose these, so read them directly
+fn read_meminfo_cache() -> (u64, u64) {
+    let Ok(meminfo) = fs::read_to_string("/proc/meminfo") else {
+        return (0, 0);
+    };
+
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|value| {
+                value
+                    .trim()
+                    .trim_end_matches("kB")
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+            })
+            .map_or(0, |kib| kib.saturating_mul(1024))
+    };
+
+    // Reclaimable slab memory is counted as cache by `free`, so do the same
+    (field("Buffers"), field("Cached") + field("SReclaimable"))
+}
+
+fn disk_info(disk: &Disk) -> DiskInfo {
+    DiskInfo {
+        name: disk.name().to_str().unwrap_or("unknown").into(),
+        mnt_point: disk.mount_point().to_str().unwrap_or("unknown").into(),
+        fs_type: disk.file_system().to_str().unwrap_or("unknown").into(),
+        // Some filesystems in containers report more space available than there is in total
+        usage: UsageData {
+            used: disk.total_space().saturating_sub(disk.available_space()),
+            total: disk.total_space(),
+        },
+    }
 }
 
 pub fn disks(mut ctx: BackendContext) -> DiskResponse {
@@ -79,38 +185,348 @@ pub fn disks(mut ctx: BackendContext) -> DiskResponse {
     disks.refresh(false);
     let disks = disks.list();
 
+    // Folders like /mnt/dietpi_userdata are often just part of the root filesystem, so show
+    // whichever disk each path is actually stored on. Mount points are always absolute, so a
+    // longer one that matches is always the more specific one.
+    let mut shown: Vec<&Disk> = Vec::new();
+    for path in &mnt_points {
+        let disk = disks
+            .iter()
+            .filter(|disk| path.starts_with(disk.mount_point()))
+            .max_by_key(|disk| disk.mount_point().as_os_str().len());
+
+        if let Some(disk) = disk
+            && !shown
+                .iter()
+                .any(|shown| shown.mount_point() == disk.mount_point())
+        {
+            shown.push(disk);
+        }
+    }
+
+    let disks = shown.into_iter().map(disk_info).collect();
+
+    DiskResponse { disks }
+}
+
+pub fn mounts(mut ctx: BackendContext) -> DiskResponse {
+    let disks = &mut ctx.system().disks;
+    // Also picks up any filesystems that have been mounted or unmounted since the last refresh
+    disks.refresh(true);
+    let disks = disks.list();
+
+    // sysinfo already skips pseudo-filesystems (proc, sysfs, tmpfs, etc.)
     let disks: Vec<_> = disks
         .iter()
-        .filter(|disk| mnt_points.iter().any(|path| path == disk.mount_point()))
-        .map(|disk| DiskInfo {
-            name: disk.name().to_str().unwrap_or("unknown").into(),
-            mnt_point: disk.mount_point().to_str().unwrap_or("unknown").into(),
-            usage: UsageData {
-                used: disk.total_space() - disk.available_space(),
-                total: disk.total_space(),
-            },
-        })
+        .filter(|disk| disk.total_space() > 0)
+        .map(disk_info)
         .collect();
 
     DiskResponse { disks }
 }
 
+// Mount points have spaces and the like escaped as octal, e.g. "\040"
+fn unescape_mount_field(field: &str) -> String {
+    let mut out = Vec::with_capacity(field.len());
+    let mut bytes = field.bytes();
+
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            out.push(byte);
+            continue;
+        }
+
+        let digits: Vec<u8> = bytes.by_ref().take(3).collect();
+        let escaped = std::str::from_utf8(&digits)
+            .ok()
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+
+        match escaped {
+            Some(escaped) => out.push(escaped),
+            None => {
+                out.push(byte);
+                out.extend(digits);
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// Maps device paths to where they're mounted and with which filesystem, keeping the first mount
+// of each
+fn read_mounts() -> HashMap<String, (String, String)> {
+    let Ok(mounts) = fs::read_to_string("/proc/self/mounts") else {
+        return HashMap::new();
+    };
+
+    let mut map = HashMap::new();
+
+    for line in mounts.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(device), Some(mnt_point), Some(fs_type)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+
+        map.entry(unescape_mount_field(device))
+            .or_insert_with(|| (unescape_mount_field(mnt_point), fs_type.to_string()));
+    }
+
+    map
+}
+
+// udev has already probed every device, so this finds filesystems on partitions that aren't
+// mounted without needing to run blkid as root
+fn udev_properties(sys_path: &str) -> HashMap<String, String> {
+    let Ok(dev) = fs::read_to_string(format!("{sys_path}/dev")) else {
+        return HashMap::new();
+    };
+    let Ok(data) = fs::read_to_string(format!("/run/udev/data/b{}", dev.trim())) else {
+        return HashMap::new();
+    };
+
+    data.lines()
+        .filter_map(|line| line.strip_prefix("E:")?.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+fn block_partition(
+    name: &str,
+    sys_path: &str,
+    mounts: &HashMap<String, (String, String)>,
+) -> BlockPartition {
+    // Sizes are always in 512 byte sectors, whatever the device's own sector size is
+    let size = fs::read_to_string(format!("{sys_path}/size"))
+        .ok()
+        .and_then(|size| size.trim().parse::<u64>().ok())
+        .unwrap_or(0)
+        * 512;
+
+    let (mnt_point, fs_type) = match mounts.get(&format!("/dev/{name}")) {
+        Some((mnt_point, fs_type)) => (mnt_point.clone(), fs_type.clone()),
+        None => {
+            let fs_type = udev_properties(sys_path)
+                .remove("ID_FS_TYPE")
+                .unwrap_or_default();
+            (String::new(), fs_type)
+        }
+    };
+
+    BlockPartition {
+        name: name.into(),
+        size,
+        fs_type,
+        mnt_point,
+    }
+}
+
+// Lists physical drives, like SD cards, eMMC and USB drives, along with their partitions. Virtual
+// devices (loop, RAM, zram, device mapper) don't have a `device` link, and are left out.
+pub fn block_devices_helper() -> Vec<BlockDevice> {
+    let Ok(entries) = fs::read_dir("/sys/block") else {
+        return Vec::new();
+    };
+
+    let mounts = read_mounts();
+
+    let mut devices: Vec<_> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let sys_path = format!("/sys/block/{name}");
+
+            if !fs::exists(format!("{sys_path}/device")).unwrap_or(false) {
+                return None;
+            }
+
+            let udev = udev_properties(&sys_path);
+
+            let model = udev
+                .get("ID_MODEL")
+                .cloned()
+                .or_else(|| fs::read_to_string(format!("{sys_path}/device/model")).ok())
+                // SD cards and eMMC call it the name instead
+                .or_else(|| fs::read_to_string(format!("{sys_path}/device/name")).ok())
+                .map(|model| model.trim().replace('_', " "))
+                .unwrap_or_default();
+
+            let removable = fs::read_to_string(format!("{sys_path}/removable"))
+                .is_ok_and(|removable| removable.trim() == "1");
+
+            // USB SSDs usually don't count themselves as removable, so check how they're connected
+            let usb = udev.get("ID_BUS").is_some_and(|bus| bus == "usb")
+                || fs::canonicalize(&sys_path)
+                    .is_ok_and(|path| path.to_string_lossy().contains("/usb"));
+
+            let mut partitions: Vec<_> = fs::read_dir(&sys_path)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter(|entry| entry.path().join("partition").exists())
+                .filter_map(|entry| {
+                    let part_name = entry.file_name().into_string().ok()?;
+                    let part_path = format!("{sys_path}/{part_name}");
+                    Some(block_partition(&part_name, &part_path, &mounts))
+                })
+                .collect();
+
+            // So that sda10 comes after sda9
+            partitions.sort_by(|a, b| (a.name.len(), &a.name).cmp(&(b.name.len(), &b.name)));
+
+            let whole = block_partition(&name, &sys_path, &mounts);
+
+            if partitions.is_empty() && !whole.fs_type.is_empty() {
+                partitions.push(whole.clone());
+            }
+
+            Some(BlockDevice {
+                name,
+                model,
+                size: whole.size,
+                removable,
+                usb,
+                partitions,
+            })
+        })
+        .collect();
+
+    devices.sort_by(|a, b| a.name.cmp(&b.name));
+
+    devices
+}
+
+pub fn block_devices(_ctx: BackendContext) -> Vec<BlockDevice> {
+    block_devices_helper()
+}
+
+fn is_loopback(name: &str, net: &NetworkData) -> bool {
+    name == "lo" || net.ip_networks().iter().any(|ip| ip.addr.is_loopback())
+}
+
+fn is_interface_up(name: &str) -> bool {
+    // Tunnels like WireGuard report an "unknown" state even while they're up
+    fs::read_to_string(format!("/sys/class/net/{name}/operstate"))
+        .is_ok_and(|state| state.trim() != "down")
+}
+
 pub fn network_io(mut ctx: BackendContext) -> NetworkResponse {
     let networks = &mut ctx.system().networks;
     networks.refresh(false);
     let networks = networks.list();
 
-    let mut resp = NetworkResponse { sent: 0, recv: 0 };
+    let mut resp = NetworkResponse {
+        sent: 0,
+        recv: 0,
+        total_sent: 0,
+        total_recv: 0,
+        interfaces: Vec::new(),
+    };
 
-    for net in networks.values() {
+    for (name, net) in networks {
         resp.recv += net.received();
         resp.sent += net.transmitted();
+        resp.total_recv += net.total_received();
+        resp.total_sent += net.total_transmitted();
+
+        let loopback = is_loopback(name, net);
+        let up = is_interface_up(name);
+
+        // sysinfo keeps track of the previous counters for each interface, so these are deltas
+        // since the last refresh just like the totals above
+        resp.interfaces.push(InterfaceInfo {
+            name: name.clone(),
+            sent: net.transmitted(),
+            recv: net.received(),
+            total_sent: net.total_transmitted(),
+            total_recv: net.total_received(),
+            loopback,
+            up,
+        });
     }
 
+    resp.interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+
     resp
 }
 
-pub fn processes(mut ctx: BackendContext) -> ProcessResponse {
+// Returns the sectors read and written by each whole disk, leaving out partitions, since they would
+// be counted twice, and virtual devices like loop and RAM disks
+fn read_diskstats() -> HashMap<String, (u64, u64)> {
+    let Ok(diskstats) = fs::read_to_string("/proc/diskstats") else {
+        return HashMap::new();
+    };
+
+    diskstats
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<_> = line.split_whitespace().collect();
+            let name = *fields.get(2)?;
+
+            if name.starts_with("loop") || name.starts_with("ram") {
+                return None;
+            }
+            // Partitions don't get an entry of their own in /sys/block
+            if !fs::exists(format!("/sys/block/{name}")).unwrap_or(false) {
+                return None;
+            }
+
+            let read = fields.get(5)?.parse().ok()?;
+            let written = fields.get(9)?.parse().ok()?;
+
+            Some((name.to_string(), (read, written)))
+        })
+        .collect()
+}
+
+pub fn disk_io(mut ctx: BackendContext) -> DiskIoResponse {
+    let counters = read_diskstats();
+    let now = Instant::now();
+
+    let prev = std::mem::replace(
+        &mut ctx.system().disk_io,
+        DiskIoSample {
+            taken: Some(now),
+            counters: counters.clone(),
+        },
+    );
+
+    let elapsed = prev
+        .taken
+        .map_or(0., |taken| now.duration_since(taken).as_secs_f64());
+
+    let rate = |curr: u64, prev: u64| {
+        if elapsed == 0. {
+            return 0;
+        }
+        // Counters go backwards if a device is removed and another one takes its name
+        let bytes = curr.saturating_sub(prev) * DISKSTATS_SECTOR_SIZE;
+        (bytes as f64 / elapsed) as u64
+    };
+
+    let mut devices: Vec<_> = counters
+        .into_iter()
+        .map(|(name, (read, written))| {
+            // Devices that just showed up don't have anything to compare against yet
+            let (prev_read, prev_written) =
+                prev.counters.get(&name).copied().unwrap_or((read, written));
+
+            DiskIoInfo {
+                name,
+                read: rate(read, prev_read),
+                write: rate(written, prev_written),
+            }
+        })
+        .collect();
+
+    devices.sort_by(|a, b| a.name.cmp(&b.name));
+
+    DiskIoResponse { devices }
+}
+
+pub fn processes(mut ctx: BackendContext, action: ProcessAction) -> ProcessResponse {
     let sys = &mut ctx.system().system;
 
     sys.refresh_processes_specifics(
@@ -122,12 +538,13 @@ pub fn processes(mut ctx: BackendContext) -> ProcessResponse {
             .with_cmd(UpdateKind::OnlyIfNotSet),
     );
 
-    let processes = sys
+    let mut processes: Vec<_> = sys
         .processes()
         .iter()
         .filter(|(_, proc)| !proc.cmd().is_empty())
         .map(|(pid, proc)| ProcessInfo {
             pid: pid.as_u32(),
+            ppid: proc.parent().map(|ppid| ppid.as_u32()),
             name: proc.name().to_string_lossy().into(),
             cpu: round_to_2(proc.cpu_usage()),
             mem: proc.memory(),
@@ -140,59 +557,193 @@ pub fn processes(mut ctx: BackendContext) -> ProcessResponse {
         })
         .collect();
 
+    // Sorted here rather than by the frontend, so that only the processes that are wanted get sent
+    match action.sort {
+        ProcessSort::Pid => processes.sort_by_key(|a| a.pid),
+        ProcessSort::Name => processes.sort_by(|a, b| a.name.cmp(&b.name)),
+        ProcessSort::Status => processes.sort_by_key(|a| a.status),
+        ProcessSort::Cpu => processes.sort_by(|a, b| a.cpu.total_cmp(&b.cpu)),
+        ProcessSort::Ram => processes.sort_by_key(|a| a.mem),
+    }
+    if action.reverse {
+        processes.reverse();
+    }
+    if action.limit != 0 {
+        processes.truncate(action.limit as usize);
+    }
+
     ProcessResponse { processes }
 }
 
-pub fn host(mut ctx: BackendContext) -> HostResponse {
-    let net = &ctx.system().networks;
+// Map the kernel's machine names onto the architecture names that DietPi uses
+fn pretty_arch(arch: &str) -> String {
+    let pretty = match arch {
+        "" => "unknown",
+        "x86_64" | "amd64" => "x86_64",
+        "i386" | "i486" | "i586" | "i686" => "x86",
+        "aarch64" | "arm64" => "aarch64",
+        // armv8l is a 32-bit userland running on a 64-bit CPU
+        "armv7l" | "armv7" | "armv8l" => "armv7",
+        "armv6l" | "armv6" => "armv6",
+        "riscv64" => "riscv64",
+        other => other,
+    };
+
+    pretty.to_string()
+}
+
+fn dietpi_version() -> Option<String> {
+    let file = fs::read_to_string("/boot/dietpi/.version").ok()?;
+
+    // Look fields up by name rather than position, so that extra or reordered lines don't
+    // result in a bogus version
+    let field = |name: &str| {
+        file.lines()
+            .filter_map(|line| line.split_once('='))
+            .find(|(key, _)| key.trim() == name)
+            .map(|(_, value)| value.trim().trim_matches(['"', '\'']))
+    };
+
+    Some(format!(
+        "{}.{}.{}",
+        field("G_DIETPI_VERSION_CORE")?,
+        field("G_DIETPI_VERSION_SUB")?,
+        field("G_DIETPI_VERSION_RC")?
+    ))
+}
+
+// DietPi writes the version an update would bring here once it finds one, and removes the file
+// again after updating
+pub fn update_info() -> Option<UpdateInfo> {
+    let new_version = fs::read_to_string("/run/dietpi/.update_available").ok()?;
+    let new_version = new_version.trim();
+
+    let new_version = match new_version {
+        "" => return None,
+        // The update requires a newer base image, so there's no version to go to
+        "-1" => "new image required".to_string(),
+        version => version.to_string(),
+    };
+
+    Some(UpdateInfo {
+        current_version: dietpi_version().unwrap_or_else(|| "unknown".into()),
+        new_version,
+    })
+}
 
+// The firmware reports these as a bit field, like "throttled=0x50005", with the current state in
+// the low bits and whether each has happened since boot 16 bits higher
+fn throttle_flags() -> Option<ThrottleFlags> {
+    let output = Command::new("vcgencmd")
+        .arg("get_throttled")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+
+    let bits = stdout.trim().strip_prefix("throttled=0x")?;
+    let bits = u32::from_str_radix(bits, 16).ok()?;
+    let bit = |n: u32| bits & (1 << n) != 0;
+
+    Some(ThrottleFlags {
+        under_voltage: bit(0),
+        freq_capped: bit(1),
+        throttled: bit(2),
+        soft_temp_limit: bit(3),
+        under_voltage_occurred: bit(16),
+        freq_capped_occurred: bit(17),
+        throttled_occurred: bit(18),
+        soft_temp_limit_occurred: bit(19),
+    })
+}
+
+pub fn host(mut ctx: BackendContext) -> HostResponse {
     let unknown = || "unknown".to_string();
 
-    let nic = net
+    // Everything that runs a command or reads the system on its own is done before taking the
+    // lock, so that a slow command doesn't hold up the history sampler and other requests
+    let dp_version = dietpi_version().unwrap_or_else(unknown);
+
+    let pkg_list = Command::new("dpkg")
+        .arg("--get-selections")
+        .output()
+        .ok()
+        .filter(|output| output.status.success());
+    let num_pkgs = pkg_list
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|out| {
+            // Packages that were removed but still have config files left are listed as "deinstall"
+            out.lines()
+                .filter(|line| line.split_whitespace().nth(1) == Some("install"))
+                .count()
+        })
+        .unwrap_or(0);
+
+    // Read separately from the shared list, since refreshing that would throw off the network
+    // graph, and addresses can change at any time (like when a DHCP lease is renewed)
+    let networks = Networks::new_with_refreshed_list();
+    let mut interfaces: Vec<_> = networks
         .iter()
-        .max_by_key(|(_, net)| net.total_transmitted())
-        .map(|(name, _)| name)
-        .cloned()
-        .unwrap_or_else(unknown);
+        .filter(|(name, net)| !is_loopback(name, net))
+        .map(|(name, net)| InterfaceAddresses {
+            name: name.clone(),
+            mac: net.mac_address().to_string(),
+            addresses: net
+                .ip_networks()
+                .iter()
+                .map(|ip| format!("{}/{}", ip.addr, ip.prefix))
+                .collect(),
+            up: is_interface_up(name),
+            mtu: net.mtu(),
+        })
+        .collect();
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let throttling = throttle_flags();
 
     let uptime = System::uptime();
-    let arch = {
-        let a = System::cpu_arch();
-        if a.is_empty() { unknown() } else { a }
-    };
+    let boot_time = System::boot_time();
+    let backend_start = ctx
+        .start_time
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let load_avg = System::load_average();
+    let load_avg = [load_avg.one, load_avg.five, load_avg.fifteen];
+    let arch = pretty_arch(&System::cpu_arch());
     let os_version = System::long_os_version().unwrap_or_else(unknown);
     let kernel = System::kernel_version().unwrap_or_else(unknown);
     let hostname = System::host_name().unwrap_or_else(unknown);
 
-    let dp_file = fs::read_to_string("/boot/dietpi/.version").ok();
-    let dp_version = dp_file
-        .and_then(|file| {
-            let mut fields = file.split(['=', '\n']);
-
-            // Extract items 1, 3, and 5
-            Some(format!(
-                "{}.{}.{}",
-                fields.nth(1)?,
-                fields.nth(1)?,
-                fields.nth(1)?
-            ))
-        })
-        .unwrap_or_else(unknown);
+    let (nic, num_cpus) = {
+        let sys = ctx.system();
 
-    let pkg_list = Command::new("dpkg").arg("--get-selections").output().ok();
-    let num_pkgs = pkg_list
-        .map(|output| output.stdout.into_iter().filter(|&x| x == b'\n').count())
-        .unwrap_or(0);
+        let nic = sys
+            .networks
+            .iter()
+            .max_by_key(|(_, net)| net.total_transmitted())
+            .map(|(name, _)| name)
+            .cloned()
+            .unwrap_or_else(unknown);
+
+        (nic, sys.system.cpus().len())
+    };
 
     HostResponse {
         nic,
         uptime,
+        boot_time,
+        backend_start,
+        load_avg,
+        num_cpus,
         arch,
         os_version,
         kernel,
         hostname,
         dp_version,
         num_pkgs,
+        interfaces,
+        throttling,
     }
 }
 
@@ -229,7 +780,24 @@ fn parse_software_line(line: &str) -> Option<(SoftwareInfo, bool)> {
     ))
 }
 
-pub fn software(_ctx: BackendContext) -> SoftwareResponse {
+pub fn software(ctx: BackendContext) -> SoftwareResponse {
+    // dietpi-software takes a few seconds to list everything, so only run it when the cached list
+    // is stale. The lock is held while listing, so concurrent requests don't run it twice.
+    let mut cache = ctx.software.lock().unwrap();
+
+    if let Some((time, resp)) = &*cache
+        && time.elapsed() < SOFTWARE_CACHE_TTL
+    {
+        return resp.clone();
+    }
+
+    let resp = list_software();
+    *cache = Some((Instant::now(), resp.clone()));
+
+    resp
+}
+
+fn list_software() -> SoftwareResponse {
     let cmd_out = Command::new("/boot/dietpi/dietpi-software")
         .args(["list", "--machine-readable"])
         .output()
@@ -257,7 +825,7 @@ pub fn software(_ctx: BackendContext) -> SoftwareResponse {
     resp
 }
 
-fn remove_escape_codes(s: impl Iterator<Item = u8>) -> Vec<u8> {
+pub fn remove_escape_codes(s: impl Iterator<Item = u8>) -> Vec<u8> {
     s.scan(false, |in_escape, c| {
         if *in_escape {
             if c.is_ascii_alphabetic() {
@@ -275,20 +843,38 @@ fn remove_escape_codes(s: impl Iterator<Item = u8>) -> Vec<u8> {
     .collect()
 }
 
-pub fn command(_ctx: BackendContext, action: CommandAction) -> CommandResponse {
-    let output = Command::new(action.cmd)
-        .args(&action.args)
-        .output()
-        .map(|out| out.stdout.into_iter())
-        .map(remove_escape_codes)
-        .unwrap_or_else(|err| format!("command execution failed: {err}").into());
+pub fn command(ctx: BackendContext, name: String) -> Option<CommandResponse> {
+    // Only commands from the config can be run, the request just picks one
+    let command = ctx.config.commands.iter().find(|cmd| cmd.name == name)?;
 
-    CommandResponse { output }
+    let Some((program, args)) = command.argv.split_first() else {
+        return Some(CommandResponse {
+            output: "command has no program to run".into(),
+            exit_code: None,
+        });
+    };
+
+    let resp = match Command::new(program).args(args).output() {
+        Ok(out) => CommandResponse {
+            output: String::from_utf8_lossy(&remove_escape_codes(out.stdout.into_iter())).into(),
+            exit_code: out.status.code(),
+        },
+        Err(err) => CommandResponse {
+            output: format!("command execution failed: {err}"),
+            exit_code: None,
+        },
+    };
+
+    Some(resp)
 }
 
-fn services_helper() -> Option<ServiceResponse> {
+pub fn services_helper() -> Option<ServiceResponse> {
+    // The output is parsed by matching systemd's English status text, so force an English locale.
+    // C.UTF-8 is used over plain C, since systemd only uses the "×" marker in UTF-8 locales.
     let output = Command::new("/boot/dietpi/dietpi-services")
         .arg("status")
+        .env("LANG", "C.UTF-8")
+        .env("LC_ALL", "C.UTF-8")
         .output()
         .ok()?;
 
@@ -298,6 +884,14 @@ fn services_helper() -> Option<ServiceResponse> {
     let stderr = remove_escape_codes(output.stderr.into_iter());
     let stderr = std::str::from_utf8(&stderr).ok()?;
 
+    Some(ServiceResponse {
+        services: parse_services(stdout, stderr),
+    })
+}
+
+// Running and stopped services are listed on stdout, one per line, while failed ones are printed to
+// stderr along with their full `systemctl status` output
+fn parse_services(stdout: &str, stderr: &str) -> Vec<ServiceInfo> {
     let ok_services = stdout
         .lines()
         .map(|line| {
@@ -319,7 +913,7 @@ fn services_helper() -> Option<ServiceResponse> {
             };
 
             ServiceInfo {
-                name: name.into(),
+                name: name.trim().into(),
                 status,
                 start: date.into(),
                 err_log: String::new(),
@@ -331,61 +925,367 @@ fn services_helper() -> Option<ServiceResponse> {
         .filter_map(|desc| desc.split_once(".service"))
         .filter_map(|(name, rest)| rest.split_once("\n\n").map(|(_, err_log)| (name, err_log)))
         .map(|(name, err_log)| ServiceInfo {
-            name: name.into(),
+            name: name.trim().into(),
             status: ServiceStatus::Failed,
             start: String::new(),
             err_log: err_log.into(),
         });
 
-    let mut services = Vec::new();
-    services.extend(ok_services);
-    services.extend(failed_services);
+    ok_services.chain(failed_services).collect()
+}
+
+pub fn services(ctx: BackendContext) -> ServiceResponse {
+    let mut resp = services_helper().unwrap_or_default();
 
-    Some(ServiceResponse { services })
+    // The sort is stable, so everything that isn't pinned keeps the order it was listed in
+    let pinned = &ctx.config.pinned_services;
+    resp.services.sort_by_key(|service| {
+        pinned
+            .iter()
+            .position(|name| *name == service.name)
+            .unwrap_or(pinned.len())
+    });
+
+    resp
 }
 
-pub fn services(_ctx: BackendContext) -> ServiceResponse {
-    services_helper().unwrap_or_default()
+pub fn read_id_names(path: &str) -> HashMap<u32, String> {
+    let Ok(file) = fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+
+    // Both /etc/passwd and /etc/group start with "name:password:id"
+    file.lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id = fields.nth(1)?.parse().ok()?;
+
+            Some((id, name.to_string()))
+        })
+        .collect()
+}
+
+// Loaded once per request, rather than looking up every file's owner separately
+struct IdNames {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl IdNames {
+    fn load() -> Self {
+        Self {
+            users: read_id_names("/etc/passwd"),
+            groups: read_id_names("/etc/group"),
+        }
+    }
+
+    fn user(&self, uid: u32) -> String {
+        self.users
+            .get(&uid)
+            .cloned()
+            .unwrap_or_else(|| uid.to_string())
+    }
+
+    fn group(&self, gid: u32) -> String {
+        self.groups
+            .get(&gid)
+            .cloned()
+            .unwrap_or_else(|| gid.to_string())
+    }
 }
 
-pub fn list_directory_helper(path: String) -> Option<DirectoryResponse> {
-    let dir = fs::read_dir(path).ok()?;
+fn directory_item(path: PathBuf, names: &IdNames) -> Option<DirectoryItemInfo> {
+    let metadata = fs::metadata(&path).ok()?;
 
-    let dir_list: Vec<DirectoryItemInfo> = dir
+    let path = path.into_os_string().into_string().ok()?;
+
+    let file_type = metadata.file_type();
+
+    let kind = if file_type.is_dir() {
+        FileKind::Directory
+    } else if file_type.is_file() {
+        match mime_guess::from_path(&path).first_or_octet_stream() {
+            mime if mime.type_() == mime::TEXT => FileKind::TextFile,
+            mime if is_previewable(&mime) => FileKind::Image,
+            _ => FileKind::BinaryFile,
+        }
+    } else {
+        FileKind::Special
+    };
+
+    let size = metadata.is_file().then_some(metadata.size());
+
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|time| time.as_secs());
+
+    Some(DirectoryItemInfo {
+        path,
+        kind,
+        size,
+        modified,
+        mode: metadata.mode() & 0o7777,
+        owner: names.user(metadata.uid()),
+        group: names.group(metadata.gid()),
+    })
+}
+
+pub fn list_directory_helper(action: DirectoryAction) -> Option<DirectoryResponse> {
+    let dir = fs::read_dir(action.path).ok()?;
+
+    let names = IdNames::load();
+
+    let mut dir_list: Vec<DirectoryItemInfo> = dir
         .filter_map(Result::ok)
-        .filter_map(|item| {
-            let metadata = fs::metadata(item.path()).ok()?;
+        .filter_map(|item| directory_item(item.path(), &names))
+        .collect();
 
-            let path = item.path().into_os_string().into_string().ok()?;
+    // Sorting has to happen before paging, otherwise each page would only be sorted on its own
+    match action.sort {
+        DirectorySort::Name => dir_list.sort_by(|a, b| a.path.cmp(&b.path)),
+        DirectorySort::Size => dir_list.sort_by_key(|a| a.size),
+        DirectorySort::Modified => dir_list.sort_by_key(|a| a.modified),
+    }
+    if action.reverse {
+        dir_list.reverse();
+    }
 
-            let file_type = metadata.file_type();
+    let total = dir_list.len() as u32;
 
-            let kind = if file_type.is_dir() {
-                FileKind::Directory
-            } else if file_type.is_file() {
-                match mime_guess::from_path(&path).first_or_octet_stream().type_() {
-                    mime::TEXT => FileKind::TextFile,
-                    _ => FileKind::BinaryFile,
-                }
+    let dir_list = dir_list
+        .into_iter()
+        .skip(action.offset as usize)
+        .take(action.limit as usize)
+        .collect();
+
+    Some(DirectoryResponse { dir_list, total })
+}
+
+pub fn list_directory(ctx: BackendContext, action: DirectoryAction) -> DirectoryResponse {
+    if check_path(&ctx, &action.path, true).is_err() {
+        return DirectoryResponse::default();
+    }
+
+    list_directory_helper(action).unwrap_or_default()
+}
+
+// Supports `*` for any run of characters and `?` for any single character
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Where to resume from if the current attempt after a `*` fails
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => {
+                let Some((star_p, star_n)) = backtrack else {
+                    return false;
+                };
+                // Let the `*` swallow one more character and try again
+                backtrack = Some((star_p, star_n + 1));
+                p = star_p + 1;
+                n = star_n + 1;
+            }
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+pub fn search_files(ctx: BackendContext, action: SearchAction) -> SearchResponse {
+    if check_path(&ctx, &action.root, true).is_err() {
+        return SearchResponse::default();
+    }
+
+    let start = Instant::now();
+
+    let pattern = action.pattern.to_lowercase();
+    let is_glob = pattern.contains(['*', '?']);
+    let pattern_chars: Vec<char> = pattern.chars().collect();
+
+    let names = IdNames::load();
+
+    let mut results = Vec::new();
+    let mut truncated = false;
+
+    let mut dirs = vec![(PathBuf::from(action.root), 0)];
+
+    'search: while let Some((dir, depth)) = dirs.pop() {
+        if start.elapsed() > SEARCH_TIMEOUT {
+            truncated = true;
+            break;
+        }
+
+        // Folders that can't be read are skipped, rather than failing the whole search
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for entry in entries.filter_map(Result::ok) {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+
+            let is_match = if is_glob {
+                glob_match(&pattern_chars, &name.chars().collect::<Vec<_>>())
             } else {
-                FileKind::Special
+                name.contains(&pattern)
             };
 
-            let size = metadata.is_file().then_some(metadata.size());
+            if is_match && let Some(item) = directory_item(entry.path(), &names) {
+                if results.len() == MAX_SEARCH_RESULTS {
+                    truncated = true;
+                    break 'search;
+                }
+                results.push(item);
+            }
 
-            Some(DirectoryItemInfo { path, kind, size })
-        })
+            // The entry's file type doesn't follow symlinks, so linked folders can't cause loops
+            if depth < MAX_SEARCH_DEPTH && entry.file_type().is_ok_and(|typ| typ.is_dir()) {
+                dirs.push((entry.path(), depth + 1));
+            }
+        }
+    }
+
+    results.sort_by(|a, b| a.path.cmp(&b.path));
+
+    SearchResponse { results, truncated }
+}
+
+pub fn stat(ctx: BackendContext, path: String) -> Option<DirectoryItemInfo> {
+    // The metadata is read through symlinks, so it's the target that has to be allowed
+    check_path(&ctx, &path, true).ok()?;
+
+    directory_item(PathBuf::from(path), &IdNames::load())
+}
+
+fn checksum_helper(path: String) -> io::Result<ChecksumResponse> {
+    let mut file = fs::File::open(path)?;
+
+    let mut ctx = digest::Context::new(&digest::SHA256);
+    let mut buf = vec![0; CHECKSUM_CHUNK_SIZE];
+    let mut size = 0;
+
+    // Hash the file a chunk at a time, so that large files don't have to fit in memory
+    loop {
+        let n = match file.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        ctx.update(&buf[..n]);
+        size += n as u64;
+    }
+
+    let sha256 = ctx
+        .finish()
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
         .collect();
 
-    Some(DirectoryResponse { dir_list })
+    Ok(ChecksumResponse { sha256, size })
+}
+
+pub fn checksum(ctx: BackendContext, path: String) -> Option<ChecksumResponse> {
+    check_path(&ctx, &path, true).ok()?;
+
+    checksum_helper(path).ok()
+}
+
+fn is_previewable(mime: &Mime) -> bool {
+    mime.type_() == mime::IMAGE && PREVIEW_FORMATS.contains(&mime.subtype().as_str())
+}
+
+// Goes by the extension alone, so that anything else is turned away without being read
+pub fn preview_mime(path: &str) -> Result<Mime, String> {
+    mime_guess::from_path(path)
+        .first()
+        .filter(is_previewable)
+        .ok_or_else(|| "not an image that can be previewed".into())
+}
+
+pub fn check_preview_size(size: u64) -> Result<(), String> {
+    if size > MAX_PREVIEW_SIZE {
+        return Err(format!(
+            "image is too large to preview ({} MiB, at most {} MiB), download it instead",
+            size.div_ceil(1024 * 1024),
+            MAX_PREVIEW_SIZE / 1024 / 1024
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn thumbnail(ctx: BackendContext, action: ThumbnailAction) -> Result<Vec<u8>, String> {
+    check_path(&ctx, &action.path, true).map_err(|err| err.to_string())?;
+    preview_mime(&action.path)?;
+
+    let size = fs::metadata(&action.path)
+        .map_err(|err| format!("failed to read image: {err}"))?
+        .len();
+    check_preview_size(size)?;
+
+    // The extension only decided whether to try, the contents decide how to decode it
+    let mut reader = ImageReader::open(&action.path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|err| format!("failed to read image: {err}"))?;
+
+    let mut limits = Limits::default();
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+    reader.limits(limits);
+
+    let image = reader
+        .decode()
+        .map_err(|err| format!("failed to decode image: {err}"))?;
+
+    let size = action.size.clamp(1, MAX_THUMBNAIL_SIZE);
+    let mut buf = io::Cursor::new(Vec::new());
+
+    // JPEG has no alpha channel, so it has to be dropped first
+    image
+        .thumbnail(size, size)
+        .into_rgb8()
+        .write_to(&mut buf, ImageFormat::Jpeg)
+        .map_err(|err| format!("failed to encode thumbnail: {err}"))?;
+
+    Ok(buf.into_inner())
 }
 
-pub fn list_directory(_ctx: BackendContext, path: String) -> DirectoryResponse {
-    list_directory_helper(path).unwrap_or_default()
+fn read_file_helper(path: &str) -> io::Result<Result<String, String>> {
+    let file = fs::File::open(path)?;
+    let size = file.metadata()?.len();
+
+    if size > MAX_OPEN_SIZE {
+        return Ok(Err(format!(
+            "file is too large to open ({} MiB, at most {} MiB), download it instead",
+            size.div_ceil(1024 * 1024),
+            MAX_OPEN_SIZE / 1024 / 1024
+        )));
+    }
+
+    // Files like logs can grow after the size was checked, so never read past the limit
+    let mut buf = Vec::new();
+    file.take(MAX_OPEN_SIZE).read_to_end(&mut buf)?;
+
+    Ok(String::from_utf8(buf).map_err(|_| "not a text file, download it instead".into()))
 }
 
-pub fn read_file(_ctx: BackendContext, path: String) -> Vec<u8> {
-    fs::read(path).unwrap_or_default()
+pub fn read_file(ctx: BackendContext, path: String) -> Result<String, String> {
+    check_path(&ctx, &path, true).map_err(|err| err.to_string())?;
+
+    read_file_helper(&path).map_err(|err| format!("failed to read file: {err}"))?
 }
 
 pub fn read_config_helper() -> Option<String> {
@@ -399,3 +1299,172 @@ pub fn read_config_helper() -> Option<String> {
 pub fn read_config(_ctx: BackendContext) -> String {
     read_config_helper().unwrap_or_default()
 }
+
+pub fn read_dietpi_txt() -> HashMap<String, String> {
+    let Ok(file) = fs::read_to_string(DIETPI_TXT_PATH) else {
+        return HashMap::new();
+    };
+
+    file.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+pub fn available_governors() -> Vec<String> {
+    fs::read_to_string("/sys/devices/system/cpu/cpu0/cpufreq/scaling_available_governors")
+        .map(|governors| governors.split_whitespace().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+// Everything that's currently swapped to, whether or not DietPi set it up
+fn swap_devices() -> Vec<SwapDevice> {
+    let Ok(swaps) = fs::read_to_string("/proc/swaps") else {
+        return Vec::new();
+    };
+
+    // Skip the header line, sizes are in KiB
+    swaps
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let path = fields.next()?;
+            let kind = fields.next()?;
+            let size: u64 = fields.next()?.parse().ok()?;
+            let used: u64 = fields.next()?.parse().ok()?;
+            let priority = fields.next()?.parse().ok()?;
+
+            // zram devices show up as ordinary partitions
+            let kind = if path.starts_with("/dev/zram") {
+                "zram"
+            } else {
+                kind
+            };
+
+            Some(SwapDevice {
+                path: path.into(),
+                kind: kind.into(),
+                size: size.saturating_mul(1024),
+                used: used.saturating_mul(1024),
+                priority,
+            })
+        })
+        .collect()
+}
+
+pub fn dietpi_settings(_ctx: BackendContext) -> DietPiSettingsResponse {
+    let settings = read_dietpi_txt();
+
+    DietPiSettingsResponse {
+        swap_size: settings
+            .get("AUTO_SETUP_SWAPFILE_SIZE")
+            .and_then(|size| size.parse().ok()),
+        swap_location: settings.get("AUTO_SETUP_SWAPFILE_LOCATION").cloned(),
+        swap_devices: swap_devices(),
+        cpu_governor: settings.get("CONFIG_CPU_GOVERNOR").cloned(),
+        available_governors: available_governors(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pretty_arch_maps_machine_names() {
+        let cases = [
+            ("x86_64", "x86_64"),
+            ("amd64", "x86_64"),
+            ("i386", "x86"),
+            ("i486", "x86"),
+            ("i586", "x86"),
+            ("i686", "x86"),
+            ("aarch64", "aarch64"),
+            ("arm64", "aarch64"),
+            ("armv7l", "armv7"),
+            ("armv7", "armv7"),
+            ("armv8l", "armv7"),
+            ("armv6l", "armv6"),
+            ("armv6", "armv6"),
+            ("riscv64", "riscv64"),
+        ];
+
+        for (arch, expected) in cases {
+            assert_eq!(pretty_arch(arch), expected, "for {arch}");
+        }
+    }
+
+    #[test]
+    fn pretty_arch_falls_back() {
+        assert_eq!(pretty_arch(""), "unknown");
+        // Anything else is shown as the kernel reports it
+        assert_eq!(pretty_arch("loongarch64"), "loongarch64");
+    }
+
+    #[test]
+    fn parse_services_status_output() {
+        // As printed by dietpi-services, with the colours that get stripped before parsing
+        let stdout = concat!(
+            "\x1b[90m[\x1b[32m  OK  \x1b[90m]\x1b[0m DietPi-Services | cron\tactive (running) since Mon 2024-01-01 12:00:00 UTC; 1 day ago\n",
+            "[  OK  ] DietPi-Services | fake-hwclock\tactive (exited) since Mon 2024-01-01 11:59:58 UTC; 1 day ago\n",
+            "[ INFO ] DietPi-Services | nginx\tinactive (dead)\n",
+            "[ INFO ] DietPi-Services | Stopping services\n",
+            "[  OK  ] DietPi-Services | ssh\tactivating (auto-restart) since Mon 2024-01-01 12:01:00 UTC; 5s ago\n",
+        );
+        let stderr = concat!(
+            "[FAILED] DietPi-Services | × php8.2-fpm.service - The PHP 8.2 FastCGI Process Manager\n",
+            "     Loaded: loaded (/lib/systemd/system/php8.2-fpm.service; enabled; preset: enabled)\n",
+            "     Active: failed (Result: exit-code) since Mon 2024-01-01 12:00:01 UTC; 1 day ago\n",
+            "\n",
+            "Jan 01 12:00:01 DietPi php-fpm8.2[512]: ERROR: unable to bind listening socket\n",
+        );
+
+        let stdout = remove_escape_codes(stdout.bytes());
+        let stdout = std::str::from_utf8(&stdout).unwrap();
+
+        let services = parse_services(stdout, stderr);
+        let summary: Vec<_> = services
+            .iter()
+            .map(|service| {
+                (
+                    service.name.as_str(),
+                    service.status,
+                    service.start.as_str(),
+                )
+            })
+            .collect();
+
+        assert!(
+            matches!(
+                summary.as_slice(),
+                [
+                    (
+                        "cron",
+                        ServiceStatus::Active,
+                        "Mon 2024-01-01 12:00:00 UTC; 1 day ago"
+                    ),
+                    (
+                        "fake-hwclock",
+                        ServiceStatus::Active,
+                        "Mon 2024-01-01 11:59:58 UTC; 1 day ago"
+                    ),
+                    ("nginx", ServiceStatus::Inactive, ""),
+                    (
+                        "ssh",
+                        ServiceStatus::Unknown,
+                        "Mon 2024-01-01 12:01:00 UTC; 5s ago"
+                    ),
+                    ("php8.2-fpm", ServiceStatus::Failed, ""),
+                ]
+            ),
+            "{summary:?}"
+        );
+        assert_eq!(
+            services[4].err_log,
+            "Jan 01 12:00:01 DietPi php-fpm8.2[512]: ERROR: unable to bind listening socket\n"
+        );
+    }
+}
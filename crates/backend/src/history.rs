@@ -0,0 +1,79 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use proto::backend::{CpuResponse, HistoryResponse};
+
+use crate::{client::BackendContext, getters};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+const HISTORY_LEN: usize = 60;
+
+pub type SharedHistory = Arc<Mutex<History>>;
+
+#[derive(Default)]
+pub struct History {
+    // The most recent sample is kept whole, so that CPU requests can be answered from it
+    latest_cpu: Option<CpuResponse>,
+    // Newest first
+    cpu: VecDeque<f32>,
+    temp: VecDeque<f32>,
+}
+
+impl History {
+    fn push(&mut self, cpu: CpuResponse, temp: Option<f32>) {
+        self.cpu.push_front(cpu.global_cpu);
+        self.cpu.truncate(HISTORY_LEN);
+
+        if let Some(temp) = temp {
+            self.temp.push_front(temp);
+            self.temp.truncate(HISTORY_LEN);
+        }
+
+        self.latest_cpu = Some(cpu);
+    }
+}
+
+// Samples on a fixed schedule, so that the history keeps going even with nobody watching, and so
+// that it doesn't depend on how often or how many clients ask for it
+pub async fn sample(ctx: BackendContext) {
+    let start = tokio::time::Instant::now() + SAMPLE_INTERVAL;
+    let mut interval = tokio::time::interval_at(start, SAMPLE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let sample_ctx = ctx.clone();
+        let (cpu, temp) = tokio::task::spawn_blocking(move || {
+            (
+                getters::cpu(sample_ctx.clone()),
+                getters::temp(sample_ctx).temp,
+            )
+        })
+        .await
+        .unwrap();
+
+        ctx.history.lock().unwrap().push(cpu, temp);
+    }
+}
+
+// CPU usage is measured between two refreshes, so refreshing for every request as well would
+// leave some readings covering only a few milliseconds
+pub fn cpu(ctx: BackendContext) -> CpuResponse {
+    let latest = ctx.history.lock().unwrap().latest_cpu.clone();
+
+    // Only before the first sample has been taken
+    latest.unwrap_or_else(|| getters::cpu(ctx))
+}
+
+pub fn history(ctx: BackendContext) -> HistoryResponse {
+    let history = ctx.history.lock().unwrap();
+
+    HistoryResponse {
+        interval: SAMPLE_INTERVAL.as_secs() as u32,
+        cpu: history.cpu.iter().copied().collect(),
+        temp: history.temp.iter().copied().collect(),
+    }
+}
@@ -1,6 +1,7 @@
 use std::{
+    collections::HashMap,
     sync::{Arc, Mutex},
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::{Context, Result};
@@ -8,16 +9,23 @@ use client::{BackendClient, BackendContext, SystemComponents};
 use config::{
     APP_VERSION,
     backend::{BackendConfig, get_config},
+    logging::{LogOptions, init_logger},
+    runtime::build_runtime,
 };
 use log::{error, info};
+use network_usage::NetworkUsage;
 use proto::backend::BackendMessage;
-use simple_logger::SimpleLogger;
-use terminal::Terminal;
+use streams::StreamRegistry;
 use tokio::sync::mpsc;
 
 mod actions;
 mod client;
+mod docker;
 mod getters;
+mod history;
+mod network_usage;
+mod sandbox;
+mod streams;
 mod terminal;
 
 pub type SharedConfig = Arc<BackendConfig>;
@@ -30,38 +38,53 @@ async fn run_client(
     client.run().await
 }
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
+    // Compared against the boot time, so that a backend that keeps restarting stands out
+    let start_time = SystemTime::now();
+
     let config = Arc::new(get_config().context("failed to get config")?);
 
-    SimpleLogger::new()
-        .with_level(config.log_level)
-        .init()
-        .unwrap();
+    init_logger(LogOptions {
+        level: config.log_level,
+        filters: &config.log_filters,
+        format: config.log_format,
+        file: &config.log_file,
+        max_files: config.log_max_files,
+    })?;
 
     info!("Starting DietPi-Dashboard backend v{APP_VERSION}...");
 
+    build_runtime(config.worker_threads)
+        .context("failed to start async runtime")?
+        .block_on(run(config, start_time))
+}
+
+async fn run(config: SharedConfig, start_time: SystemTime) -> Result<()> {
     info!("Connecting to {}", config.frontend_addr);
 
-    let (term_tx, term_rx) = mpsc::unbounded_channel();
     let (socket_tx, mut socket_rx) = mpsc::unbounded_channel();
 
-    let terminal = Terminal::new(socket_tx.clone(), term_rx);
-    match terminal {
-        Ok(terminal) => {
-            tokio::spawn(terminal.run());
-        }
-        Err(err) => error!("terminal failed to start: {err:?}"),
-    }
-
     let system = Arc::new(Mutex::new(SystemComponents::new()));
+    let streams = Arc::new(Mutex::new(StreamRegistry::new()));
+    let software = Arc::new(Mutex::new(None));
+    let history = Arc::new(Mutex::new(Default::default()));
+    let terminals = Arc::new(Mutex::new(HashMap::new()));
+    let network_usage = Arc::new(Mutex::new(NetworkUsage::load()));
     let context = BackendContext {
         config,
         system,
-        term_tx,
+        streams,
+        software,
+        history,
+        terminals,
+        network_usage,
         socket_tx,
+        start_time,
     };
 
+    tokio::spawn(history::sample(context.clone()));
+    tokio::spawn(network_usage::track(context.clone()));
+
     let mut errors = 0;
     let mut last_attempt = Instant::now();
 
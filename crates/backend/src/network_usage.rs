@@ -0,0 +1,207 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+use proto::backend::NetworkUsageResponse;
+use sysinfo::System;
+
+use crate::client::BackendContext;
+
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+const USAGE_FILE: &str = "network-usage.txt";
+
+pub type SharedNetworkUsage = Arc<Mutex<NetworkUsage>>;
+
+// The kernel's counters start over on every boot, so usage is added up from them as they go, and
+// saved next to the config file, so that it can cover a whole billing period
+pub struct NetworkUsage {
+    since: u64,
+    sent: u64,
+    recv: u64,
+    // The counters as of the last update, and the boot they were read during
+    boot_time: u64,
+    last_sent: u64,
+    last_recv: u64,
+}
+
+impl NetworkUsage {
+    fn new() -> Self {
+        let (last_sent, last_recv) = read_counters().unwrap_or_default();
+
+        Self {
+            since: unix_now(),
+            sent: 0,
+            recv: 0,
+            boot_time: System::boot_time(),
+            last_sent,
+            last_recv,
+        }
+    }
+
+    pub fn load() -> Self {
+        let saved = usage_path()
+            .and_then(fs::read_to_string)
+            .ok()
+            .and_then(|file| Self::parse(&file));
+
+        let Some(mut usage) = saved else {
+            return Self::new();
+        };
+
+        // Count anything that happened while the backend wasn't running
+        usage.update();
+        usage
+    }
+
+    fn parse(file: &str) -> Option<Self> {
+        let mut usage = Self {
+            since: 0,
+            sent: 0,
+            recv: 0,
+            boot_time: 0,
+            last_sent: 0,
+            last_recv: 0,
+        };
+
+        for line in file.lines() {
+            let (key, value) = line.split_once(' ')?;
+            let value = value.trim().parse().ok()?;
+
+            match key {
+                "since" => usage.since = value,
+                "sent" => usage.sent = value,
+                "recv" => usage.recv = value,
+                "boot_time" => usage.boot_time = value,
+                "last_sent" => usage.last_sent = value,
+                "last_recv" => usage.last_recv = value,
+                _ => {}
+            }
+        }
+
+        Some(usage)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let file = format!(
+            "since {}\nsent {}\nrecv {}\nboot_time {}\nlast_sent {}\nlast_recv {}\n",
+            self.since, self.sent, self.recv, self.boot_time, self.last_sent, self.last_recv
+        );
+
+        fs::write(usage_path()?, file)
+    }
+
+    fn update(&mut self) {
+        let Some((sent, recv)) = read_counters() else {
+            return;
+        };
+
+        let boot_time = System::boot_time();
+        if boot_time != self.boot_time {
+            self.boot_time = boot_time;
+            self.last_sent = 0;
+            self.last_recv = 0;
+        }
+
+        // An interface going away takes its counters with it, which shouldn't take away from the usage
+        self.sent += sent.saturating_sub(self.last_sent);
+        self.recv += recv.saturating_sub(self.last_recv);
+        self.last_sent = sent;
+        self.last_recv = recv;
+    }
+
+    fn response(&self) -> NetworkUsageResponse {
+        NetworkUsageResponse {
+            since: self.since,
+            sent: self.sent,
+            recv: self.recv,
+        }
+    }
+}
+
+fn usage_path() -> io::Result<PathBuf> {
+    let mut path = std::env::current_exe()?;
+    path.set_file_name(USAGE_FILE);
+    Ok(path)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// Returns the bytes sent and received by every interface but loopback, whose traffic never leaves
+// the system. Read straight from /proc, since refreshing sysinfo's networks here would shorten the
+// interval that the next network reading covers.
+fn read_counters() -> Option<(u64, u64)> {
+    let dev = fs::read_to_string("/proc/net/dev").ok()?;
+
+    let mut sent = 0;
+    let mut recv = 0;
+
+    // The first two lines are headers
+    for line in dev.lines().skip(2) {
+        let Some((name, counters)) = line.split_once(':') else {
+            continue;
+        };
+
+        if name.trim() == "lo" {
+            continue;
+        }
+
+        let counters: Vec<u64> = counters
+            .split_whitespace()
+            .filter_map(|x| x.parse().ok())
+            .collect();
+
+        if let (Some(&iface_recv), Some(&iface_sent)) = (counters.first(), counters.get(8)) {
+            recv += iface_recv;
+            sent += iface_sent;
+        }
+    }
+
+    Some((sent, recv))
+}
+
+pub async fn track(ctx: BackendContext) {
+    let start = tokio::time::Instant::now() + SAVE_INTERVAL;
+    let mut interval = tokio::time::interval_at(start, SAVE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let usage = ctx.network_usage.clone();
+        let saved = tokio::task::spawn_blocking(move || {
+            let mut usage = usage.lock().unwrap();
+            usage.update();
+            usage.save()
+        })
+        .await;
+
+        if let Ok(Err(err)) = saved {
+            warn!("Failed to save network usage: {err}");
+        }
+    }
+}
+
+pub fn usage(ctx: BackendContext) -> NetworkUsageResponse {
+    let mut usage = ctx.network_usage.lock().unwrap();
+    usage.update();
+    usage.response()
+}
+
+pub fn reset(ctx: BackendContext) -> Result<NetworkUsageResponse, String> {
+    let mut usage = ctx.network_usage.lock().unwrap();
+    *usage = NetworkUsage::new();
+
+    usage
+        .save()
+        .map_err(|err| format!("failed to write {USAGE_FILE}: {err}"))?;
+
+    Ok(usage.response())
+}
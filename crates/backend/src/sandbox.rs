@@ -0,0 +1,83 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+
+use crate::client::BackendContext;
+
+// Resolves everything but the last component, so that operations on a symlink itself (like
+// deleting it) act on the link and not on whatever it points to
+fn resolve_entry(path: &Path) -> io::Result<PathBuf> {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => Ok(fs::canonicalize(parent)?.join(name)),
+        // Either the root directory or a path ending in "..", which can't be a symlink
+        _ => fs::canonicalize(path),
+    }
+}
+
+// Checks that a path is inside one of the given folders. If `follow` is set, a symlink at the path
+// is resolved too, which is needed for anything that reads or writes through it.
+pub fn check_path_in(roots: &[String], path: &str, follow: bool) -> io::Result<()> {
+    let path = Path::new(path);
+
+    if !path.is_absolute() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "path must be absolute",
+        ));
+    }
+
+    let mut resolved = resolve_entry(path)?;
+
+    // Paths that don't exist yet are being created, so there's nothing more to resolve
+    if follow {
+        match fs::canonicalize(&resolved) {
+            Ok(canonical) => resolved = canonical,
+            // Unless it's a symlink to something that doesn't exist, which would be created
+            // wherever the link points
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                if fs::symlink_metadata(&resolved).is_ok_and(|x| x.is_symlink()) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        format!("{} is a broken symlink", resolved.display()),
+                    ));
+                }
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    let allowed = roots.iter().any(|root| {
+        let root = fs::canonicalize(root).unwrap_or_else(|_| root.into());
+        resolved.starts_with(root)
+    });
+
+    if !allowed {
+        warn!(
+            "Refusing to access {}, which is outside of the allowed paths",
+            resolved.display()
+        );
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is outside of the allowed paths", resolved.display()),
+        ));
+    }
+
+    Ok(())
+}
+
+// Checks against the folders the file browser is allowed to access
+pub fn check_path(ctx: &BackendContext, path: &str, follow: bool) -> io::Result<()> {
+    check_path_in(&ctx.config.allowed_paths, path, follow)
+}
+
+pub async fn check_path_async(ctx: &BackendContext, path: &str, follow: bool) -> io::Result<()> {
+    let (ctx, path) = (ctx.clone(), path.to_string());
+
+    // Canonicalizing can block on slow storage, and the runtime may only have a single thread
+    tokio::task::spawn_blocking(move || check_path(&ctx, &path, follow))
+        .await
+        .unwrap()
+}
@@ -0,0 +1,426 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    path::Path,
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use flate2::{Compression, write::GzEncoder};
+use log::error;
+use proto::{
+    backend::PreviewResponse,
+    frontend::{JournalAction, SoftwareAction, SoftwareCommand, TailAction},
+};
+use tokio::{io::AsyncReadExt, process::Command, sync::mpsc};
+
+use crate::{
+    client::BackendContext,
+    getters,
+    sandbox::{check_path_async, check_path_in},
+};
+
+// Limits for the configured chunk size, since tiny chunks would mean a request for every few bytes,
+// and huge ones would have to be held in memory all at once
+const MIN_CHUNK_SIZE: usize = 16 * 1024;
+// Frames are limited to 8 MiB, which also has to fit the rest of the message
+const MAX_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+const FOLLOW_READ_SIZE: usize = 8 * 1024;
+const SOFTWARE_READ_SIZE: usize = 8 * 1024;
+// How long software output can go unread before it's dropped instead
+const SOFTWARE_SEND_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub type SharedStreams = Arc<Mutex<StreamRegistry>>;
+
+// Streams are pulled one chunk at a time by the frontend, so that large transfers never have to be
+// held in memory all at once, and a slow client can't cause chunks to pile up
+pub struct StreamRegistry {
+    next_id: u32,
+    // Receivers are taken out while a chunk is being read, leaving `None` in their place
+    streams: HashMap<u32, Option<mpsc::Receiver<Vec<u8>>>>,
+}
+
+impl StreamRegistry {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            streams: HashMap::new(),
+        }
+    }
+
+    fn insert(&mut self, rx: mpsc::Receiver<Vec<u8>>) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+
+        self.streams.insert(id, Some(rx));
+
+        id
+    }
+
+    pub fn clear(&mut self) {
+        // Dropping the receivers makes any running producers exit
+        self.streams.clear();
+    }
+}
+
+// Batches writes into chunks, sending each one once it's full
+struct ChunkWriter {
+    buf: Vec<u8>,
+    chunk_size: usize,
+    tx: mpsc::Sender<Vec<u8>>,
+}
+
+impl ChunkWriter {
+    fn new(tx: mpsc::Sender<Vec<u8>>, chunk_size: usize) -> Self {
+        Self {
+            buf: Vec::with_capacity(chunk_size),
+            chunk_size,
+            tx,
+        }
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let len = data.len().min(self.chunk_size - self.buf.len());
+        self.buf.extend_from_slice(&data[..len]);
+
+        if self.buf.len() == self.chunk_size {
+            self.flush()?;
+        }
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        let chunk = std::mem::replace(&mut self.buf, Vec::with_capacity(self.chunk_size));
+
+        self.tx
+            .blocking_send(chunk)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "stream was closed"))
+    }
+}
+
+fn spawn_producer<F>(ctx: &BackendContext, producer: F) -> u32
+where
+    F: FnOnce(&mut ChunkWriter) -> io::Result<()> + Send + 'static,
+{
+    // Only allow one chunk to be buffered ahead of the one being sent
+    let (tx, rx) = mpsc::channel(1);
+
+    let chunk_size = (ctx.config.stream_chunk_size as usize).clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE);
+
+    tokio::task::spawn_blocking(move || {
+        let mut writer = ChunkWriter::new(tx, chunk_size);
+
+        let res = producer(&mut writer).and_then(|_| writer.flush());
+
+        // A broken pipe just means that the stream was closed early
+        if let Err(err) = res
+            && err.kind() != io::ErrorKind::BrokenPipe
+        {
+            error!("Failed to produce stream: {err}");
+        }
+    });
+
+    ctx.streams.lock().unwrap().insert(rx)
+}
+
+pub async fn download(ctx: BackendContext, path: String) -> Option<u32> {
+    check_path_async(&ctx, &path, true).await.ok()?;
+
+    // Open the file asynchronously, since opening can block on slow storage, and the runtime
+    // may only have a single thread
+    let mut file = tokio::fs::File::open(path).await.ok()?.into_std().await;
+
+    let id = spawn_producer(&ctx, move |writer| {
+        io::copy(&mut file, writer)?;
+        Ok(())
+    });
+
+    Some(id)
+}
+
+pub async fn preview(ctx: BackendContext, path: String) -> Result<PreviewResponse, String> {
+    check_path_async(&ctx, &path, true)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let mime = getters::preview_mime(&path)?;
+
+    let file = tokio::fs::File::open(path)
+        .await
+        .map_err(|err| format!("failed to open image: {err}"))?;
+    let size = file
+        .metadata()
+        .await
+        .map_err(|err| format!("failed to open image: {err}"))?
+        .len();
+    getters::check_preview_size(size)?;
+
+    // The file could grow after its size was checked, so never send more than was allowed
+    let mut file = file.into_std().await.take(size);
+
+    let stream_id = spawn_producer(&ctx, move |writer| {
+        io::copy(&mut file, writer)?;
+        Ok(())
+    });
+
+    Ok(PreviewResponse {
+        mime: mime.to_string(),
+        stream_id,
+    })
+}
+
+pub async fn download_archive(ctx: BackendContext, path: String) -> Option<u32> {
+    check_path_async(&ctx, &path, true).await.ok()?;
+
+    if !tokio::fs::metadata(&path).await.ok()?.is_dir() {
+        return None;
+    }
+
+    // Entries are stored under the folder's own name, so extracting doesn't spill into the
+    // current directory
+    let name = Path::new(&path)
+        .file_name()
+        .unwrap_or("root".as_ref())
+        .to_owned();
+
+    let id = spawn_producer(&ctx, move |writer| {
+        let encoder = GzEncoder::new(writer, Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+
+        // Store symlinks as links, since following them could pull in huge or endless files, or
+        // loop forever on a link back to a parent folder
+        builder.follow_symlinks(false);
+
+        builder.append_dir_all(name, &path)?;
+        builder.into_inner()?.finish()?;
+
+        Ok(())
+    });
+
+    Some(id)
+}
+
+pub async fn journal(ctx: BackendContext, action: JournalAction) -> Option<u32> {
+    // Only allow units from the services list, rather than anything on the system
+    let services = tokio::task::spawn_blocking(getters::services_helper)
+        .await
+        .unwrap()?;
+    if !services
+        .services
+        .iter()
+        .any(|service| service.name == action.unit)
+    {
+        return None;
+    }
+
+    let mut cmd = Command::new("journalctl");
+    cmd.args(["--follow", "--no-pager", "--output=short-iso"])
+        .arg(format!("--unit={}", action.unit))
+        .arg(format!("--lines={}", action.lines));
+    if let Some(since) = action.since {
+        cmd.arg(format!("--since={since}"));
+    }
+
+    follow_output(&ctx, cmd)
+}
+
+pub async fn tail_log(ctx: BackendContext, action: TailAction) -> Option<u32> {
+    let (roots, path) = (ctx.config.log_paths.clone(), action.path.clone());
+    tokio::task::spawn_blocking(move || check_path_in(&roots, &path, true))
+        .await
+        .unwrap()
+        .ok()?;
+
+    // Following by name reopens the file when it's rotated, and retrying keeps going through the
+    // moment where it's been moved away and not recreated yet
+    let mut cmd = Command::new("tail");
+    cmd.args(["--follow=name", "--retry"])
+        .arg(format!("--lines={}", action.lines))
+        .arg("--")
+        .arg(action.path);
+
+    follow_output(&ctx, cmd)
+}
+
+// Streams the output of a command that keeps following something until it's killed
+fn follow_output(ctx: &BackendContext, mut cmd: Command) -> Option<u32> {
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .ok()?;
+    let mut stdout = child.stdout.take()?;
+
+    let (tx, rx) = mpsc::channel(1);
+
+    // Following never finishes by itself, so this can't use a blocking producer, which would only
+    // notice the stream being closed once the next line is written
+    tokio::spawn(async move {
+        let mut buf = vec![0; FOLLOW_READ_SIZE];
+
+        loop {
+            tokio::select! {
+                _ = tx.closed() => break,
+                n = stdout.read(&mut buf) => {
+                    let Ok(n @ 1..) = n else {
+                        break;
+                    };
+
+                    if tx.send(buf[..n].to_vec()).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Dropping the child kills the command
+        drop(child);
+    });
+
+    Some(ctx.streams.lock().unwrap().insert(rx))
+}
+
+pub async fn software_action(ctx: BackendContext, action: SoftwareAction) -> Option<u32> {
+    let list = {
+        let ctx = ctx.clone();
+        tokio::task::spawn_blocking(move || getters::software(ctx))
+            .await
+            .unwrap()
+    };
+
+    let (arg, candidates) = match action.action {
+        SoftwareCommand::Install => ("install", &list.uninstalled),
+        SoftwareCommand::Uninstall => ("uninstall", &list.installed),
+    };
+
+    // Only allow IDs from the list, so that nothing else can be passed to dietpi-software
+    let valid = action
+        .software
+        .iter()
+        .all(|id| candidates.iter().any(|item| item.id == *id));
+    if action.software.is_empty() || !valid {
+        return None;
+    }
+
+    let mut child = Command::new("/boot/dietpi/dietpi-software")
+        .arg(arg)
+        .args(action.software.iter().map(u16::to_string))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let mut stdout = child.stdout.take()?;
+    let mut stderr = child.stderr.take()?;
+
+    let (tx, rx) = mpsc::channel(1);
+
+    tokio::spawn(async move {
+        let mut stdout_buf = vec![0; SOFTWARE_READ_SIZE];
+        let mut stderr_buf = vec![0; SOFTWARE_READ_SIZE];
+        let (mut stdout_open, mut stderr_open) = (true, true);
+        let mut tx = Some(tx);
+
+        while stdout_open || stderr_open {
+            let chunk = tokio::select! {
+                n = stdout.read(&mut stdout_buf), if stdout_open => match n {
+                    Ok(n @ 1..) => getters::remove_escape_codes(stdout_buf[..n].iter().copied()),
+                    _ => {
+                        stdout_open = false;
+                        continue;
+                    }
+                },
+                n = stderr.read(&mut stderr_buf), if stderr_open => match n {
+                    Ok(n @ 1..) => getters::remove_escape_codes(stderr_buf[..n].iter().copied()),
+                    _ => {
+                        stderr_open = false;
+                        continue;
+                    }
+                },
+            };
+
+            // Stopping dietpi-software partway through could leave things half installed, so it
+            // keeps running even once nobody's watching, and the rest of the output is dropped
+            if let Some(sender) = &tx
+                && !matches!(
+                    tokio::time::timeout(SOFTWARE_SEND_TIMEOUT, sender.send(chunk)).await,
+                    Ok(Ok(()))
+                )
+            {
+                tx = None;
+            }
+        }
+
+        let status = child.wait().await;
+
+        // The list has changed, so make sure it gets re-read
+        *ctx.software.lock().unwrap() = None;
+
+        if let (Some(tx), Ok(status)) = (tx, status) {
+            let msg = format!("\ndietpi-software finished ({status})\n");
+            let _ = tx.send(msg.into_bytes()).await;
+        }
+    });
+
+    Some(ctx.streams.lock().unwrap().insert(rx))
+}
+
+pub async fn read_stream(ctx: BackendContext, id: u32) -> Option<Vec<u8>> {
+    // Take the receiver out while waiting, so that the lock isn't held across an await
+    let mut rx = ctx.streams.lock().unwrap().streams.get_mut(&id)?.take()?;
+
+    let Some(chunk) = rx.recv().await else {
+        ctx.streams.lock().unwrap().streams.remove(&id);
+        return None;
+    };
+
+    // If the stream was closed while waiting, the receiver is dropped here instead of put back
+    if let Some(slot) = ctx.streams.lock().unwrap().streams.get_mut(&id) {
+        *slot = Some(rx);
+    }
+
+    Some(chunk)
+}
+
+pub fn close_stream(ctx: BackendContext, id: u32) {
+    ctx.streams.lock().unwrap().streams.remove(&id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_writer_sends_short_last_chunk() {
+        let chunk_size = MIN_CHUNK_SIZE;
+        // Two and a half chunks, plus a few bytes so that it isn't a round number either
+        let data: Vec<u8> = (0..chunk_size * 5 / 2 + 7).map(|x| x as u8).collect();
+
+        let (tx, mut rx) = mpsc::channel(4);
+        let mut writer = ChunkWriter::new(tx, chunk_size);
+
+        // The same as a download, which copies the file in and flushes whatever is left
+        io::copy(&mut data.as_slice(), &mut writer).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        let mut chunks = Vec::new();
+        while let Ok(chunk) = rx.try_recv() {
+            chunks.push(chunk);
+        }
+
+        let sizes: Vec<_> = chunks.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [chunk_size, chunk_size, chunk_size / 2 + 7]);
+        assert_eq!(sizes.iter().sum::<usize>(), data.len());
+        assert_eq!(chunks.concat(), data);
+    }
+}
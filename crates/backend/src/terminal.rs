@@ -1,4 +1,11 @@
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    sync::{Arc, Mutex},
+};
+
 use anyhow::{Context, Result};
+use config::backend::TerminalMode;
+use log::{error, warn};
 use proto::backend::{ActionBackendMessage, BackendMessage};
 use pty_process::{Command, Pty, Size};
 use tokio::{
@@ -6,42 +13,98 @@ use tokio::{
     sync::mpsc,
 };
 
-fn create_pty() -> Result<Pty> {
+use crate::{SharedConfig, client::BackendContext};
+
+pub type SharedTerminals = Arc<Mutex<HashMap<u32, mpsc::UnboundedSender<Vec<u8>>>>>;
+
+fn create_pty(config: &SharedConfig) -> Result<Pty> {
     let (pty, pts) = pty_process::open().context("failed to open pty")?;
     pty.resize(Size::new(24, 80))
         .context("failed to resize pty")?;
 
-    let cmd = Command::new("agetty").args(["-8", "-L", "-", "xterm-256color"]);
+    let cmd = match config.terminal_mode {
+        TerminalMode::Login => Command::new("agetty").args(["-8", "-L", "-", "xterm-256color"]),
+        TerminalMode::Sudo => Command::new("sudo")
+            .args(["--login", "--user", &config.terminal_user])
+            .env("TERM", "xterm-256color"),
+        TerminalMode::Custom => {
+            let (program, args) = config
+                .terminal_command
+                .split_first()
+                .context("terminal_command is empty")?;
+
+            let cmd = Command::new(program)
+                .args(args)
+                .env("TERM", "xterm-256color");
+
+            if config.terminal_dir.is_empty() {
+                cmd
+            } else {
+                cmd.current_dir(&config.terminal_dir)
+            }
+        }
+    };
+
+    let mut cmd = cmd;
+    for var in &config.terminal_env {
+        let Some((key, value)) = var.split_once('=') else {
+            warn!("Ignoring terminal environment variable {var:?}, which isn't NAME=value");
+            continue;
+        };
+
+        cmd = cmd.env(key, value);
+    }
+
     cmd.spawn(pts).context("failed to spawn terminal")?;
 
     Ok(pty)
 }
 
-pub struct Terminal {
+fn send(socket_tx: &mpsc::UnboundedSender<BackendMessage>, id: u32, data: Vec<u8>) {
+    let msg = ActionBackendMessage::Terminal(id, data);
+    let _ = socket_tx.send(BackendMessage::Action(msg));
+}
+
+struct Terminal {
+    id: u32,
+    config: SharedConfig,
     socket_tx: mpsc::UnboundedSender<BackendMessage>,
     rx: mpsc::UnboundedReceiver<Vec<u8>>,
     pty: Pty,
 }
 
 impl Terminal {
-    pub fn new(
+    fn new(
+        id: u32,
+        config: SharedConfig,
         socket_tx: mpsc::UnboundedSender<BackendMessage>,
         rx: mpsc::UnboundedReceiver<Vec<u8>>,
     ) -> Result<Self> {
-        let pty = create_pty()?;
+        let pty = create_pty(&config)?;
 
-        Ok(Self { socket_tx, rx, pty })
+        Ok(Self {
+            id,
+            config,
+            socket_tx,
+            rx,
+            pty,
+        })
     }
 
-    pub async fn run(mut self) {
+    fn send(&self, data: Vec<u8>) {
+        send(&self.socket_tx, self.id, data);
+    }
+
+    async fn run(mut self) {
         let mut buf = [0; 512];
 
         loop {
             loop {
                 tokio::select! {
                     data = self.rx.recv() => {
+                        // The session was closed, dropping the pty hangs up the shell
                         let Some(data): Option<Vec<u8>> = data else {
-                            break;
+                            return;
                         };
 
                         if self.pty.write_all(&data).await.is_err() {
@@ -57,22 +120,22 @@ impl Terminal {
                             break;
                         }
 
-                        let msg = ActionBackendMessage::Terminal(buf[..n].to_vec());
-                        let msg = BackendMessage::Action(msg);
-
-                        let _ = self.socket_tx.send(msg);
+                        self.send(buf[..n].to_vec());
                     }
                 }
             }
 
+            // A shell that exits straight away, like when sudo isn't allowed, would otherwise be
+            // restarted over and over. The session ends instead, and the next input starts a new one.
+            if self.config.terminal_mode == TerminalMode::Sudo {
+                self.send(b"\r\n[Shell exited, press any key to start a new one]\r\n".to_vec());
+                break;
+            }
+
             // Send escape sequence to clear terminal
-            let _ = self
-                .socket_tx
-                .send(BackendMessage::Action(ActionBackendMessage::Terminal(
-                    b"\x1Bc".to_vec(),
-                )));
+            self.send(b"\x1Bc".to_vec());
 
-            if let Ok(pty) = create_pty() {
+            if let Ok(pty) = create_pty(&self.config) {
                 self.pty = pty;
             } else {
                 break;
@@ -80,3 +143,50 @@ impl Terminal {
         }
     }
 }
+
+pub fn write(ctx: BackendContext, id: u32, data: Vec<u8>) {
+    let mut terminals = ctx.terminals.lock().unwrap();
+
+    // The shell has exited, so start a new one in its place
+    if terminals.get(&id).is_some_and(|tx| tx.is_closed()) {
+        terminals.remove(&id);
+    }
+
+    // Sessions are started the first time they're written to
+    let tx = match terminals.entry(id) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => {
+            let (tx, rx) = mpsc::unbounded_channel();
+
+            match Terminal::new(id, ctx.config.clone(), ctx.socket_tx.clone(), rx) {
+                Ok(terminal) => {
+                    tokio::spawn(terminal.run());
+                    entry.insert(tx)
+                }
+                Err(err) => {
+                    error!("terminal failed to start: {err:?}");
+                    // Show why in the terminal itself, rather than leaving it blank
+                    let msg = format!("\r\n[Failed to start terminal: {err:#}]\r\n");
+                    send(&ctx.socket_tx, id, msg.into_bytes());
+                    return;
+                }
+            }
+        }
+    };
+
+    // The terminal exited and couldn't be restarted
+    if tx.send(data).is_err() {
+        terminals.remove(&id);
+    }
+}
+
+pub fn close(ctx: BackendContext, id: u32) {
+    ctx.terminals.lock().unwrap().remove(&id);
+}
+
+pub fn list(ctx: BackendContext) -> Vec<u32> {
+    let mut ids: Vec<_> = ctx.terminals.lock().unwrap().keys().copied().collect();
+    ids.sort_unstable();
+
+    ids
+}
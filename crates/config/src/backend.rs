@@ -1,14 +1,16 @@
 use std::net::SocketAddr;
+use std::path::PathBuf;
 
 use anyhow::Result;
 use log::LevelFilter;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use toml_migrate::build_migration_chain;
 
 use crate::custom_serde::HexArray;
 use crate::generate_config_file;
+use crate::logging::LogFormat;
 
-pub type BackendConfig = BackendConfigV1;
+pub type BackendConfig = BackendConfigV2;
 
 pub fn get_config() -> Result<BackendConfig> {
     crate::read_config("config-backend.toml", generate_config_file)
@@ -18,14 +20,162 @@ fn generate_config_file(config: &BackendConfig) -> String {
     generate_config_file!(
         "config-backend.template.toml",
         log_level = config.log_level,
+        log_filters = config.log_filters,
+        log_format = config.log_format,
+        log_file = config.log_file,
+        log_max_files = config.log_max_files,
+        worker_threads = config.worker_threads,
         frontend_addr = config.frontend_addr,
         nickname = config.nickname,
         secret = config.secret,
-        disks = config.disks
+        disks = config.disks,
+        stream_chunk_size = config.stream_chunk_size,
+        allowed_paths = config.allowed_paths,
+        log_paths = config.log_paths,
+        pinned_services = config.pinned_services,
+        terminal_mode = config.terminal_mode,
+        terminal_user = config.terminal_user,
+        terminal_command = config.terminal_command,
+        terminal_dir = config.terminal_dir,
+        terminal_env = config.terminal_env,
+        enable_power_actions = config.enable_power_actions,
+        power_action_delay = config.power_action_delay,
+        enable_drop_caches = config.enable_drop_caches,
+        commands = CommandsTable {
+            commands: &config.commands
+        }
     )
 }
 
-build_migration_chain!(BackendConfigV0 = 0, BackendConfigV1 = 1);
+build_migration_chain!(
+    BackendConfigV0 = 0,
+    BackendConfigV1 = 1,
+    BackendConfigV2 = 2
+);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TerminalMode {
+    // A login prompt, like on a physical console
+    #[default]
+    Login,
+    // A shell as `terminal_user`, without having to log in
+    Sudo,
+    // `terminal_command`, as the user the backend runs as
+    Custom,
+}
+
+// A command that can be run by name, without anything from the request ending up in it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomCommand {
+    pub name: String,
+    // The program followed by its arguments, run directly without a shell
+    pub argv: Vec<String>,
+}
+
+// Arrays of tables can't be written as a plain value, so they go at the end of the file under
+// their own key
+#[derive(Serialize)]
+struct CommandsTable<'a> {
+    #[serde(skip_serializing_if = "<[CustomCommand]>::is_empty")]
+    commands: &'a [CustomCommand],
+}
+
+#[derive(Deserialize)]
+pub struct BackendConfigV2 {
+    pub log_level: LevelFilter,
+    pub log_filters: String,
+    pub log_format: LogFormat,
+    pub log_file: PathBuf,
+    pub log_max_files: u32,
+    pub worker_threads: usize,
+    pub frontend_addr: SocketAddr,
+    pub nickname: String,
+    pub secret: HexArray<32>,
+    pub disks: Vec<String>,
+    pub stream_chunk_size: u32,
+    pub allowed_paths: Vec<String>,
+    pub log_paths: Vec<String>,
+    pub pinned_services: Vec<String>,
+    pub terminal_mode: TerminalMode,
+    pub terminal_user: String,
+    pub terminal_command: Vec<String>,
+    pub terminal_dir: String,
+    pub terminal_env: Vec<String>,
+    pub enable_power_actions: bool,
+    pub power_action_delay: u64,
+    pub enable_drop_caches: bool,
+    // Left out of the file entirely when there aren't any
+    #[serde(default)]
+    pub commands: Vec<CustomCommand>,
+}
+
+impl Default for BackendConfigV2 {
+    fn default() -> Self {
+        Self {
+            log_level: LevelFilter::Info,
+            log_filters: String::new(),
+            log_format: LogFormat::Text,
+            log_file: PathBuf::new(),
+            log_max_files: 7,
+            worker_threads: 0,
+            frontend_addr: ([127, 0, 0, 1], 5253).into(),
+            nickname: String::new(),
+            secret: HexArray(rand::random()),
+            disks: vec!["/".into()],
+            stream_chunk_size: 1024 * 1024,
+            allowed_paths: vec![
+                "/root".into(),
+                "/home".into(),
+                "/mnt".into(),
+                "/boot/dietpi".into(),
+            ],
+            log_paths: vec!["/var/log".into()],
+            pinned_services: Vec::new(),
+            terminal_mode: TerminalMode::Login,
+            terminal_user: "dietpi".into(),
+            terminal_command: vec!["/bin/bash".into(), "--login".into()],
+            terminal_dir: String::new(),
+            terminal_env: Vec::new(),
+            enable_power_actions: false,
+            power_action_delay: 5,
+            enable_drop_caches: false,
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl From<BackendConfigV1> for BackendConfigV2 {
+    fn from(val: BackendConfigV1) -> Self {
+        let default = Self::default();
+
+        Self {
+            log_level: val.log_level,
+            log_filters: default.log_filters,
+            log_format: default.log_format,
+            log_file: default.log_file,
+            log_max_files: default.log_max_files,
+            worker_threads: default.worker_threads,
+            frontend_addr: val.frontend_addr,
+            nickname: val.nickname,
+            secret: val.secret,
+            disks: val.disks,
+            stream_chunk_size: default.stream_chunk_size,
+            allowed_paths: default.allowed_paths,
+            log_paths: default.log_paths,
+            pinned_services: default.pinned_services,
+            terminal_mode: default.terminal_mode,
+            terminal_user: default.terminal_user,
+            terminal_command: default.terminal_command,
+            terminal_dir: default.terminal_dir,
+            terminal_env: default.terminal_env,
+            enable_power_actions: default.enable_power_actions,
+            power_action_delay: default.power_action_delay,
+            enable_drop_caches: default.enable_drop_caches,
+            commands: default.commands,
+        }
+    }
+}
 
 #[derive(Deserialize)]
 pub struct BackendConfigV1 {
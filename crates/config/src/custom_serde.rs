@@ -1,3 +1,6 @@
+#[cfg(feature = "frontend")]
+use std::{fmt, net::IpAddr, str::FromStr};
+
 use data_encoding::HEXLOWER;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
@@ -35,3 +38,84 @@ impl<'de, const N: usize> Deserialize<'de> for HexArray<N> {
         Ok(Self(arr))
     }
 }
+
+// A range of IPs in CIDR notation, or a single IP if the prefix length is left out
+#[cfg(feature = "frontend")]
+#[derive(Debug, Clone, Copy)]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+#[cfg(feature = "frontend")]
+impl IpRange {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let (net, ip, len) = match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => (net.to_bits().into(), ip.to_bits().into(), 32),
+            (IpAddr::V6(net), IpAddr::V6(ip)) => (net.to_bits(), ip.to_bits(), 128),
+            _ => return false,
+        };
+
+        // Shifting out every bit (a /0 range) isn't allowed, but it would leave nothing to compare
+        let shift = len - self.prefix;
+        net.checked_shr(shift).unwrap_or(0) == ip.checked_shr(shift).unwrap_or(0)
+    }
+}
+
+#[cfg(feature = "frontend")]
+impl FromStr for IpRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+
+        let addr = addr
+            .parse::<IpAddr>()
+            .map_err(|err| format!("invalid IP {addr}: {err}"))?
+            .to_canonical();
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse()
+                .ok()
+                .filter(|&prefix| prefix <= max)
+                .ok_or_else(|| format!("invalid prefix length {prefix}, expected 0 to {max}"))?,
+            None => max,
+        };
+
+        Ok(Self { addr, prefix })
+    }
+}
+
+#[cfg(feature = "frontend")]
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+#[cfg(feature = "frontend")]
+impl Serialize for IpRange {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        ser.collect_str(self)
+    }
+}
+
+#[cfg(feature = "frontend")]
+impl<'de> Deserialize<'de> for IpRange {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let str = String::deserialize(de)?;
+
+        str.parse().map_err(serde::de::Error::custom)
+    }
+}
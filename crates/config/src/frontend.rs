@@ -1,18 +1,58 @@
 use std::net::{IpAddr, Ipv6Addr};
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use log::LevelFilter;
 use serde::Deserialize;
 use toml_migrate::build_migration_chain;
 
-use crate::custom_serde::HexArray;
+use crate::custom_serde::{HexArray, IpRange};
 use crate::generate_config_file;
+use crate::logging::LogFormat;
 
-pub type FrontendConfig = FrontendConfigV1;
+pub type FrontendConfig = FrontendConfigV2;
 
 pub fn get_config() -> Result<FrontendConfig> {
-    crate::read_config("config-frontend.toml", generate_config_file)
+    let config = crate::read_config("config-frontend.toml", generate_config_file)?;
+    check_config(&config)?;
+    Ok(config)
+}
+
+pub fn save_config_file(config_file: &str) -> Result<()> {
+    crate::save_config("config-frontend.toml", config_file, check_config)
+}
+
+// For options that parse fine on their own, but can't be used together
+fn check_config(config: &FrontendConfig) -> Result<()> {
+    // Certificates from ACME are served without a client verifier
+    if config.enable_acme && config.enable_client_auth {
+        return Err(anyhow!(
+            "enable_client_auth can't be used together with enable_acme"
+        ));
+    }
+
+    Ok(())
+}
+
+// Only for the first-run setup, so a password that has already been set is never replaced
+pub fn set_initial_hash(hash: &str) -> Result<()> {
+    crate::update_config(
+        "config-frontend.toml",
+        generate_config_file,
+        |config: &mut FrontendConfig| {
+            if !config.hash.is_empty() {
+                return Err(anyhow!("a password has already been set"));
+            }
+
+            config.hash = hash.into();
+            Ok(())
+        },
+    )
+}
+
+// Includes the defaults for anything missing from the file, since that's what is actually in effect
+pub fn effective_config_file(config: &FrontendConfig) -> String {
+    crate::redact_config_file(&generate_config_file(config), &["hash", "secret"])
 }
 
 fn generate_config_file(config: &FrontendConfig) -> String {
@@ -20,19 +60,208 @@ fn generate_config_file(config: &FrontendConfig) -> String {
         "config-frontend.template.toml",
         http_port = config.http_port,
         http_subnet = config.http_subnet,
+        base_path = config.base_path,
+        trusted_proxies = config.trusted_proxies,
+        allowed_ips = config.allowed_ips,
+        allowed_origins = config.allowed_origins,
+        frame_options = config.frame_options,
+        referrer_policy = config.referrer_policy,
+        content_security_policy = config.content_security_policy,
+        http_read_timeout = config.http_read_timeout,
         backend_port = config.backend_port,
         backend_subnet = config.backend_subnet,
         log_level = config.log_level,
+        log_filters = config.log_filters,
+        log_format = config.log_format,
+        log_file = config.log_file,
+        log_max_files = config.log_max_files,
+        worker_threads = config.worker_threads,
         enable_tls = config.enable_tls,
         key_path = config.key_path,
         cert_path = config.cert_path,
+        enable_client_auth = config.enable_client_auth,
+        client_ca_path = config.client_ca_path,
+        tls_alpn_protocols = config.tls_alpn_protocols,
+        enable_acme = config.enable_acme,
+        acme_domains = config.acme_domains,
+        acme_contact = config.acme_contact,
+        acme_cache_dir = config.acme_cache_dir,
         enable_login = config.enable_login,
         hash = config.hash,
-        secret = config.secret
+        secret = config.secret,
+        enable_metrics = config.enable_metrics,
+        terminal_timeout = config.terminal_timeout,
+        terminal_buffer_size = config.terminal_buffer_size,
+        idle_timeout = config.idle_timeout,
+        terminal_lock_timeout = config.terminal_lock_timeout,
+        websocket_compression = config.websocket_compression,
+        websocket_max_size = config.websocket_max_size,
+        websocket_idle_timeout = config.websocket_idle_timeout,
+        max_upload_size = config.max_upload_size,
+        maintenance_file = config.maintenance_file,
+        audit_log_path = config.audit_log_path,
+        audit_log_max_size = config.audit_log_max_size,
+        audit_log_max_files = config.audit_log_max_files
     )
 }
 
-build_migration_chain!(FrontendConfigV0 = 0, FrontendConfigV1 = 1);
+build_migration_chain!(
+    FrontendConfigV0 = 0,
+    FrontendConfigV1 = 1,
+    FrontendConfigV2 = 2
+);
+
+#[derive(Deserialize)]
+pub struct FrontendConfigV2 {
+    pub http_port: u16,
+    pub http_subnet: IpAddr,
+    pub base_path: String,
+    pub trusted_proxies: Vec<IpAddr>,
+    pub allowed_ips: Vec<IpRange>,
+    pub allowed_origins: Vec<String>,
+    pub frame_options: String,
+    pub referrer_policy: String,
+    pub content_security_policy: String,
+    pub http_read_timeout: u64,
+    pub backend_port: u16,
+    pub backend_subnet: IpAddr,
+    pub log_level: LevelFilter,
+    pub log_filters: String,
+    pub log_format: LogFormat,
+    pub log_file: PathBuf,
+    pub log_max_files: u32,
+    pub worker_threads: usize,
+    pub enable_tls: bool,
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    pub enable_client_auth: bool,
+    pub client_ca_path: PathBuf,
+    pub tls_alpn_protocols: Vec<String>,
+    pub enable_acme: bool,
+    pub acme_domains: Vec<String>,
+    pub acme_contact: Vec<String>,
+    pub acme_cache_dir: PathBuf,
+    pub enable_login: bool,
+    pub hash: String,
+    pub secret: HexArray<32>,
+    pub enable_metrics: bool,
+    pub terminal_timeout: u64,
+    pub terminal_buffer_size: usize,
+    pub idle_timeout: u64,
+    pub terminal_lock_timeout: u64,
+    pub websocket_compression: bool,
+    pub websocket_max_size: usize,
+    pub websocket_idle_timeout: u64,
+    pub max_upload_size: u64,
+    pub maintenance_file: PathBuf,
+    pub audit_log_path: PathBuf,
+    pub audit_log_max_size: u64,
+    pub audit_log_max_files: u32,
+}
+
+impl Default for FrontendConfigV2 {
+    fn default() -> Self {
+        Self {
+            http_port: 5252,
+            http_subnet: IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            base_path: String::new(),
+            trusted_proxies: Vec::new(),
+            allowed_ips: vec!["0.0.0.0/0".parse().unwrap(), "::/0".parse().unwrap()],
+            allowed_origins: Vec::new(),
+            frame_options: "sameorigin".into(),
+            referrer_policy: "no-referrer".into(),
+            content_security_policy: String::new(),
+            http_read_timeout: 60,
+            backend_port: 5253,
+            backend_subnet: IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            log_level: LevelFilter::Info,
+            log_filters: String::new(),
+            log_format: LogFormat::Text,
+            log_file: PathBuf::new(),
+            log_max_files: 7,
+            worker_threads: 0,
+            enable_tls: false,
+            cert_path: PathBuf::new(),
+            key_path: PathBuf::new(),
+            enable_client_auth: false,
+            client_ca_path: PathBuf::new(),
+            tls_alpn_protocols: vec!["http/1.1".into()],
+            enable_acme: false,
+            acme_domains: Vec::new(),
+            acme_contact: Vec::new(),
+            acme_cache_dir: PathBuf::from("acme-cache"),
+            enable_login: false,
+            hash: String::new(),
+            secret: HexArray(rand::random()),
+            enable_metrics: false,
+            terminal_timeout: 3600,
+            terminal_buffer_size: 64 * 1024,
+            idle_timeout: 0,
+            terminal_lock_timeout: 0,
+            websocket_compression: false,
+            websocket_max_size: 16 * 1024 * 1024,
+            websocket_idle_timeout: 60,
+            max_upload_size: 0,
+            maintenance_file: "/run/dietpi-dashboard/maintenance".into(),
+            audit_log_path: PathBuf::new(),
+            audit_log_max_size: 10 * 1024 * 1024,
+            audit_log_max_files: 5,
+        }
+    }
+}
+
+impl From<FrontendConfigV1> for FrontendConfigV2 {
+    fn from(val: FrontendConfigV1) -> Self {
+        let default = Self::default();
+
+        Self {
+            http_port: val.http_port,
+            http_subnet: val.http_subnet,
+            base_path: default.base_path,
+            trusted_proxies: default.trusted_proxies,
+            allowed_ips: default.allowed_ips,
+            allowed_origins: default.allowed_origins,
+            frame_options: default.frame_options,
+            referrer_policy: default.referrer_policy,
+            content_security_policy: default.content_security_policy,
+            http_read_timeout: default.http_read_timeout,
+            backend_port: val.backend_port,
+            backend_subnet: val.backend_subnet,
+            log_level: val.log_level,
+            log_filters: default.log_filters,
+            log_format: default.log_format,
+            log_file: default.log_file,
+            log_max_files: default.log_max_files,
+            worker_threads: default.worker_threads,
+            enable_tls: val.enable_tls,
+            cert_path: val.cert_path,
+            key_path: val.key_path,
+            enable_client_auth: default.enable_client_auth,
+            client_ca_path: default.client_ca_path,
+            tls_alpn_protocols: default.tls_alpn_protocols,
+            enable_acme: default.enable_acme,
+            acme_domains: default.acme_domains,
+            acme_contact: default.acme_contact,
+            acme_cache_dir: default.acme_cache_dir,
+            enable_login: val.enable_login,
+            hash: val.hash,
+            secret: val.secret,
+            enable_metrics: default.enable_metrics,
+            terminal_timeout: default.terminal_timeout,
+            terminal_buffer_size: default.terminal_buffer_size,
+            idle_timeout: default.idle_timeout,
+            terminal_lock_timeout: default.terminal_lock_timeout,
+            websocket_compression: default.websocket_compression,
+            websocket_max_size: default.websocket_max_size,
+            websocket_idle_timeout: default.websocket_idle_timeout,
+            max_upload_size: default.max_upload_size,
+            maintenance_file: default.maintenance_file,
+            audit_log_path: default.audit_log_path,
+            audit_log_max_size: default.audit_log_max_size,
+            audit_log_max_files: default.audit_log_max_files,
+        }
+    }
+}
 
 #[derive(Deserialize)]
 pub struct FrontendConfigV1 {
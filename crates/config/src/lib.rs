@@ -1,4 +1,4 @@
-use std::{fs, io};
+use std::{fs, io, path::PathBuf};
 
 use anyhow::{Context, Result};
 use toml_migrate::Migrate;
@@ -7,11 +7,13 @@ use toml_migrate::Migrate;
 pub mod backend;
 #[cfg(feature = "frontend")]
 pub mod frontend;
+pub mod logging;
+pub mod runtime;
 
 mod custom_serde;
 
 pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
-pub const PROTOCOL_VERSION: u32 = 1;
+pub const PROTOCOL_VERSION: u32 = 3;
 
 macro_rules! generate_config_file {
     ($template:literal, $($key:ident = $val:expr),*) => {{
@@ -23,6 +25,20 @@ macro_rules! generate_config_file {
 
 pub(crate) use generate_config_file;
 
+// Blanks out the values of the given top-level keys, so that a config can be shared (like in a bug
+// report) without giving away anything that grants access
+#[cfg(feature = "frontend")]
+fn redact_config_file(config_file: &str, keys: &[&str]) -> String {
+    config_file
+        .lines()
+        .map(|line| match line.split_once(" = ") {
+            Some((key, _)) if keys.contains(&key) => format!("{key} = \"<redacted>\""),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[derive(serde::Deserialize)]
 struct Version {
     #[serde(rename = "CONFIG_VERSION_DO_NOT_CHANGE", default)]
@@ -35,12 +51,17 @@ impl toml_migrate::Version for Version {
     }
 }
 
+fn config_path(config_name: &str) -> Result<PathBuf> {
+    let mut cfgpath = std::env::current_exe().context("couldn't get path to executable")?;
+    cfgpath.set_file_name(config_name);
+    Ok(cfgpath)
+}
+
 fn read_config<T: Migrate + Default>(
     config_name: &str,
     config_file_generator: fn(&T) -> String,
 ) -> Result<T> {
-    let mut cfgpath = std::env::current_exe().context("couldn't get path to executable")?;
-    cfgpath.set_file_name(config_name);
+    let cfgpath = config_path(config_name)?;
 
     let config_str = match std::fs::read_to_string(&cfgpath) {
         Ok(config_str) => config_str,
@@ -64,3 +85,48 @@ fn read_config<T: Migrate + Default>(
 
     Ok(config)
 }
+
+// Parsed the same way as on startup before anything is written, so that an edit that would keep the
+// program from starting again is turned away instead
+#[cfg(feature = "frontend")]
+fn save_config<T: Migrate>(
+    config_name: &str,
+    config_file: &str,
+    check: fn(&T) -> Result<()>,
+) -> Result<()> {
+    let (config, _) =
+        toml_migrate::migrate_config::<T, Version>(config_file).context("invalid config")?;
+    check(&config).context("invalid config")?;
+
+    write_config(config_name, config_file)
+}
+
+// Changes the config file as it is now, rather than the config that was loaded on startup, so that
+// any edits made since then are kept
+#[cfg(feature = "frontend")]
+fn update_config<T: Migrate>(
+    config_name: &str,
+    config_file_generator: fn(&T) -> String,
+    update: impl FnOnce(&mut T) -> Result<()>,
+) -> Result<()> {
+    let config_str =
+        fs::read_to_string(config_path(config_name)?).context("failed to read config file")?;
+    let (mut config, _) = toml_migrate::migrate_config::<T, Version>(&config_str)
+        .context("failed to migrate config file")?;
+
+    update(&mut config)?;
+
+    write_config(config_name, &config_file_generator(&config))
+}
+
+#[cfg(feature = "frontend")]
+fn write_config(config_name: &str, config_file: &str) -> Result<()> {
+    let cfgpath = config_path(config_name)?;
+    // Written alongside and then moved into place, so that a failed write can't leave half a config
+    let tmppath = cfgpath.with_extension("toml.tmp");
+
+    fs::write(&tmppath, config_file).context("failed to write config file")?;
+    fs::rename(&tmppath, &cfgpath).context("failed to replace config file")?;
+
+    Ok(())
+}
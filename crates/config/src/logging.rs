@@ -0,0 +1,260 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, bail};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+pub struct LogOptions<'a> {
+    pub level: LevelFilter,
+    // Directives like "frontend::pages::browser=debug,hyper=warn"
+    pub filters: &'a str,
+    pub format: LogFormat,
+    // Empty to only log to stdout
+    pub file: &'a Path,
+    pub max_files: u32,
+}
+
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    timestamp: String,
+    level: log::Level,
+    target: &'a str,
+    message: String,
+}
+
+fn day_of(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / SECS_PER_DAY
+}
+
+// Starts a new file every day, keeping the previous ones as log.1, log.2, etc.
+struct RollingFile {
+    path: PathBuf,
+    max_files: u32,
+    file: File,
+    day: u64,
+}
+
+impl RollingFile {
+    fn open(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    fn new(path: &Path, max_files: u32) -> io::Result<Self> {
+        let file = Self::open(path)?;
+
+        // An existing file may be left over from a previous day
+        let modified = file.metadata()?.modified()?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_files,
+            file,
+            day: day_of(modified),
+        })
+    }
+
+    fn rotated_path(&self, n: u32) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{n}"));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        // Shift every rotated log up by one, which overwrites the oldest one
+        for n in (1..self.max_files).rev() {
+            let _ = fs::rename(self.rotated_path(n), self.rotated_path(n + 1));
+        }
+
+        if self.max_files > 0 {
+            fs::rename(&self.path, self.rotated_path(1))?;
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+
+        self.file = Self::open(&self.path)?;
+
+        Ok(())
+    }
+
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        let today = day_of(SystemTime::now());
+
+        if today != self.day {
+            self.day = today;
+            self.rotate()?;
+        }
+
+        self.file.write_all(line.as_bytes())
+    }
+}
+
+// Per-module level overrides, sorted so that the most specific match comes first
+struct Filters {
+    default: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Filters {
+    fn parse(default: LevelFilter, directives: &str) -> Result<Self> {
+        let mut filters = Self {
+            default,
+            targets: Vec::new(),
+        };
+
+        for directive in directives.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+
+            let parse_level = |level: &str| {
+                level
+                    .parse::<LevelFilter>()
+                    .ok()
+                    .with_context(|| format!("invalid log level in filter \"{directive}\""))
+            };
+
+            // A bare level replaces the default for every other module
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    let target = target.trim();
+                    if target.is_empty() {
+                        bail!("missing module in log filter \"{directive}\"");
+                    }
+                    filters
+                        .targets
+                        .push((target.to_string(), parse_level(level.trim())?));
+                }
+                None => filters.default = parse_level(directive)?,
+            }
+        }
+
+        filters
+            .targets
+            .sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+
+        Ok(filters)
+    }
+
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .find(|(prefix, _)| {
+                target
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+struct Logger {
+    filters: Filters,
+    format: LogFormat,
+    file: Option<Mutex<RollingFile>>,
+}
+
+impl Logger {
+    fn format(&self, record: &Record) -> String {
+        let timestamp = humantime::format_rfc3339_millis(SystemTime::now());
+
+        match self.format {
+            LogFormat::Text => format!(
+                "{timestamp} {:<5} [{}] {}\n",
+                record.level(),
+                record.target(),
+                record.args()
+            ),
+            LogFormat::Json => {
+                let line = JsonLine {
+                    timestamp: timestamp.to_string(),
+                    level: record.level(),
+                    target: record.target(),
+                    message: record.args().to_string(),
+                };
+
+                // Serializing a struct of strings can't fail
+                let mut line = serde_json::to_string(&line).unwrap();
+                line.push('\n');
+                line
+            }
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filters.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = self.format(record);
+
+        let _ = io::stdout().lock().write_all(line.as_bytes());
+
+        if let Some(file) = &self.file {
+            // Can't log the failure, since that would try to write to the file again
+            if let Err(err) = file.lock().unwrap().write(&line) {
+                eprintln!("Failed to write to log file: {err}");
+            }
+        }
+    }
+
+    fn flush(&self) {
+        let _ = io::stdout().flush();
+    }
+}
+
+pub fn init_logger(options: LogOptions) -> Result<()> {
+    let file = if options.file.as_os_str().is_empty() {
+        None
+    } else {
+        let file =
+            RollingFile::new(options.file, options.max_files).context("failed to open log file")?;
+        Some(Mutex::new(file))
+    };
+
+    let filters = Filters::parse(options.level, options.filters)?;
+    let max_level = filters.max_level();
+
+    let logger = Logger {
+        filters,
+        format: options.format,
+        file,
+    };
+
+    // The logger lives for the rest of the program
+    log::set_logger(Box::leak(Box::new(logger))).unwrap();
+    log::set_max_level(max_level);
+
+    Ok(())
+}
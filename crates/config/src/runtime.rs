@@ -0,0 +1,16 @@
+use std::io;
+
+use tokio::runtime::{Builder, Runtime};
+
+// A single thread keeps memory use down on small boards, while more can be configured for busier ones
+pub fn build_runtime(worker_threads: usize) -> io::Result<Runtime> {
+    let mut builder = if worker_threads == 0 {
+        Builder::new_current_thread()
+    } else {
+        let mut builder = Builder::new_multi_thread();
+        builder.worker_threads(worker_threads);
+        builder
+    };
+
+    builder.enable_all().build()
+}
@@ -23,6 +23,35 @@ async function getUpdateMessage(oldVersion) {
     }
 }
 
+// Matches the close code the server uses when the login has expired
+const UNAUTHORIZED_CLOSE_CODE = 4001;
+
+// Opens a websocket that the server may compress, in which case everything it sends is one
+// continuous raw deflate stream split across messages. Calls `onData` with the decompressed bytes.
+function openCompressedSocket(url, onData) {
+    const socket = new WebSocket(url, ["deflate"]);
+    socket.binaryType = "arraybuffer";
+
+    let writer = null;
+
+    socket.onopen = () => {
+        if (socket.protocol !== "deflate") return;
+
+        const stream = new DecompressionStream("deflate-raw");
+        writer = stream.writable.getWriter();
+        stream.readable.pipeTo(new WritableStream({ write: onData }));
+    };
+
+    socket.onmessage = (e) => {
+        const data = new Uint8Array(e.data);
+
+        if (writer) writer.write(data);
+        else onData(data);
+    };
+
+    return socket;
+}
+
 (() => {
     customElements.define(
         "web-terminal",
@@ -31,16 +60,88 @@ async function getUpdateMessage(oldVersion) {
                 const term = new Terminal();
                 term.open(this);
 
-                const socket = new WebSocket("/terminal/ws");
+                const session = this.getAttribute("session") ?? 0;
+                const socket = new WebSocket(`terminal/ws?session=${session}`);
                 socket.binaryType = "arraybuffer";
 
                 socket.onmessage = (e) => term.write(new Uint8Array(e.data));
+                socket.onclose = (e) => {
+                    if (e.code === UNAUTHORIZED_CLOSE_CODE) {
+                        location.href = "login";
+                        return;
+                    }
+                    term.write(`\r\n[${e.reason || "Disconnected"}, reload the page to reconnect]\r\n`);
+                };
 
                 term.onData((data) => socket.send(data));
             }
         }
     );
 
+    customElements.define(
+        "log-viewer",
+        class extends HTMLElement {
+            connectedCallback() {
+                const pre = document.createElement("pre");
+                this.append(pre);
+
+                // Lines can be split across messages, so decode as a single stream
+                const decoder = new TextDecoder();
+
+                this.socket = openCompressedSocket(this.getAttribute("src"), (data) => {
+                    // Only keep following new lines if already scrolled to the bottom
+                    const atBottom = pre.scrollHeight - pre.scrollTop - pre.clientHeight < 8;
+
+                    pre.append(decoder.decode(data, { stream: true }));
+
+                    if (atBottom) pre.scrollTop = pre.scrollHeight;
+                });
+
+                this.socket.onclose = (e) => {
+                    if (e.code === UNAUTHORIZED_CLOSE_CODE) location.href = "login";
+                };
+            }
+
+            disconnectedCallback() {
+                this.socket?.close();
+            }
+        }
+    );
+
+    customElements.define(
+        "software-output",
+        class extends HTMLElement {
+            connectedCallback() {
+                const pre = document.createElement("pre");
+                this.append(pre);
+
+                const decoder = new TextDecoder();
+
+                const socket = openCompressedSocket(`software/ws?stream=${this.getAttribute("stream")}`, (data) => {
+                    pre.append(decoder.decode(data, { stream: true }));
+                    pre.scrollTop = pre.scrollHeight;
+                });
+
+                socket.onclose = async (e) => {
+                    if (e.code === UNAUTHORIZED_CLOSE_CODE) {
+                        location.href = "login";
+                        return;
+                    }
+
+                    // Refresh the lists, without replacing the output along with the rest of the page
+                    const resp = await fetch("software", { headers: { "nm-request": true } });
+                    const doc = new DOMParser().parseFromString(await resp.text(), "text/html");
+                    const card = doc.getElementById("software-card");
+
+                    if (card) {
+                        document.getElementById("software-card").replaceWith(card);
+                        card.dispatchEvent(new CustomEvent("nm:process", { bubbles: true }));
+                    }
+                };
+            }
+        }
+    );
+
     customElements.define(
         "code-editor",
         class extends HTMLElement {
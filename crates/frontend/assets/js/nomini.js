@@ -13,21 +13,40 @@
             if (data)
                 url += (url.includes("?") ? "&" : "?") + new URLSearchParams(data);
 
-            fetch(url, { headers: { "nm-request": true } })
-                .then(res => res.text())
+            // Resolves to whether the request succeeded
+            return fetch(url, { headers: { "nm-request": true } })
+                .then(checkResponse)
                 .then(swap)
-                .catch(err => this.nmError = err)
+                .then(() => {
+                    // Don't keep showing an error that has since resolved itself
+                    if (this.nmError) this.nmError = null;
+                    return true;
+                }, (err) => {
+                    this.nmError = err;
+                    return false;
+                })
                 .finally(() => this.nmFetching = false);
         },
         post(url, data) {
             this.nmFetching = true;
 
-            fetch(url, { headers: { "nm-request": true }, method: "POST", body: new URLSearchParams(data) })
-                .then(res => res.text())
+            // Files are sent as-is, so that they don't need to be encoded in memory first
+            const body = data instanceof Blob ? data : new URLSearchParams(data);
+
+            fetch(url, { headers: { "nm-request": true }, method: "POST", body })
+                .then(checkResponse)
                 .then(swap)
                 .catch(err => this.nmError = err)
                 .finally(() => this.nmFetching = false);
         },
+        // Refreshes a live view. A successful swap brings in the next poll along with the new content,
+        // but after an error the same URL is tried again, so a flaky connection doesn't stop updates
+        // or lose the history carried in the URL.
+        poll(url, ms) {
+            this.debounce(async () => {
+                if (!await this.get(url)) this.poll(url, ms);
+            }, ms);
+        },
         debounce(fn, ms) {
             const internal = this.nmInternal;
 
@@ -38,6 +57,13 @@
 
     let currentBind = null;
 
+    // Error responses contain a message instead of HTML, so show it instead of swapping it in
+    const checkResponse = async (res) => {
+        const text = await res.text();
+        if (!res.ok) throw text;
+        return text;
+    };
+
     const swap = (text) => {
         const fragments = new DOMParser().parseFromString(text, "text/html").body.children;
 
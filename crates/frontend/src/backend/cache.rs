@@ -35,4 +35,5 @@ macro_rules! cache {
     };
 }
 
-cache!(BackendCache, [cpu: Cpu, temp: Temp, mem: Mem, disk: Disk, net_io: NetIO, processes: Processes]);
+// Only requests without any arguments can be cached, since every request for one shares the response
+cache!(BackendCache, [cpu: Cpu, temp: Temp, mem: Mem, disk: Disk, net_io: NetIO, disk_io: DiskIO]);
@@ -1,11 +1,20 @@
-use std::{collections::VecDeque, net::IpAddr};
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    net::IpAddr,
+    time::{Duration, Instant, SystemTime},
+};
 
 use anyhow::{Context, Result, anyhow};
-use config::PROTOCOL_VERSION;
+use config::{APP_VERSION, PROTOCOL_VERSION};
+use futures_util::Stream;
+use hyper::body::Bytes;
 use log::{error, info, warn};
 use proto::{
     DashboardSocket,
-    backend::{ActionBackendMessage, BackendMessage, Handshake, ResponseBackendMessage},
+    backend::{
+        ActionBackendMessage, BackendMessage, Handshake, ResponseBackendMessage, UpdateInfo,
+    },
     frontend::{ActionFrontendMessage, FrontendMessage, RequestFrontendMessage},
 };
 use slab::Slab;
@@ -14,13 +23,28 @@ use tokio::{
     sync::{mpsc, oneshot},
 };
 
+use crate::SharedConfig;
+
 use super::{SharedBackendRegistry, cache::BackendCache};
 
 #[derive(Debug)]
 pub struct BackendInfo {
     pub nickname: String,
-    pub update: Option<String>,
+    pub update: Option<UpdateInfo>,
+    pub app_version: String,
     pub handle: BackendHandle,
+    pub connected_since: SystemTime,
+    // When the backend last answered a ping
+    pub last_seen: SystemTime,
+    // `None` if the last ping went unanswered
+    pub latency: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct OfflineBackend {
+    pub nickname: String,
+    pub since: SystemTime,
+    pub last_seen: SystemTime,
 }
 
 #[derive(Debug)]
@@ -33,33 +57,110 @@ enum BackendRequest {
         msg: ActionFrontendMessage,
     },
     PushTerminalHandle {
-        term_tx: mpsc::UnboundedSender<Vec<u8>>,
+        id: u32,
+        term_tx: mpsc::Sender<Vec<u8>>,
     },
 }
 
+const PING_INTERVAL: Duration = Duration::from_secs(10);
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Keep a chatty session from using up too much memory, regardless of the configured size
+const MAX_TERMINAL_BUFFER_SIZE: usize = 1024 * 1024;
+// Number of output messages that can be queued for a viewer before it's considered too slow
+const TERMINAL_CHANNEL_SIZE: usize = 256;
+
+struct TerminalSession {
+    txs: Vec<mpsc::Sender<Vec<u8>>>,
+    buf: VecDeque<u8>,
+    buf_size: usize,
+    wrapped: bool,
+    detached_since: Option<Instant>,
+}
+
+impl TerminalSession {
+    fn new(buf_size: usize) -> Self {
+        let buf_size = buf_size.min(MAX_TERMINAL_BUFFER_SIZE);
+
+        Self {
+            txs: Vec::new(),
+            buf: VecDeque::with_capacity(buf_size),
+            buf_size,
+            wrapped: false,
+            detached_since: None,
+        }
+    }
+
+    fn push_output(&mut self, data: &[u8]) {
+        for &x in data {
+            if self.buf.len() >= self.buf_size {
+                self.buf.pop_front();
+                self.wrapped = true;
+            }
+            self.buf.push_back(x);
+        }
+
+        // The connection is shared by every session, so it can't wait on a slow viewer. Dropping
+        // output would garble the terminal, so disconnect the viewer instead, and let it catch up
+        // from the buffer when it reattaches.
+        self.txs.retain(|tx| tx.try_send(data.to_vec()).is_ok());
+    }
+
+    fn attach(&mut self, tx: mpsc::Sender<Vec<u8>>) {
+        let buf = self.buf.make_contiguous();
+
+        // Once old output has been dropped, the buffer could start in the middle of an escape sequence
+        // or character, so start replaying from the next full line instead
+        let replay = if self.wrapped {
+            let start = buf
+                .iter()
+                .position(|&x| x == b'\n')
+                .map_or(buf.len(), |x| x + 1);
+            &buf[start..]
+        } else {
+            buf
+        };
+
+        if tx.try_send(replay.to_vec()).is_ok() {
+            self.txs.push(tx);
+        }
+    }
+}
+
 pub struct BackendConnection {
     socket: DashboardSocket,
     registry: SharedBackendRegistry,
+    config: SharedConfig,
     addr: IpAddr,
 }
 
 impl BackendConnection {
-    pub fn new(
+    pub async fn new(
         stream: TcpStream,
         registry: SharedBackendRegistry,
+        config: SharedConfig,
         addr: IpAddr,
-        key: [u8; 32],
-    ) -> Self {
-        Self {
-            socket: DashboardSocket::new(stream, key),
+    ) -> Result<Self> {
+        let socket = DashboardSocket::new(stream, config.secret.0)
+            .await
+            .context("failed to set up encryption")?;
+
+        Ok(Self {
+            socket,
             registry,
+            config,
             addr,
-        }
+        })
     }
 
     pub async fn handle_connection(mut self) {
         let (tx, rx) = mpsc::unbounded_channel();
 
+        if let Err(err) = self.check_version().await {
+            error!("Backend {} is incompatible: {err:#}", self.addr);
+            return;
+        }
+
         let handshake = match self.read_handshake().await {
             Ok(handshake) => handshake,
             Err(err) => {
@@ -68,9 +169,11 @@ impl BackendConnection {
             }
         };
 
-        if handshake.version != PROTOCOL_VERSION {
-            warn!("Backend with incompatable version connected");
-            return;
+        if handshake.app_version != APP_VERSION {
+            warn!(
+                "Backend {} is running v{}, but the frontend is running v{APP_VERSION}",
+                self.addr, handshake.app_version
+            );
         }
 
         let nickname = if !handshake.nickname.is_empty() {
@@ -79,19 +182,42 @@ impl BackendConnection {
             self.addr.to_string()
         };
 
+        let handle = BackendHandle::new(tx);
+        let now = SystemTime::now();
+
         let conn_info = BackendInfo {
             nickname,
             update: handshake.update,
-            handle: BackendHandle::new(tx),
+            app_version: handshake.app_version,
+            handle: handle.clone(),
+            connected_since: now,
+            last_seen: now,
+            latency: None,
         };
 
-        self.registry.lock().unwrap().insert(self.addr, conn_info);
+        {
+            let mut registry = self.registry.lock().unwrap();
+            registry.offline.remove(&self.addr);
+            registry.connected.insert(self.addr, conn_info);
+        }
+
+        let pinger = tokio::spawn(ping(self.registry.clone(), self.addr, handle));
 
         if let Err(err) = self.handle_requests(rx).await {
             error!("Error handling requests for backend {}: {err:#}", self.addr)
         }
 
-        self.registry.lock().unwrap().remove(&self.addr);
+        pinger.abort();
+
+        let mut registry = self.registry.lock().unwrap();
+        if let Some(info) = registry.connected.remove(&self.addr) {
+            let offline = OfflineBackend {
+                nickname: info.nickname,
+                since: SystemTime::now(),
+                last_seen: info.last_seen,
+            };
+            registry.offline.insert(self.addr, offline);
+        }
     }
 
     async fn read_frame(&mut self) -> Result<Option<BackendMessage>> {
@@ -101,6 +227,31 @@ impl BackendConnection {
             .context("failed to read frame from socket")
     }
 
+    // Versions are exchanged on their own first, since messages from an incompatible version might
+    // not even be decodable
+    async fn check_version(&mut self) -> Result<()> {
+        let version: u32 = self
+            .socket
+            .read_frame()
+            .await
+            .context("failed to read protocol version, the backend may be outdated")?
+            .context("peer disconnected before sending protocol version")?;
+
+        // Always reply, so that the backend can give a clear error too
+        self.socket
+            .write_frame(PROTOCOL_VERSION)
+            .await
+            .context("failed to send protocol version")?;
+
+        if version != PROTOCOL_VERSION {
+            return Err(anyhow!(
+                "backend uses protocol v{version}, but this frontend uses v{PROTOCOL_VERSION}, update both to the same release"
+            ));
+        }
+
+        Ok(())
+    }
+
     async fn read_handshake(&mut self) -> Result<Handshake> {
         let message = self
             .read_frame()
@@ -113,13 +264,41 @@ impl BackendConnection {
         Ok(handshake)
     }
 
+    fn expired_terminals(&self, terminals: &mut HashMap<u32, TerminalSession>) -> Vec<u32> {
+        let timeout = self.config.terminal_timeout;
+        if timeout == 0 {
+            return Vec::new();
+        }
+
+        let mut expired = Vec::new();
+
+        for (&id, session) in terminals {
+            session.txs.retain(|tx| !tx.is_closed());
+
+            if !session.txs.is_empty() {
+                session.detached_since = None;
+                continue;
+            }
+
+            let detached_since = *session.detached_since.get_or_insert_with(Instant::now);
+
+            if detached_since.elapsed() >= Duration::from_secs(timeout) {
+                expired.push(id);
+            }
+        }
+
+        expired
+    }
+
     async fn handle_requests(
         &mut self,
         mut rx: mpsc::UnboundedReceiver<BackendRequest>,
     ) -> Result<()> {
         let mut in_progress: Slab<oneshot::Sender<ResponseBackendMessage>> = Slab::new();
-        let mut term_txs = Vec::new();
-        let mut term_buf = VecDeque::with_capacity(10_000);
+        // Output is kept per session, so that a session can be detached from and reattached to later
+        let mut terminals: HashMap<u32, TerminalSession> = HashMap::new();
+        let term_buf_size = self.config.terminal_buffer_size;
+        let mut term_timeout_check = tokio::time::interval(Duration::from_secs(10));
         let mut cache = BackendCache::new();
 
         loop {
@@ -147,6 +326,11 @@ impl BackendConnection {
                                 .context("failed to write request frame")?;
                         },
                         BackendRequest::Action { msg } => {
+                            // Dropping the senders disconnects anyone still attached to the session
+                            if let ActionFrontendMessage::CloseTerminal(id) = msg {
+                                terminals.remove(&id);
+                            }
+
                             let msg = FrontendMessage::Action(msg);
 
                             self.socket
@@ -154,13 +338,26 @@ impl BackendConnection {
                                 .await
                                 .context("failed to write action frame")?;
                         },
-                        BackendRequest::PushTerminalHandle { term_tx } => {
-                            if term_tx.send(term_buf.make_contiguous().to_vec()).is_ok() {
-                                term_txs.push(term_tx);
-                            }
+                        BackendRequest::PushTerminalHandle { id, term_tx } => {
+                            terminals
+                                .entry(id)
+                                .or_insert_with(|| TerminalSession::new(term_buf_size))
+                                .attach(term_tx);
                         },
                     }
                 }
+                _ = term_timeout_check.tick() => {
+                    for id in self.expired_terminals(&mut terminals) {
+                        terminals.remove(&id);
+
+                        let msg = FrontendMessage::Action(ActionFrontendMessage::CloseTerminal(id));
+
+                        self.socket
+                            .write_frame(msg)
+                            .await
+                            .context("failed to write action frame")?;
+                    }
+                }
                 resp_result = self.read_frame() => {
                     let Some(resp) = resp_result? else {
                         info!("Backend {} disconnected", self.addr);
@@ -184,15 +381,11 @@ impl BackendConnection {
                                     warn!("Received extraneous handshake from backend {}", self.addr);
                                     continue;
                                 },
-                                ActionBackendMessage::Terminal(data) => {
-                                    for &x in &data {
-                                        if term_buf.len() == term_buf.capacity() {
-                                            term_buf.pop_front();
-                                        }
-                                        term_buf.push_back(x);
-                                    }
-
-                                    term_txs.retain(|tx| tx.send(data.clone()).is_ok());
+                                ActionBackendMessage::Terminal(id, data) => {
+                                    terminals
+                                        .entry(id)
+                                        .or_insert_with(|| TerminalSession::new(term_buf_size))
+                                        .push_output(&data);
                                 }
                             }
                         }
@@ -205,6 +398,31 @@ impl BackendConnection {
     }
 }
 
+// Measures the round trip time, and notices a backend that has stopped answering even though its
+// connection hasn't been closed yet
+async fn ping(registry: SharedBackendRegistry, addr: IpAddr, handle: BackendHandle) {
+    let mut interval = tokio::time::interval(PING_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let start = Instant::now();
+        let resp =
+            tokio::time::timeout(PING_TIMEOUT, handle.send_req(RequestFrontendMessage::Ping));
+        let latency = matches!(resp.await, Ok(Ok(_))).then(|| start.elapsed());
+
+        let mut registry = registry.lock().unwrap();
+        let Some(info) = registry.connected.get_mut(&addr) else {
+            break;
+        };
+
+        info.latency = latency;
+        if latency.is_some() {
+            info.last_seen = SystemTime::now();
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BackendHandle {
     tx: mpsc::UnboundedSender<BackendRequest>,
@@ -238,10 +456,10 @@ impl BackendHandle {
             .context("failed to send message, connection likely closed")
     }
 
-    pub async fn get_terminal_handle(&self) -> Result<mpsc::UnboundedReceiver<Vec<u8>>> {
-        let (term_tx, term_rx) = mpsc::unbounded_channel();
+    pub async fn get_terminal_handle(&self, id: u32) -> Result<mpsc::Receiver<Vec<u8>>> {
+        let (term_tx, term_rx) = mpsc::channel(TERMINAL_CHANNEL_SIZE);
 
-        let msg = BackendRequest::PushTerminalHandle { term_tx };
+        let msg = BackendRequest::PushTerminalHandle { id, term_tx };
 
         self.tx
             .send(msg)
@@ -250,3 +468,56 @@ impl BackendHandle {
         Ok(term_rx)
     }
 }
+
+struct StreamGuard {
+    handle: BackendHandle,
+    id: u32,
+    done: bool,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        // If the stream was dropped early (e.g. client disconnected), tell the backend to stop producing it
+        if !self.done {
+            let msg = ActionFrontendMessage::CloseStream(self.id);
+            let _ = self.handle.tx.send(BackendRequest::Action { msg });
+        }
+    }
+}
+
+impl BackendHandle {
+    pub fn read_stream(&self, id: u32) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static {
+        let guard = StreamGuard {
+            handle: self.clone(),
+            id,
+            done: false,
+        };
+
+        // The next chunk is only requested once the previous one has been consumed
+        futures_util::stream::unfold(guard, async |mut guard| {
+            if guard.done {
+                return None;
+            }
+
+            let resp = guard
+                .handle
+                .send_req(RequestFrontendMessage::ReadStream(guard.id))
+                .await;
+
+            match resp {
+                Ok(ResponseBackendMessage::ReadStream(Some(chunk))) => {
+                    Some((Ok(Bytes::from(chunk)), guard))
+                }
+                Ok(ResponseBackendMessage::ReadStream(None)) => {
+                    guard.done = true;
+                    None
+                }
+                Ok(_) => unreachable!(),
+                Err(err) => {
+                    guard.done = true;
+                    Some((Err(io::Error::other(err)), guard))
+                }
+            }
+        })
+    }
+}
@@ -5,7 +5,7 @@ use std::{
 };
 
 use anyhow::{Context, Result};
-use conn::{BackendConnection, BackendInfo};
+use conn::{BackendConnection, BackendInfo, OfflineBackend};
 use log::{error, info};
 use tokio::net::TcpListener;
 
@@ -16,9 +16,19 @@ pub use conn::BackendHandle;
 
 use crate::SharedConfig;
 
-pub type BackendRegistry = HashMap<IpAddr, BackendInfo>;
+#[derive(Default)]
+pub struct BackendRegistry {
+    pub connected: HashMap<IpAddr, BackendInfo>,
+    // Kept after disconnecting, so that a backend going down shows up as offline instead of just
+    // disappearing from the list
+    pub offline: HashMap<IpAddr, OfflineBackend>,
+}
+
 pub type SharedBackendRegistry = Arc<Mutex<BackendRegistry>>;
 
+// Backends connect to the frontend rather than the other way around, so there's no list of nodes to
+// configure here. Each one names itself with its `nickname`, and everything sent over the
+// connection is encrypted and authenticated with keys derived from the shared `secret`.
 pub struct BackendServer {
     listener: TcpListener,
     config: SharedConfig,
@@ -53,14 +63,14 @@ impl BackendServer {
 
             info!("New backend connection from {peer_ip}");
 
-            let conn = BackendConnection::new(
-                stream,
-                self.registry.clone(),
-                peer_ip,
-                self.config.secret.0,
-            );
+            let (registry, config) = (self.registry.clone(), self.config.clone());
 
-            tokio::spawn(conn.handle_connection());
+            tokio::spawn(async move {
+                match BackendConnection::new(stream, registry, config, peer_ip).await {
+                    Ok(conn) => conn.handle_connection().await,
+                    Err(err) => error!("Failed to connect to backend {peer_ip}: {err:#}"),
+                }
+            });
         }
     }
 }
@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use futures_util::StreamExt;
+use log::{error, info};
+use rustls_acme::{AcmeConfig, caches::DirCache};
+use tokio_rustls::TlsAcceptor;
+
+use crate::SharedConfig;
+
+pub fn get_tlsacceptor_from_acme(
+    config: &SharedConfig,
+    alpn_protocols: Vec<Vec<u8>>,
+) -> TlsAcceptor {
+    // Addresses can be given with or without the scheme
+    let contact = config.acme_contact.iter().map(|x| {
+        let addr = x.strip_prefix("mailto:").unwrap_or(x);
+        format!("mailto:{addr}")
+    });
+
+    let mut state = AcmeConfig::new(&config.acme_domains)
+        .contact(contact)
+        .cache(DirCache::new(config.acme_cache_dir.clone()))
+        .directory_lets_encrypt(true)
+        .state();
+
+    // The resolver in this config swaps in renewed certificates by itself, so existing
+    // connections are kept, and it also answers TLS-ALPN-01 challenges
+    let mut rustls_config = Arc::unwrap_or_clone(state.default_rustls_config());
+    // Added after the challenge protocol, which ACME servers ask for on its own anyway
+    rustls_config.alpn_protocols.extend(alpn_protocols);
+
+    // Certificates are only ordered and renewed while the state is being polled
+    tokio::spawn(async move {
+        while let Some(event) = state.next().await {
+            match event {
+                Ok(event) => info!("ACME: {event:?}"),
+                Err(err) => error!("ACME error: {err:?}"),
+            }
+        }
+    });
+
+    TlsAcceptor::from(Arc::new(rustls_config))
+}
@@ -0,0 +1,132 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    net::IpAddr,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use config::frontend::FrontendConfig;
+use data_encoding::HEXLOWER;
+use log::{error, info};
+use ring::digest::{SHA256, digest};
+
+struct AuditWriter {
+    path: PathBuf,
+    max_size: u64,
+    max_files: u32,
+    file: File,
+    size: u64,
+}
+
+impl AuditWriter {
+    fn open(path: &Path) -> io::Result<File> {
+        // The log can reveal what's on the system, so keep it private
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(path)
+    }
+
+    fn new(config: &FrontendConfig) -> io::Result<Self> {
+        let path = config.audit_log_path.clone();
+        let file = Self::open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            max_size: config.audit_log_max_size,
+            max_files: config.audit_log_max_files,
+            file,
+            size,
+        })
+    }
+
+    fn rotated_path(&self, n: u32) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{n}"));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        // Shift every rotated log up by one, which overwrites the oldest one
+        for n in (1..self.max_files).rev() {
+            let _ = fs::rename(self.rotated_path(n), self.rotated_path(n + 1));
+        }
+
+        if self.max_files > 0 {
+            fs::rename(&self.path, self.rotated_path(1))?;
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+
+        self.file = Self::open(&self.path)?;
+        self.size = 0;
+
+        Ok(())
+    }
+
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64;
+
+        if self.size > 0 && self.size + len > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line.as_bytes())?;
+        self.size += len;
+
+        Ok(())
+    }
+}
+
+// Sessions are told apart by a short hash of their token, so that the log can't be used to hijack them
+pub fn session_subject(token: &str) -> String {
+    let hash = digest(&SHA256, token.as_bytes());
+    format!("session-{}", &HEXLOWER.encode(hash.as_ref())[..8])
+}
+
+// Records privileged actions, so that there's a trail of who did what. Entries are written on a
+// separate thread, so that slow storage doesn't hold up requests.
+#[derive(Clone)]
+pub struct AuditLog {
+    tx: Option<mpsc::Sender<String>>,
+}
+
+impl AuditLog {
+    pub fn new(config: &FrontendConfig) -> Result<Self> {
+        if config.audit_log_path.as_os_str().is_empty() {
+            return Ok(Self { tx: None });
+        }
+
+        let mut writer = AuditWriter::new(config).context("failed to open audit log")?;
+
+        let (tx, rx) = mpsc::channel::<String>();
+
+        std::thread::spawn(move || {
+            for line in rx {
+                if let Err(err) = writer.write(&line) {
+                    error!("Failed to write to audit log: {err}");
+                }
+            }
+        });
+
+        Ok(Self { tx: Some(tx) })
+    }
+
+    pub fn record(&self, ip: IpAddr, subject: &str, action: &str) {
+        // Paths and names come from the client, so don't let them forge extra entries
+        let action = action.replace(char::is_control, "?");
+
+        info!("{ip} ({subject}) {action}");
+
+        if let Some(tx) = &self.tx {
+            let timestamp = humantime::format_rfc3339_seconds(SystemTime::now());
+            let _ = tx.send(format!("{timestamp} {ip} {subject} {action}\n"));
+        }
+    }
+}
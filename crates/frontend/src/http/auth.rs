@@ -5,7 +5,22 @@ use std::{
     time::{Duration, Instant},
 };
 
-pub struct LoginMap(HashMap<[u8; 12], Instant>);
+struct Login {
+    created: Instant,
+    // Set once a terminal has been left idle for too long, and only cleared by logging in again
+    terminal_locked: bool,
+}
+
+// Tokens are random and only mean anything to this frontend, so there's no issuer or audience to
+// check. Expiry is measured with a monotonic clock, so a board that boots with the wrong time and
+// corrects it later doesn't invalidate tokens or make new ones look like they're from the future.
+pub struct LoginMap(HashMap<[u8; 12], Login>);
+
+fn decode_token(token: &str) -> Option<[u8; 12]> {
+    let bytes = data_encoding::HEXLOWER.decode(token.as_bytes()).ok()?;
+
+    bytes.try_into().ok()
+}
 
 impl LoginMap {
     pub fn new() -> Self {
@@ -13,10 +28,13 @@ impl LoginMap {
     }
 
     pub fn new_token(&mut self) -> String {
-        let now = Instant::now();
+        let login = Login {
+            created: Instant::now(),
+            terminal_locked: false,
+        };
         let bytes: [u8; 12] = rand::random();
 
-        self.0.insert(bytes, now);
+        self.0.insert(bytes, login);
 
         data_encoding::HEXLOWER.encode(&bytes)
     }
@@ -24,17 +42,26 @@ impl LoginMap {
     pub fn contains_token(&mut self, token: &str) -> bool {
         let now = Instant::now();
         self.0
-            .retain(|_, earlier| now.duration_since(*earlier) < Duration::from_secs(3600));
+            .retain(|_, login| now.duration_since(login.created) < Duration::from_secs(3600));
 
-        let Ok(bytes) = data_encoding::HEXLOWER.decode(token.as_bytes()) else {
-            return false;
-        };
-        let Ok(bytes) = <[u8; 12]>::try_from(bytes) else {
+        let Some(bytes) = decode_token(token) else {
             return false;
         };
 
         self.0.contains_key(&bytes)
     }
+
+    pub fn lock_terminal(&mut self, token: &str) {
+        if let Some(login) = decode_token(token).and_then(|bytes| self.0.get_mut(&bytes)) {
+            login.terminal_locked = true;
+        }
+    }
+
+    pub fn is_terminal_locked(&self, token: &str) -> bool {
+        decode_token(token)
+            .and_then(|bytes| self.0.get(&bytes))
+            .is_some_and(|login| login.terminal_locked)
+    }
 }
 
 #[derive(Clone)]
@@ -0,0 +1,35 @@
+use std::io::Write;
+
+use flate2::{Compression, write::DeflateEncoder};
+use hyper::body::Bytes;
+
+// Offered by clients as a websocket subprotocol, and echoed back if compression is enabled
+pub const COMPRESSION_PROTOCOL: &str = "deflate";
+
+// Compresses everything sent over a websocket as one continuous raw deflate stream, so that each
+// message can refer back to earlier ones. Log lines are short but repetitive, so compressing them
+// one at a time would barely help.
+pub struct MessageCompressor {
+    encoder: Option<DeflateEncoder<Vec<u8>>>,
+}
+
+impl MessageCompressor {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            encoder: enabled.then(|| DeflateEncoder::new(Vec::new(), Compression::default())),
+        }
+    }
+
+    pub fn compress(&mut self, data: Bytes) -> Bytes {
+        let Some(encoder) = &mut self.encoder else {
+            return data;
+        };
+
+        // Flushing ends the message on a byte boundary, so the client can decompress all of it
+        // without waiting for the next one. Writing to a Vec can't fail.
+        encoder.write_all(&data).unwrap();
+        encoder.flush().unwrap();
+
+        std::mem::take(encoder.get_mut()).into()
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+
+// Keeps track of whether a websocket client is still there. Proxies often silently drop idle
+// connections, which would otherwise leave the socket open until the OS gives up on it, keeping
+// anything attached to it (like a terminal session) alive too.
+pub struct Heartbeat {
+    // `None` if pings are disabled
+    interval: Option<Interval>,
+    timeout: Duration,
+    last_seen: Instant,
+}
+
+impl Heartbeat {
+    // Pings twice per timeout, so that one can go missing on a slow connection without the client
+    // being given up on, and so that a proxy with the same timeout always sees some traffic
+    pub fn new(timeout: Duration) -> Self {
+        let now = Instant::now();
+        let ping_interval = timeout / 2;
+
+        let interval = (!ping_interval.is_zero()).then(|| {
+            let mut interval = tokio::time::interval_at(now + ping_interval, ping_interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        });
+
+        Self {
+            interval,
+            timeout,
+            last_seen: now,
+        }
+    }
+
+    // Any message from the client counts, not just pongs
+    pub fn seen(&mut self) {
+        self.last_seen = Instant::now();
+    }
+
+    // Waits until the next ping should be sent, returning false if the client has stopped
+    // responding instead
+    pub async fn tick(&mut self) -> bool {
+        let Some(interval) = &mut self.interval else {
+            return std::future::pending().await;
+        };
+
+        interval.tick().await;
+
+        self.last_seen.elapsed() < self.timeout
+    }
+}
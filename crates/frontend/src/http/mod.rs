@@ -1,37 +1,88 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 use anyhow::{Context, Result};
+use audit::AuditLog;
 use auth::SharedLoginMap;
-use flexible_hyper_server_tls::{HttpOrHttpsAcceptor, rustls_helpers};
-use hyper::service::service_fn;
+use config::frontend::FrontendConfig;
+use hyper::{
+    header::{self, HeaderName, HeaderValue},
+    server::conn::http1,
+    service::service_fn,
+};
+use hyper_util::rt::{TokioIo, TokioTimer};
 use log::{error, info};
-use request::ServerRequest;
+use request::{ConnectionInfo, ServerRequest};
 use router::router;
-use tokio::net::TcpListener;
+use rustls_acme::acme::ACME_TLS_ALPN_NAME;
+use shutdown::Shutdown;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream},
+};
+use tokio_rustls::TlsAcceptor;
 
 use crate::{SharedConfig, backend::SharedBackendRegistry};
 
+mod acme;
+pub mod audit;
 pub mod auth;
+pub mod compression;
+pub mod heartbeat;
 pub mod query_array;
 pub mod request;
 pub mod response;
 mod router;
+pub mod shutdown;
 mod statics;
+mod tls;
+
+pub type SecurityHeaders = Arc<[(HeaderName, HeaderValue)]>;
+
+// Built once at startup, so that invalid values are caught straight away rather than on every response
+fn security_headers(config: &FrontendConfig) -> Result<SecurityHeaders> {
+    let headers = [
+        (header::X_FRAME_OPTIONS, &config.frame_options),
+        (header::REFERRER_POLICY, &config.referrer_policy),
+        (
+            header::CONTENT_SECURITY_POLICY,
+            &config.content_security_policy,
+        ),
+    ];
+
+    headers
+        .into_iter()
+        // Empty values leave the header out entirely
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(name, value)| {
+            let value = HeaderValue::from_str(value)
+                .with_context(|| format!("invalid value for {name} header"))?;
+            Ok((name, value))
+        })
+        .collect()
+}
 
 #[derive(Clone)]
 pub struct FrontendContext {
     backends: SharedBackendRegistry,
     config: SharedConfig,
     logins: SharedLoginMap,
+    audit: AuditLog,
+    shutdown: Shutdown,
+    security_headers: SecurityHeaders,
 }
 
 pub struct HttpServer {
-    acceptor: HttpOrHttpsAcceptor,
+    listener: TcpListener,
+    tls: Option<TlsAcceptor>,
     context: FrontendContext,
 }
 
 impl HttpServer {
-    pub async fn new(config: SharedConfig, backends: SharedBackendRegistry) -> Result<Self> {
+    pub async fn new(
+        config: SharedConfig,
+        backends: SharedBackendRegistry,
+        shutdown: Shutdown,
+    ) -> Result<Self> {
         info!("Starting web server on port {}", config.http_port);
 
         let addr = SocketAddr::from((config.http_subnet, config.http_port));
@@ -39,45 +90,132 @@ impl HttpServer {
             .await
             .context("failed to bind http server")?;
 
-        let mut acceptor = HttpOrHttpsAcceptor::new(listener);
+        let mut tls = None;
+
+        if config.enable_acme {
+            let alpn = tls::alpn_protocols(&config.tls_alpn_protocols)?;
+            tls = Some(acme::get_tlsacceptor_from_acme(&config, alpn));
+        } else if config.enable_tls {
+            let alpn = tls::alpn_protocols(&config.tls_alpn_protocols)?;
+            let client_ca_path = config
+                .enable_client_auth
+                .then_some(config.client_ca_path.as_path());
 
-        if config.enable_tls {
-            let tls =
-                rustls_helpers::get_tlsacceptor_from_files(&config.cert_path, &config.key_path)
-                    .await
-                    .context("failed to build TlsAcceptor")?;
+            let acceptor = tls::get_reloading_tlsacceptor(
+                &config.cert_path,
+                &config.key_path,
+                client_ca_path,
+                alpn,
+            )
+            .context("failed to build TlsAcceptor")?;
 
-            acceptor = acceptor.with_tls(tls)
+            tls = Some(acceptor);
         }
 
         let logins = SharedLoginMap::new();
+        let audit = AuditLog::new(&config)?;
+        let security_headers = security_headers(&config)?;
 
         Ok(Self {
-            acceptor,
+            listener,
+            tls,
             context: FrontendContext {
                 config,
                 logins,
                 backends,
+                audit,
+                shutdown,
+                security_headers,
             },
         })
     }
 
     pub async fn run(self) {
         loop {
+            let Ok((stream, addr)) = self.listener.accept().await else {
+                continue;
+            };
+
+            let tls = self.tls.clone();
             let ctx = self.context.clone();
 
-            let service = service_fn(move |req| {
-                let req = ServerRequest::new(req, ctx.clone());
-                async move { router(req).await }
+            tokio::spawn(async move {
+                if let Err(err) = serve_connection(stream, addr, tls, ctx).await {
+                    error!("Error serving HTTP connection: {err:#}");
+                }
             });
-
-            if let Ok((_, conn_fut)) = self.acceptor.accept(service).await {
-                tokio::spawn(async move {
-                    if let Err(err) = conn_fut.await {
-                        error!("Error serving HTTP connection: {err}");
-                    }
-                });
-            }
         }
     }
 }
+
+async fn serve_connection(
+    stream: TcpStream,
+    addr: SocketAddr,
+    tls: Option<TlsAcceptor>,
+    ctx: FrontendContext,
+) -> Result<()> {
+    let mut conn = ConnectionInfo {
+        // Listening on :: gives IPv4 clients mapped addresses, which wouldn't match configured IPs
+        remote_addr: addr.ip().to_canonical(),
+        client_verified: false,
+    };
+
+    // Also covers the TLS handshake, so that a client can't hold a connection open by never
+    // finishing it
+    let read_timeout = (ctx.config.http_read_timeout != 0)
+        .then(|| Duration::from_secs(ctx.config.http_read_timeout));
+
+    let Some(tls) = tls else {
+        return serve_http(stream, conn, read_timeout, ctx).await;
+    };
+
+    let accept = tls.accept(stream);
+    let stream = match read_timeout {
+        Some(timeout) => tokio::time::timeout(timeout, accept)
+            .await
+            .context("TLS handshake timed out")?,
+        None => accept.await,
+    }
+    .context("TLS handshake failed")?;
+
+    // ACME servers only connect to check the challenge certificate, and don't send a request after
+    if stream.get_ref().1.alpn_protocol() == Some(ACME_TLS_ALPN_NAME) {
+        return Ok(());
+    }
+
+    // Clients are only asked for a certificate when the acceptor has a client verifier, which fails
+    // the handshake unless the certificate is signed by the configured CA
+    conn.client_verified = stream.get_ref().1.peer_certificates().is_some();
+
+    serve_http(stream, conn, read_timeout, ctx).await
+}
+
+async fn serve_http<I>(
+    io: I,
+    conn: ConnectionInfo,
+    read_timeout: Option<Duration>,
+    ctx: FrontendContext,
+) -> Result<()>
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = service_fn(move |req| {
+        let req = ServerRequest::new(req, ctx.clone(), conn);
+        let guard = ctx.shutdown.guard();
+
+        async move {
+            let resp = router(req).await;
+            drop(guard);
+            resp
+        }
+    });
+
+    // Upgrades are needed for websockets
+    http1::Builder::new()
+        .timer(TokioTimer::new())
+        .header_read_timeout(read_timeout)
+        .serve_connection(TokioIo::new(io), service)
+        .with_upgrades()
+        .await
+        .context("failed to serve connection")
+}
@@ -2,6 +2,7 @@ use std::{
     collections::HashMap,
     net::IpAddr,
     ops::{Deref, DerefMut},
+    time::{Duration, SystemTime},
 };
 
 use config::frontend::FrontendConfig;
@@ -16,22 +17,30 @@ use hyper::{
 };
 use hyper_util::rt::TokioIo;
 use proto::{
-    backend::ResponseBackendMessage,
+    backend::{ResponseBackendMessage, UpdateInfo},
     frontend::{ActionFrontendMessage, RequestFrontendMessage},
 };
 use ring::digest::SHA1_FOR_LEGACY_USE_ONLY;
-use tokio_tungstenite::{WebSocketStream, tungstenite::protocol::Role};
+use tokio_tungstenite::{
+    WebSocketStream,
+    tungstenite::protocol::{CloseFrame, Role, WebSocketConfig, frame::coding::CloseCode},
+};
 
 use crate::backend::BackendHandle;
 
 use super::{
-    FrontendContext,
+    FrontendContext, SecurityHeaders, audit,
     auth::SharedLoginMap,
+    compression::COMPRESSION_PROTOCOL,
     response::{RedirectType, ServerResponse},
+    shutdown::Shutdown,
 };
 
 pub type HyperRequest = hyper::Request<Incoming>;
 
+// Sent when closing a websocket because the client isn't logged in, so that it knows to log in again
+pub const UNAUTHORIZED_CLOSE_CODE: u16 = 4001;
+
 fn get_cookies(parts: &RequestParts) -> HashMap<String, String> {
     let cookie_header = parts
         .headers
@@ -48,24 +57,47 @@ fn get_cookies(parts: &RequestParts) -> HashMap<String, String> {
 
 pub struct BackendData {
     pub backend_list: Vec<(IpAddr, String)>,
+    // Along with when they went offline
+    pub offline_list: Vec<(IpAddr, String, SystemTime)>,
     pub current_backend: CurrentBackendData,
 }
 
 pub struct CurrentBackendData {
     pub addr: IpAddr,
     pub handle: BackendHandle,
-    pub update: Option<String>,
+    pub update: Option<UpdateInfo>,
+    pub app_version: String,
+    pub latency: Option<Duration>,
+}
+
+pub struct BackendStatus {
+    pub addr: IpAddr,
+    pub nickname: String,
+    pub online: bool,
+    // When the backend connected, or when it went offline
+    pub since: SystemTime,
+    pub last_seen: SystemTime,
+    pub latency: Option<Duration>,
+}
+
+// What's known about the connection a request came in on
+#[derive(Clone, Copy)]
+pub struct ConnectionInfo {
+    pub remote_addr: IpAddr,
+    // Whether the client presented a certificate that was verified during the TLS handshake
+    pub client_verified: bool,
 }
 
 pub struct ServerRequest {
     parts: RequestParts,
     body: Option<Incoming>,
     cookies: HashMap<String, String>,
+    conn: ConnectionInfo,
     context: FrontendContext,
 }
 
 impl ServerRequest {
-    pub fn new(req: HyperRequest, context: FrontendContext) -> Self {
+    pub fn new(req: HyperRequest, context: FrontendContext, conn: ConnectionInfo) -> Self {
         let (parts, body) = req.into_parts();
 
         let cookies = get_cookies(&parts);
@@ -74,20 +106,94 @@ impl ServerRequest {
             parts,
             body: Some(body),
             cookies,
+            conn,
             context,
         }
     }
 
+    pub fn client_ip(&self) -> IpAddr {
+        let trusted_proxies = &self.config().trusted_proxies;
+        let mut ip = self.conn.remote_addr;
+
+        // X-Forwarded-For can be set to anything by the client, so only the entries appended by
+        // trusted proxies can be believed. Walk back from the right until a non-proxy is found.
+        if trusted_proxies.contains(&ip) {
+            let forwarded = self
+                .headers
+                .get_all("X-Forwarded-For")
+                .iter()
+                .filter_map(|x| x.to_str().ok())
+                .flat_map(|x| x.split(','))
+                .collect::<Vec<_>>();
+
+            for entry in forwarded.into_iter().rev() {
+                let Ok(entry) = entry.trim().parse() else {
+                    break;
+                };
+
+                ip = entry;
+
+                if !trusted_proxies.contains(&ip) {
+                    break;
+                }
+            }
+        }
+
+        ip
+    }
+
+    // Identifies who made the request for the audit log
+    fn auth_subject(&self) -> String {
+        if !self.config().enable_login {
+            return "anonymous".into();
+        }
+        if self.conn.client_verified {
+            return "client-certificate".into();
+        }
+
+        self.bearer_token()
+            .or_else(|| self.cookies.get("token").map(String::as_str))
+            .map_or_else(|| "unauthenticated".into(), audit::session_subject)
+    }
+
+    pub fn audit(&self, action: &str) {
+        let subject = self.auth_subject();
+        self.context
+            .audit
+            .record(self.client_ip(), &subject, action);
+    }
+
+    pub fn shutdown(&self) -> Shutdown {
+        self.context.shutdown.clone()
+    }
+
+    pub fn base_path(&self) -> &str {
+        self.config().base_path.trim_end_matches('/')
+    }
+
     pub fn path_segments(&self) -> impl Iterator<Item = &str> {
-        self.uri.path().split('/').filter(|x| !x.is_empty())
+        let path = self.uri.path();
+        // Reverse proxies don't always strip the prefix before forwarding the request. Only whole
+        // segments are stripped, so that /docker isn't cut down to ocker with a base path of /d.
+        let path = path
+            .strip_prefix(self.base_path())
+            .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+            .unwrap_or(path);
+
+        path.split('/').filter(|x| !x.is_empty())
     }
 
     pub fn config(&self) -> &FrontendConfig {
         &self.context.config
     }
 
+    pub fn security_headers(&self) -> SecurityHeaders {
+        self.context.security_headers.clone()
+    }
+
     pub fn extract_backends(&self) -> Result<BackendData, ServerResponse> {
-        let backends = self.context.backends.lock().unwrap();
+        let registry = self.context.backends.lock().unwrap();
+        let backends = &registry.connected;
         let backend_list: Vec<_> = backends
             .iter()
             .map(|(addr, info)| (*addr, info.nickname.clone()))
@@ -114,15 +220,68 @@ impl ServerRequest {
                 addr,
                 handle: backend_info.handle.clone(),
                 update: backend_info.update.clone(),
+                app_version: backend_info.app_version.clone(),
+                latency: backend_info.latency,
             }
         };
 
+        let offline_list = registry
+            .offline
+            .iter()
+            .map(|(addr, info)| (*addr, info.nickname.clone(), info.since))
+            .collect();
+
         Ok(BackendData {
             backend_list,
+            offline_list,
             current_backend,
         })
     }
 
+    pub fn backend_versions(&self) -> Vec<(String, String)> {
+        let backends = self.context.backends.lock().unwrap();
+
+        backends
+            .connected
+            .values()
+            .map(|info| (info.nickname.clone(), info.app_version.clone()))
+            .collect()
+    }
+
+    pub fn all_backends(&self) -> Vec<(String, BackendHandle)> {
+        let backends = self.context.backends.lock().unwrap();
+
+        backends
+            .connected
+            .values()
+            .map(|info| (info.nickname.clone(), info.handle.clone()))
+            .collect()
+    }
+
+    pub fn backend_statuses(&self) -> Vec<BackendStatus> {
+        let backends = self.context.backends.lock().unwrap();
+
+        let connected = backends.connected.iter().map(|(addr, info)| BackendStatus {
+            addr: *addr,
+            nickname: info.nickname.clone(),
+            online: true,
+            since: info.connected_since,
+            last_seen: info.last_seen,
+            latency: info.latency,
+        });
+
+        let offline = backends.offline.iter().map(|(addr, info)| BackendStatus {
+            addr: *addr,
+            nickname: info.nickname.clone(),
+            online: false,
+            since: info.since,
+            last_seen: info.last_seen,
+            latency: None,
+        });
+
+        connected.chain(offline).collect()
+    }
+
     pub async fn send_backend_req(
         &self,
         req: RequestFrontendMessage,
@@ -159,16 +318,36 @@ impl ServerRequest {
         })
     }
 
+    pub fn extract_body(&mut self) -> Result<Incoming, ServerResponse> {
+        self.body.take().ok_or_else(|| {
+            ServerResponse::new()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body("body already extracted")
+        })
+    }
+
+    // Keeps a client that stops sending partway through a body from tying up the request forever
+    pub async fn with_read_timeout<F: Future>(&self, fut: F) -> Result<F::Output, ServerResponse> {
+        let timeout = self.config().http_read_timeout;
+        if timeout == 0 {
+            return Ok(fut.await);
+        }
+
+        tokio::time::timeout(Duration::from_secs(timeout), fut)
+            .await
+            .map_err(|_| {
+                ServerResponse::new()
+                    .status(StatusCode::REQUEST_TIMEOUT)
+                    .body("timed out waiting for the request body")
+            })
+    }
+
     pub async fn extract_form<T: serde::de::DeserializeOwned>(
         &mut self,
     ) -> Result<T, ServerResponse> {
-        let Some(body) = self.body.take() else {
-            return Err(ServerResponse::new()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body("form already extracted"));
-        };
+        let body = self.extract_body()?;
 
-        let body = body.collect().await.map_err(|_| {
+        let body = self.with_read_timeout(body.collect()).await?.map_err(|_| {
             ServerResponse::new()
                 .status(StatusCode::BAD_REQUEST)
                 .body("needs body")
@@ -185,6 +364,135 @@ impl ServerRequest {
         self.headers.contains_key("nm-request")
     }
 
+    pub fn accepts(&self, mime: &str) -> bool {
+        self.headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|x| x.to_str().ok())
+            .flat_map(|x| x.split(','))
+            .any(|x| {
+                x.split(';')
+                    .next()
+                    .unwrap()
+                    .trim()
+                    .eq_ignore_ascii_case(mime)
+            })
+    }
+
+    pub fn accepts_encoding(&self, encoding: &str) -> bool {
+        let mut wildcard = false;
+
+        let codings = self
+            .headers
+            .get_all(header::ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|x| x.to_str().ok())
+            .flat_map(|x| x.split(','));
+
+        for coding in codings {
+            let mut parts = coding.split(';').map(str::trim);
+            let name = parts.next().unwrap();
+
+            // A weight of 0 explicitly rules the coding out
+            let acceptable = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .is_none_or(|q| q > 0.);
+
+            if name.eq_ignore_ascii_case(encoding) {
+                return acceptable;
+            }
+            if name == "*" {
+                wildcard = acceptable;
+            }
+        }
+
+        wildcard
+    }
+
+    // Whether the client's cached copy, as given by If-None-Match, is still current
+    pub fn matches_etag(&self, etag: &str) -> bool {
+        self.headers
+            .get_all(header::IF_NONE_MATCH)
+            .iter()
+            .filter_map(|x| x.to_str().ok())
+            .flat_map(|x| x.split(','))
+            .map(str::trim)
+            // If-None-Match uses weak comparison
+            .any(|x| x == "*" || x.trim_start_matches("W/") == etag)
+    }
+
+    // Browsers don't apply the same-origin policy to websockets, so any page could otherwise open
+    // one using the login cookie
+    fn is_allowed_origin(&self) -> bool {
+        // Browsers always send an origin, so its absence means this isn't a cross-site request
+        let Some(origin) = self.headers.get(header::ORIGIN) else {
+            return true;
+        };
+        let Ok(origin) = origin.to_str() else {
+            return false;
+        };
+
+        if self
+            .config()
+            .allowed_origins
+            .iter()
+            .any(|allowed| allowed.trim_end_matches('/').eq_ignore_ascii_case(origin))
+        {
+            return true;
+        }
+
+        let origin_host = origin.split_once("://").map_or(origin, |(_, host)| host);
+
+        self.headers
+            .get(header::HOST)
+            .and_then(|x| x.to_str().ok())
+            .is_some_and(|host| host.eq_ignore_ascii_case(origin_host))
+    }
+
+    // For API actions, which a cross-site form could otherwise trigger using the login cookie
+    pub fn check_same_origin(&self) -> Result<(), ServerResponse> {
+        if !self.is_allowed_origin() {
+            return Err(ServerResponse::new()
+                .status(StatusCode::FORBIDDEN)
+                .body("origin not allowed"));
+        }
+
+        Ok(())
+    }
+
+    pub fn websocket_idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.config().websocket_idle_timeout)
+    }
+
+    // Only sockets whose client knows how to decompress offer the subprotocol, which leaves out
+    // ones like the terminal that mostly send small or incompressible messages
+    pub fn websocket_compression(&self) -> bool {
+        self.config().websocket_compression
+            && self
+                .headers
+                .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+                .iter()
+                .filter_map(|x| x.to_str().ok())
+                .flat_map(|x| x.split(','))
+                .any(|protocol| protocol.trim() == COMPRESSION_PROTOCOL)
+    }
+
+    pub fn is_websocket(&self) -> bool {
+        // Tokens in these headers aren't case sensitive, and proxies often lowercase them
+        let check_header = |k, v: &[u8]| {
+            self.headers.get(k).map(|x| x.as_bytes()).is_some_and(|x| {
+                x.windows(v.len())
+                    .any(|subslice| subslice.eq_ignore_ascii_case(v))
+            })
+        };
+
+        check_header(header::CONNECTION, b"Upgrade")
+            && check_header(header::UPGRADE, b"websocket")
+            && check_header(header::SEC_WEBSOCKET_VERSION, b"13")
+            && self.headers.contains_key(header::SEC_WEBSOCKET_KEY)
+    }
+
     pub fn extract_websocket<F, Fut>(self, handler_fn: F) -> Result<ServerResponse, ServerResponse>
     where
         F: FnOnce(WebSocketStream<TokioIo<Upgraded>>) -> Fut + Send + 'static,
@@ -192,22 +500,25 @@ impl ServerRequest {
     {
         const WEBSOCKET_MAGIC_NUM: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
 
-        let check_header = |k, v: &[u8]| {
-            self.headers
-                .get(k)
-                .map(|x| x.as_bytes())
-                .is_some_and(|x| x.windows(v.len()).any(|subslice| subslice == v))
-        };
-
-        let is_websocket_req = check_header(header::CONNECTION, b"Upgrade")
-            && check_header(header::UPGRADE, b"websocket")
-            && check_header(header::SEC_WEBSOCKET_VERSION, b"13")
-            && self.headers.contains_key(header::SEC_WEBSOCKET_KEY);
+        // Usually a reverse proxy that doesn't pass the upgrade on, so say what's needed instead of
+        // leaving it to be worked out from a failed connection
+        if !self.is_websocket() {
+            return Err(ServerResponse::new()
+                .status(StatusCode::UPGRADE_REQUIRED)
+                .header(header::UPGRADE, "websocket")
+                .header(header::CONNECTION, "Upgrade")
+                .header(header::SEC_WEBSOCKET_VERSION, "13")
+                .header(header::CONTENT_TYPE, "text/plain;charset=UTF-8")
+                .body(
+                    "This address only accepts websocket connections. If the dashboard is behind a \
+                    reverse proxy, make sure that it passes on the Upgrade and Connection headers.",
+                ));
+        }
 
-        if !is_websocket_req {
+        if !self.is_allowed_origin() {
             return Err(ServerResponse::new()
-                .status(StatusCode::BAD_REQUEST)
-                .body("expected websocket upgrade"));
+                .status(StatusCode::FORBIDDEN)
+                .body("websocket origin not allowed"));
         }
 
         let sec_key = self
@@ -222,18 +533,35 @@ impl ServerRequest {
         );
         let resp_key = BASE64.encode(resp_key.as_ref());
 
-        let resp = ServerResponse::new()
+        let mut resp = ServerResponse::new()
             .status(StatusCode::SWITCHING_PROTOCOLS)
             .header(header::CONNECTION, "Upgrade")
             .header(header::UPGRADE, "websocket")
             .header(header::SEC_WEBSOCKET_ACCEPT, resp_key);
 
+        if self.websocket_compression() {
+            resp = resp.header(header::SEC_WEBSOCKET_PROTOCOL, COMPRESSION_PROTOCOL);
+        }
+
+        // Messages are buffered whole before being handed over, so cap them to stop a client from
+        // making the frontend allocate as much as it likes
+        let max_size = Some(self.config().websocket_max_size).filter(|&size| size != 0);
+        let ws_config = WebSocketConfig::default()
+            .max_message_size(max_size)
+            .max_frame_size(max_size);
+
         let req = hyper::Request::from_parts(self.parts, self.body.unwrap());
 
-        tokio::spawn(async {
+        // Websockets outlive the request, so they hold off shutdown on their own
+        let guard = self.context.shutdown.guard();
+
+        tokio::spawn(async move {
+            let _guard = guard;
+
             if let Ok(stream) = upgrade::on(req).await {
                 let stream = TokioIo::new(stream);
-                let ws = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+                let ws =
+                    WebSocketStream::from_raw_socket(stream, Role::Server, Some(ws_config)).await;
                 handler_fn(ws).await;
             }
         });
@@ -241,21 +569,122 @@ impl ServerRequest {
         Ok(resp)
     }
 
+    fn is_valid_token(&self, token: Option<&str>) -> bool {
+        // A client with a verified certificate is already authenticated
+        if self.conn.client_verified {
+            return true;
+        }
+
+        token.is_some_and(|token| self.context.logins.get().contains_token(token))
+    }
+
+    pub fn login_token(&self) -> Option<&str> {
+        self.cookies.get("token").map(String::as_str)
+    }
+
+    fn is_logged_in(&self) -> bool {
+        !self.config().enable_login || self.is_valid_token(self.login_token())
+    }
+
+    // Maintenance is started by creating the file, and its contents are shown as the message
+    pub async fn maintenance_message(&self) -> Option<String> {
+        let path = &self.config().maintenance_file;
+
+        if path.as_os_str().is_empty() {
+            return None;
+        }
+
+        tokio::fs::read_to_string(path).await.ok()
+    }
+
+    pub fn check_allowed_ip(&self) -> Result<(), ServerResponse> {
+        let ip = self.client_ip();
+
+        if !self
+            .config()
+            .allowed_ips
+            .iter()
+            .any(|range| range.contains(ip))
+        {
+            return Err(ServerResponse::new()
+                .status(StatusCode::FORBIDDEN)
+                .body("forbidden"));
+        }
+
+        Ok(())
+    }
+
     pub fn check_login(&self) -> Result<(), ServerResponse> {
-        if self.config().enable_login {
+        if !self.is_logged_in() {
             let err_resp = if self.is_fixi() {
-                Err(ServerResponse::new()
-                    .body(r#"<meta http-equiv="refresh" content="0; url=/login" />"#))
+                ServerResponse::new()
+                    .body(r#"<meta http-equiv="refresh" content="0; url=login" />"#)
             } else {
-                Err(ServerResponse::new().redirect(RedirectType::SeeOther, "/login"))
+                ServerResponse::new().redirect(RedirectType::SeeOther, "/login")
             };
 
-            let Some(token) = self.cookies.get("token") else {
-                return err_resp;
+            return Err(err_resp);
+        }
+
+        Ok(())
+    }
+
+    // Browsers don't expose why a websocket handshake failed, which makes a rejected login look
+    // the same as a crashed server. Instead, finish the handshake and close with a reason.
+    pub fn check_socket_login(self) -> Result<Self, ServerResponse> {
+        if self.is_logged_in() {
+            return Ok(self);
+        }
+
+        Err(self.close_websocket(CloseCode::from(UNAUTHORIZED_CLOSE_CODE), "unauthorized"))
+    }
+
+    // A locked terminal is only unlocked by logging in again, which gives a new token
+    pub fn check_terminal_unlocked(self) -> Result<Self, ServerResponse> {
+        let locked = self
+            .login_token()
+            .is_some_and(|token| self.context.logins.get().is_terminal_locked(token));
+
+        if !locked {
+            return Ok(self);
+        }
+
+        Err(self.close_websocket(CloseCode::from(UNAUTHORIZED_CLOSE_CODE), "terminal locked"))
+    }
+
+    // Finishes the handshake only to close the socket straight away, with a reason the client can
+    // show
+    pub fn close_websocket(self, code: CloseCode, reason: &'static str) -> ServerResponse {
+        let resp = self.extract_websocket(async move |mut ws| {
+            let frame = CloseFrame {
+                code,
+                reason: reason.into(),
             };
+            let _ = ws.close(Some(frame)).await;
+        });
 
-            if !self.context.logins.get().contains_token(token) {
-                return err_resp;
+        resp.unwrap_or_else(|err| err)
+    }
+
+    fn bearer_token(&self) -> Option<&str> {
+        self.headers
+            .get(header::AUTHORIZATION)
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.strip_prefix("Bearer "))
+    }
+
+    pub fn check_api_login(&self) -> Result<(), ServerResponse> {
+        if self.config().enable_login {
+            // Scripts can pass the token from the login cookie as a bearer token instead
+            let token = self
+                .bearer_token()
+                .or_else(|| self.cookies.get("token").map(String::as_str));
+
+            if !self.is_valid_token(token) {
+                return Err(ServerResponse::new()
+                    .status(StatusCode::UNAUTHORIZED)
+                    .header(header::WWW_AUTHENTICATE, "Bearer")
+                    .body("unauthorized"));
             }
         }
 
@@ -1,16 +1,20 @@
-use http_body_util::Full;
+use std::io;
+
+use futures_util::{Stream, TryStreamExt};
+use http_body_util::{BodyExt, Full, StreamBody, combinators::UnsyncBoxBody};
 use hyper::{
     StatusCode,
-    body::Bytes,
+    body::{Bytes, Frame},
     header::{self, HeaderName, HeaderValue},
     http::response::Builder as ResponseBuilder,
 };
 
 pub struct ServerResponse {
     builder: ResponseBuilder,
-    body: Bytes,
+    body: ResponseBody,
 }
-pub type BuiltResponse = hyper::Response<Full<Bytes>>;
+pub type ResponseBody = UnsyncBoxBody<Bytes, io::Error>;
+pub type BuiltResponse = hyper::Response<ResponseBody>;
 
 pub enum RedirectType {
     Permanent,
@@ -21,7 +25,7 @@ impl ServerResponse {
     pub fn new() -> Self {
         Self {
             builder: ResponseBuilder::new(),
-            body: Bytes::new(),
+            body: full_body(Bytes::new()),
         }
     }
 
@@ -50,14 +54,45 @@ impl ServerResponse {
         self.status(status).header(header::LOCATION, path)
     }
 
+    // Redirects are given as absolute paths, so they have to be moved under the base path
+    pub fn with_base_path(mut self, base_path: &str) -> Self {
+        let Some(headers) = self.builder.headers_mut() else {
+            return self;
+        };
+
+        let location = headers
+            .get(header::LOCATION)
+            .and_then(|x| x.to_str().ok())
+            .filter(|x| x.starts_with('/'))
+            .map(|x| format!("{base_path}{x}"));
+
+        if let Some(location) = location.and_then(|x| HeaderValue::try_from(x).ok()) {
+            headers.insert(header::LOCATION, location);
+        }
+
+        self
+    }
+
     pub fn body<T: Into<Bytes>>(mut self, body: T) -> Self {
-        self.body = body.into();
+        self.body = full_body(body.into());
         self
     }
 
-    pub fn build(self) -> BuiltResponse {
-        let body = Full::from(self.body);
+    pub fn stream_body<S>(mut self, stream: S) -> Self
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + 'static,
+    {
+        self.body = StreamBody::new(stream.map_ok(Frame::data)).boxed_unsync();
+        self
+    }
 
-        self.builder.body(body).unwrap()
+    pub fn build(self) -> BuiltResponse {
+        self.builder.body(self.body).unwrap()
     }
 }
+
+fn full_body(body: Bytes) -> ResponseBody {
+    Full::new(body)
+        .map_err(|never| match never {})
+        .boxed_unsync()
+}
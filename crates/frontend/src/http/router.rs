@@ -1,4 +1,5 @@
 use hyper::{Method, StatusCode, header};
+use log::debug;
 
 use crate::pages::*;
 
@@ -25,9 +26,54 @@ macro_rules! router {
 }
 
 pub async fn router(req: ServerRequest) -> Result<BuiltResponse, std::convert::Infallible> {
+    let base_path = req.base_path().to_string();
+    let request_line = format!("{} {} {}", req.client_ip(), req.method, req.uri.path());
+    let security_headers = req.security_headers();
+
+    let resp = match check_request(req).await {
+        Ok(req) => route(req).await,
+        Err(resp) => resp,
+    };
+
+    let mut resp = resp
+        .with_base_path(&base_path)
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(header::X_XSS_PROTECTION, "1; mode=block")
+        .header("X-Robots-Tag", "none");
+
+    for (name, value) in security_headers.iter() {
+        resp = resp.header(name, value);
+    }
+
+    let resp = resp.build();
+
+    debug!("{request_line} {}", resp.status().as_u16());
+
+    Ok(resp)
+}
+
+// Checks that apply to every request, before it reaches any page
+async fn check_request(req: ServerRequest) -> Result<ServerRequest, ServerResponse> {
+    // Checked before anything else, so that no page is reachable from outside the allowed ranges
+    req.check_allowed_ip()?;
+
+    // Probes should still work, and the maintenance page needs the static files
+    let exempt = matches!(
+        req.path_segments().next(),
+        Some("static" | "favicon.svg" | "healthz" | "readyz")
+    );
+
+    if !exempt && let Some(message) = req.maintenance_message().await {
+        return Err(maintenance::page(req, &message));
+    }
+
+    Ok(req)
+}
+
+async fn route(req: ServerRequest) -> ServerResponse {
     let path_segments: Vec<_> = req.path_segments().collect();
 
-    let resp = router!(req, &*path_segments, {
+    router!(req, &*path_segments, {
         (GET, ["static", "main.css"]) => statics::css,
         (GET, ["static", "main.js"]) => statics::js,
         (GET, ["static", "icons.svg"]) => statics::icons,
@@ -37,6 +83,8 @@ pub async fn router(req: ServerRequest) -> Result<BuiltResponse, std::convert::I
 
         (GET, ["login"]) => login::page,
         (POST, ["login"]) => login::form,
+        (GET, ["setup"]) => setup::page,
+        (POST, ["setup"]) => setup::form,
 
         (GET, ["system"]) => system::page,
 
@@ -45,39 +93,77 @@ pub async fn router(req: ServerRequest) -> Result<BuiltResponse, std::convert::I
 
         (GET, ["software"]) => software::page,
         (POST, ["software"]) => software::form,
+        (GET, ["software", "ws"]) => software::socket,
 
         (GET, ["service"]) => service::page,
+        (POST, ["service", "control"]) => service::control,
+        (GET, ["service", "journal"]) => service::journal,
+        (GET, ["service", "journal", "ws"]) => service::journal_socket,
+
+        (GET, ["docker"]) => docker::page,
+
+        (GET, ["logs"]) => logs::page,
+        (GET, ["logs", "ws"]) => logs::socket,
 
         (GET, ["management"]) => management::page,
+        (POST, ["management", "mount"]) => management::mount,
+        (POST, ["management", "unmount"]) => management::unmount,
+        (POST, ["management", "power"]) => management::power,
+        (POST, ["management", "drop-caches"]) => management::drop_caches,
+        (POST, ["management", "network-usage", "reset"]) => management::reset_network_usage,
 
         (GET, ["terminal"]) => terminal::page,
         (GET, ["terminal", "ws"]) => terminal::socket,
+        (POST, ["terminal", "close"]) => terminal::close,
 
         (GET, ["browser"]) => browser::page,
         (GET, ["browser", "file"]) => browser::file,
+        (GET, ["browser", "search"]) => browser::search,
         (POST, ["browser", "file", "save"]) => browser::save,
         (GET, ["browser", "actions"]) => browser::actions,
         (POST, ["browser", "actions", "new-file"]) => browser::new_file,
         (POST, ["browser", "actions", "new-folder"]) => browser::new_folder,
         (POST, ["browser", "actions", "rename"]) => browser::rename,
+        (POST, ["browser", "actions", "move"]) => browser::move_path,
+        (POST, ["browser", "actions", "copy"]) => browser::copy,
+        (POST, ["browser", "actions", "chmod"]) => browser::chmod,
+        (POST, ["browser", "actions", "chown"]) => browser::chown,
         (POST, ["browser", "actions", "delete-file"]) => browser::delete_file,
         (POST, ["browser", "actions", "delete-folder"]) => browser::delete_folder,
         (GET, ["browser", "actions", "download"]) => browser::download,
+        (GET, ["browser", "actions", "download-folder"]) => browser::download_folder,
+        (GET, ["browser", "actions", "checksum"]) => browser::checksum,
+        (GET, ["browser", "actions", "preview"]) => browser::preview,
+        (GET, ["browser", "actions", "thumbnail"]) => browser::thumbnail,
+        (GET, ["browser", "actions", "upload-status"]) => browser::upload_status,
         (POST, ["browser", "actions", "upload"]) => browser::upload,
 
         (GET, ["config"]) => config::page,
+        (POST, ["config", "frontend"]) => config::save,
+
+        (GET, ["dietpi"]) => dietpi::page,
+        (POST, ["dietpi"]) => dietpi::form,
+
+        (GET, ["metrics"]) => metrics::page,
+
+        (GET, ["healthz"]) => health::healthz,
+        (GET, ["readyz"]) => health::readyz,
+
+        (GET, ["api", "cpu"]) => api::cpu,
+        (GET, ["api", "temp"]) => api::temp,
+        (GET, ["api", "history"]) => api::history,
+        (GET, ["api", "memory"]) => api::memory,
+        (GET, ["api", "disk"]) => api::disk,
+        (GET, ["api", "disk-io"]) => api::disk_io,
+        (GET, ["api", "network"]) => api::network,
+        (GET, ["api", "network-usage"]) => api::network_usage,
+        (GET, ["api", "host"]) => api::host,
+        (GET, ["api", "services"]) => api::services,
+        (GET, ["api", "version"]) => api::version,
+        (GET, ["api", "backends"]) => api::backends,
+        (POST, ["api", "command"]) => api::command,
+        (GET, ["api", "config"]) => api::config,
 
         _ => || { ServerResponse::new().status(StatusCode::NOT_FOUND).body("page not found") },
-    });
-
-    let resp = resp
-        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
-        .header(header::X_FRAME_OPTIONS, "sameorigin")
-        .header(header::X_XSS_PROTECTION, "1; mode=block")
-        .header("X-Robots-Tag", "none")
-        .header(header::REFERRER_POLICY, "no-referrer");
-
-    let resp = resp.build();
-
-    Ok(resp)
+    })
 }
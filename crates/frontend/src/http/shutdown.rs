@@ -0,0 +1,102 @@
+use std::{sync::Arc, time::Duration};
+
+use log::{info, warn};
+use tokio::{
+    signal::unix::{SignalKind, signal},
+    sync::{Notify, mpsc, watch},
+};
+
+// Lets requests hold off shutdown until they're finished, and tells websockets when to close
+#[derive(Clone)]
+pub struct Shutdown {
+    signal: watch::Receiver<bool>,
+    // Weak, so that cloning the server context doesn't hold off shutdown by itself
+    tracker: mpsc::WeakSender<()>,
+    restart: Arc<Notify>,
+}
+
+// Shutdown waits until every guard has been dropped
+pub struct ShutdownGuard {
+    _tracker: Option<mpsc::Sender<()>>,
+}
+
+impl Shutdown {
+    pub fn guard(&self) -> ShutdownGuard {
+        ShutdownGuard {
+            _tracker: self.tracker.upgrade(),
+        }
+    }
+
+    pub async fn wait(&mut self) {
+        // An error means the controller is gone, which only happens once shutdown is over anyway
+        let _ = self.signal.wait_for(|&shutdown| shutdown).await;
+    }
+
+    // Shuts down the same way as for a signal, and then starts again, picking up any config changes
+    pub fn restart(&self) {
+        self.restart.notify_one();
+    }
+}
+
+pub struct ShutdownController {
+    signal: watch::Sender<bool>,
+    tracker: mpsc::Sender<()>,
+    drained: mpsc::Receiver<()>,
+    restart: Arc<Notify>,
+}
+
+impl ShutdownController {
+    pub fn new() -> Self {
+        let (signal, _) = watch::channel(false);
+        let (tracker, drained) = mpsc::channel(1);
+
+        Self {
+            signal,
+            tracker,
+            drained,
+            restart: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn handle(&self) -> Shutdown {
+        Shutdown {
+            signal: self.signal.subscribe(),
+            tracker: self.tracker.downgrade(),
+            restart: self.restart.clone(),
+        }
+    }
+
+    pub async fn shutdown(mut self, timeout: Duration) {
+        let _ = self.signal.send(true);
+        drop(self.tracker);
+
+        // Nothing is ever sent, so this only returns once every guard is dropped
+        if tokio::time::timeout(timeout, self.drained.recv())
+            .await
+            .is_err()
+        {
+            warn!("Timed out waiting for requests to finish, shutting down anyway");
+        }
+    }
+
+    // Returns whether to start again afterwards
+    pub async fn wait_for_signal(&self) -> bool {
+        let mut sigterm = signal(SignalKind::terminate()).unwrap();
+        let mut sighup = signal(SignalKind::hangup()).unwrap();
+
+        let restart = tokio::select! {
+            _ = sigterm.recv() => false,
+            _ = tokio::signal::ctrl_c() => false,
+            _ = sighup.recv() => true,
+            _ = self.restart.notified() => true,
+        };
+
+        if restart {
+            info!("Restarting...");
+        } else {
+            info!("Shutting down...");
+        }
+
+        restart
+    }
+}
@@ -1,20 +1,98 @@
-use hyper::header;
+use std::{io::Read, sync::OnceLock};
+
+use data_encoding::HEXLOWER;
+use flate2::read::GzDecoder;
+use hyper::{StatusCode, body::Bytes, header};
+use ring::digest::{SHA256, digest};
 
 use super::{request::ServerRequest, response::ServerResponse};
 
+struct StaticFile {
+    mime: &'static str,
+    gzip: &'static [u8],
+    brotli: &'static [u8],
+    // Nearly every client accepts gzip, so only decompress once one doesn't
+    identity: OnceLock<Bytes>,
+    hash: OnceLock<String>,
+}
+
+impl StaticFile {
+    const fn new(mime: &'static str, gzip: &'static [u8], brotli: &'static [u8]) -> Self {
+        Self {
+            mime,
+            gzip,
+            brotli,
+            identity: OnceLock::new(),
+            hash: OnceLock::new(),
+        }
+    }
+
+    fn identity(&self) -> Bytes {
+        let identity = self.identity.get_or_init(|| {
+            let mut buf = Vec::new();
+            // The file is compressed at build time, so it's always valid
+            GzDecoder::new(self.gzip).read_to_end(&mut buf).unwrap();
+            buf.into()
+        });
+
+        identity.clone()
+    }
+
+    fn hash(&self) -> &str {
+        self.hash.get_or_init(|| {
+            let hash = digest(&SHA256, self.gzip);
+            HEXLOWER.encode(&hash.as_ref()[..8])
+        })
+    }
+
+    fn serve(&self, req: &ServerRequest) -> ServerResponse {
+        // Brotli compresses noticeably better, which helps on slow connections
+        let (encoding, body) = if req.accepts_encoding("br") {
+            ("br", Bytes::from_static(self.brotli))
+        } else if req.accepts_encoding("gzip") {
+            ("gzip", Bytes::from_static(self.gzip))
+        } else {
+            ("identity", self.identity())
+        };
+
+        // Each encoding is a different representation, so it needs its own tag
+        let etag = format!("\"{}-{encoding}\"", self.hash());
+
+        // The URLs stay the same between versions, so have browsers check that they're up to date
+        // every time, which is cheap thanks to the tag
+        let resp = ServerResponse::new()
+            .header(header::VARY, "Accept-Encoding")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .header(header::ETAG, &etag);
+
+        if req.matches_etag(&etag) {
+            return resp.status(StatusCode::NOT_MODIFIED);
+        }
+
+        let resp = resp.header(header::CONTENT_TYPE, self.mime);
+
+        match encoding {
+            "identity" => resp.body(body),
+            _ => resp.header(header::CONTENT_ENCODING, encoding).body(body),
+        }
+    }
+}
+
 macro_rules! static_file {
     ($name:ident, $path:literal, $mime:literal) => {
-        pub async fn $name(_req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
-            let file = include_bytes!($path);
+        pub async fn $name(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+            static FILE: StaticFile = StaticFile::new(
+                $mime,
+                include_bytes!($path),
+                include_bytes!(concat!($path, ".br")),
+            );
 
-            Ok(ServerResponse::new()
-                .header(header::CONTENT_TYPE, $mime)
-                .header(header::CONTENT_ENCODING, "gzip")
-                .body(&file[..]))
+            Ok(FILE.serve(&req))
         }
     };
 }
 
+// Each file has its own fixed route, so nothing from the request path is ever used to look one up
 static_file!(js, "../../dist/main.js", "text/javascript;charset=UTF-8");
 static_file!(css, "../../dist/main.css", "text/css;charset=UTF-8");
 static_file!(icons, "../../dist/icons.svg", "image/svg+xml");
@@ -0,0 +1,147 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result, anyhow};
+use log::{error, info};
+use rustls::{
+    RootCertStore, ServerConfig,
+    crypto::ring::{default_provider, sign::any_supported_type},
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier, danger::ClientCertVerifier},
+    sign::CertifiedKey,
+};
+use tokio_rustls::TlsAcceptor;
+
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+// Only what the server can actually speak, since a client that negotiates a protocol will use it
+const SUPPORTED_ALPN_PROTOCOLS: &[&str] = &["http/1.1"];
+
+pub fn alpn_protocols(protocols: &[String]) -> Result<Vec<Vec<u8>>> {
+    protocols
+        .iter()
+        .map(|protocol| {
+            if !SUPPORTED_ALPN_PROTOCOLS.contains(&protocol.as_str()) {
+                return Err(anyhow!(
+                    "unsupported ALPN protocol {protocol:?}, only {SUPPORTED_ALPN_PROTOCOLS:?} can be offered"
+                ));
+            }
+
+            Ok(protocol.as_bytes().to_vec())
+        })
+        .collect()
+}
+
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> Result<CertifiedKey> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .context("failed to open certificate file")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to parse certificate file")?;
+    let key = PrivateKeyDer::from_pem_file(key_path).context("failed to parse private key file")?;
+    let key = any_supported_type(&key).context("unsupported private key type")?;
+
+    Ok(CertifiedKey::new(certs, key))
+}
+
+fn modified_times(cert_path: &Path, key_path: &Path) -> Option<(SystemTime, SystemTime)> {
+    let cert_time = std::fs::metadata(cert_path).ok()?.modified().ok()?;
+    let key_time = std::fs::metadata(key_path).ok()?.modified().ok()?;
+
+    Some((cert_time, key_time))
+}
+
+// Hands out whichever certificate was loaded last, so that renewed certificates can be swapped in
+// without restarting the server or dropping existing connections
+#[derive(Debug)]
+struct ReloadingCertResolver {
+    key: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for ReloadingCertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.key.read().unwrap().clone())
+    }
+}
+
+async fn watch_cert_files(
+    resolver: Arc<ReloadingCertResolver>,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+) {
+    let mut last_modified = modified_times(&cert_path, &key_path);
+
+    loop {
+        tokio::time::sleep(RELOAD_CHECK_INTERVAL).await;
+
+        let (cert_path, key_path) = (cert_path.clone(), key_path.clone());
+        let res = tokio::task::spawn_blocking(move || {
+            let modified = modified_times(&cert_path, &key_path);
+
+            (modified != last_modified)
+                .then(|| (modified, load_certified_key(&cert_path, &key_path)))
+        });
+
+        let Some((modified, key)) = res.await.unwrap() else {
+            continue;
+        };
+
+        // A renewal might only have replaced one of the files so far, so keep trying until both parse
+        match key {
+            Ok(key) => {
+                info!("Reloaded TLS certificate");
+                *resolver.key.write().unwrap() = Arc::new(key);
+                last_modified = modified;
+            }
+            Err(err) => error!("Failed to reload TLS certificate: {err:#}"),
+        }
+    }
+}
+
+fn load_client_verifier(ca_path: &Path) -> Result<Arc<dyn ClientCertVerifier>> {
+    let mut roots = RootCertStore::empty();
+
+    for cert in CertificateDer::pem_file_iter(ca_path).context("failed to open client CA file")? {
+        let cert = cert.context("failed to parse client CA file")?;
+        roots.add(cert).context("invalid client CA certificate")?;
+    }
+
+    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), Arc::new(default_provider()))
+        .build()
+        .context("failed to build client certificate verifier")
+}
+
+pub fn get_reloading_tlsacceptor(
+    cert_path: &Path,
+    key_path: &Path,
+    client_ca_path: Option<&Path>,
+    alpn_protocols: Vec<Vec<u8>>,
+) -> Result<TlsAcceptor> {
+    let key = load_certified_key(cert_path, key_path)?;
+
+    let resolver = Arc::new(ReloadingCertResolver {
+        key: RwLock::new(Arc::new(key)),
+    });
+
+    let builder = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+        .with_safe_default_protocol_versions()
+        .context("failed to set TLS protocol versions")?;
+
+    // With a client verifier, handshakes fail unless the client presents a certificate signed by the CA
+    let builder = match client_ca_path {
+        Some(ca_path) => builder.with_client_cert_verifier(load_client_verifier(ca_path)?),
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder.with_cert_resolver(resolver.clone());
+    server_config.alpn_protocols = alpn_protocols;
+
+    tokio::spawn(watch_cert_files(
+        resolver,
+        cert_path.to_path_buf(),
+        key_path.to_path_buf(),
+    ));
+
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
@@ -1,14 +1,20 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    os::unix::process::CommandExt,
+    process::Command,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use anyhow::{Context, Result};
 use backend::{BackendRegistry, BackendServer};
 use config::{
     APP_VERSION,
     frontend::{FrontendConfig, get_config},
+    logging::{LogOptions, init_logger},
+    runtime::build_runtime,
 };
-use http::HttpServer;
+use http::{HttpServer, shutdown::ShutdownController};
 use log::info;
-use simple_logger::SimpleLogger;
 
 mod backend;
 mod http;
@@ -16,24 +22,53 @@ mod pages;
 
 pub type SharedConfig = Arc<FrontendConfig>;
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<()> {
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn main() -> Result<()> {
     let config = Arc::new(get_config().context("failed to get config")?);
 
-    SimpleLogger::new()
-        .with_level(config.log_level)
-        .init()
-        .unwrap();
+    init_logger(LogOptions {
+        level: config.log_level,
+        filters: &config.log_filters,
+        format: config.log_format,
+        file: &config.log_file,
+        max_files: config.log_max_files,
+    })?;
 
     info!("Starting DietPi-Dashboard frontend v{APP_VERSION}...");
 
-    let backends = Arc::new(Mutex::new(BackendRegistry::new()));
+    let restart = build_runtime(config.worker_threads)
+        .context("failed to start async runtime")?
+        .block_on(run(config))?;
+
+    // Only once the runtime is gone, so that the ports have been let go of for the new process
+    if restart {
+        let exe = std::env::current_exe().context("couldn't get path to executable")?;
+        let err = Command::new(exe).args(std::env::args_os().skip(1)).exec();
+
+        return Err(err).context("failed to restart");
+    }
+
+    Ok(())
+}
+
+async fn run(config: SharedConfig) -> Result<bool> {
+    let backends = Arc::new(Mutex::new(BackendRegistry::default()));
 
     let backend_server = BackendServer::new(config.clone(), backends.clone()).await?;
 
-    let http_server = HttpServer::new(config, backends.clone()).await?;
+    let shutdown = ShutdownController::new();
 
-    tokio::join!(http_server.run(), backend_server.run());
+    let http_server = HttpServer::new(config, backends.clone(), shutdown.handle()).await?;
 
-    Ok(())
+    // Stop accepting new connections on shutdown, but give running requests and websockets a
+    // chance to finish
+    let restart = tokio::select! {
+        _ = async { tokio::join!(http_server.run(), backend_server.run()) } => false,
+        restart = shutdown.wait_for_signal() => restart,
+    };
+
+    shutdown.shutdown(SHUTDOWN_TIMEOUT).await;
+
+    Ok(restart)
 }
@@ -0,0 +1,155 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hyper::{StatusCode, header};
+use serde::{Deserialize, Serialize};
+
+use crate::http::{request::ServerRequest, response::ServerResponse};
+
+use super::template::send_req;
+
+// JSON by default, but clients polling often can ask for the more compact MessagePack instead
+fn encode<T: Serialize>(req: &ServerRequest, data: &T) -> Result<ServerResponse, ServerResponse> {
+    let (body, mime) = if req.accepts("application/msgpack") {
+        let body = rmp_serde::to_vec_named(data).map_err(|err| err.to_string());
+        (body, "application/msgpack")
+    } else {
+        let body = serde_json::to_vec(data).map_err(|err| err.to_string());
+        (body, "application/json")
+    };
+
+    let body = body.map_err(|err| {
+        ServerResponse::new()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(format!("failed to serialize response: {err}"))
+    })?;
+
+    Ok(ServerResponse::new()
+        .header(header::CONTENT_TYPE, mime)
+        .header(header::VARY, "Accept")
+        .body(body))
+}
+
+macro_rules! api_getters {
+    ($( $name:ident => $variant:ident, )*) => {
+        $(
+            pub async fn $name(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+                req.check_api_login()?;
+
+                let data = send_req!(req, $variant)?;
+
+                encode(&req, &data)
+            }
+        )*
+    };
+}
+
+#[derive(Serialize)]
+struct BackendVersion {
+    nickname: String,
+    version: String,
+}
+
+#[derive(Serialize)]
+struct VersionResponse {
+    frontend: &'static str,
+    backends: Vec<BackendVersion>,
+}
+
+pub async fn version(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_api_login()?;
+
+    let backends = req
+        .backend_versions()
+        .into_iter()
+        .map(|(nickname, version)| BackendVersion { nickname, version })
+        .collect();
+
+    let data = VersionResponse {
+        frontend: config::APP_VERSION,
+        backends,
+    };
+
+    encode(&req, &data)
+}
+
+#[derive(Serialize)]
+struct BackendStatusResponse {
+    nickname: String,
+    address: String,
+    online: bool,
+    // Unix timestamps
+    since: u64,
+    last_seen: u64,
+    // `None` if the backend is offline, or didn't answer the last ping
+    latency_ms: Option<u64>,
+}
+
+pub async fn backends(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_api_login()?;
+
+    let unix_secs = |time: SystemTime| {
+        time.duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    };
+
+    let data: Vec<_> = req
+        .backend_statuses()
+        .into_iter()
+        .map(|status| BackendStatusResponse {
+            nickname: status.nickname,
+            address: status.addr.to_string(),
+            online: status.online,
+            since: unix_secs(status.since),
+            last_seen: unix_secs(status.last_seen),
+            latency_ms: status.latency.map(|x| x.as_millis() as u64),
+        })
+        .collect();
+
+    encode(&req, &data)
+}
+
+#[derive(Deserialize)]
+pub struct CommandQuery {
+    name: String,
+}
+
+pub async fn command(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_api_login()?;
+    req.check_same_origin()?;
+
+    let query: CommandQuery = req.extract_query()?;
+
+    req.audit(&format!("ran command {}", query.name));
+
+    let data = send_req!(req, Command(query.name))?.ok_or_else(|| {
+        ServerResponse::new()
+            .status(StatusCode::NOT_FOUND)
+            .body("unknown command")
+    })?;
+
+    encode(&req, &data)
+}
+
+api_getters! {
+    cpu => Cpu,
+    temp => Temp,
+    history => History,
+    memory => Mem,
+    disk => Disk,
+    disk_io => DiskIO,
+    network => NetIO,
+    network_usage => NetworkUsage,
+    host => Host,
+    services => Services,
+}
+
+pub async fn config(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_api_login()?;
+
+    let body = config::frontend::effective_config_file(req.config());
+
+    Ok(ServerResponse::new()
+        .header(header::CONTENT_TYPE, "text/plain;charset=UTF-8")
+        .body(body))
+}
@@ -1,33 +1,167 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
 
-use data_encoding::BASE64;
-use hyper::StatusCode;
+use http_body_util::BodyExt;
+use hyper::{StatusCode, header};
 use maud::{Markup, html};
 use pretty_bytes_typed::pretty_bytes;
 use proto::{
-    backend::FileKind,
-    frontend::{RenameAction, UploadAction},
+    backend::{DirectoryItemInfo, FileKind},
+    frontend::{
+        ChmodAction, ChownAction, DirectoryAction, DirectorySort, SaveFileAction, SearchAction,
+        ThumbnailAction, TransferAction, UploadChunkAction,
+    },
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     http::{
         request::ServerRequest,
         response::{RedirectType, ServerResponse},
     },
-    pages::template::{Icon, send_act},
+    pages::template::Icon,
 };
 
 use super::template::{send_req, template};
 
+const UPLOAD_CHUNK_SIZE: usize = 1024 * 1024;
+const DIRECTORY_PAGE_SIZE: u32 = 200;
+
 fn default_path() -> String {
     "/root".into()
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct BrowserQuery {
     #[serde(default = "default_path")]
     path: String,
+    #[serde(default)]
+    sort: DirectorySort,
+    #[serde(default)]
+    reverse: bool,
+    #[serde(default)]
+    page: u32,
+}
+
+fn browser_url(path: &str, sort: DirectorySort, reverse: bool, page: u32) -> String {
+    let query = BrowserQuery {
+        path: path.to_string(),
+        sort,
+        reverse,
+        page,
+    };
+    let query = serde_urlencoded::to_string(&query).unwrap();
+
+    format!("'browser?{query}'")
+}
+
+fn table_header(name: &str, sort: DirectorySort, query: &BrowserQuery) -> Markup {
+    let reverse = if query.sort == sort {
+        !query.reverse
+    } else {
+        false
+    };
+
+    // Changing the sort starts over from the first page
+    let url = browser_url(&query.path, sort, reverse, 0);
+
+    html! {
+        th {
+            button nm-bind={ "onclick: () => get("(url)")" } {
+                (name)
+                @if query.sort == sort {
+                    @if query.reverse {
+                        (Icon::new("fa6-solid-sort-down"))
+                    } @else {
+                        (Icon::new("fa6-solid-sort-up"))
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn pretty_modified(modified: Option<u64>) -> String {
+    modified
+        .map(|secs| {
+            let time = UNIX_EPOCH + Duration::from_secs(secs);
+            humantime::format_rfc3339_seconds(time).to_string()
+        })
+        .unwrap_or_else(|| "--".into())
+}
+
+// Formats permissions like `ls -l` does, followed by the octal value
+fn pretty_mode(mode: u32) -> String {
+    let symbolic: String = [
+        (0o400, 'r'),
+        (0o200, 'w'),
+        (0o100, 'x'),
+        (0o040, 'r'),
+        (0o020, 'w'),
+        (0o010, 'x'),
+        (0o004, 'r'),
+        (0o002, 'w'),
+        (0o001, 'x'),
+    ]
+    .into_iter()
+    .map(|(bit, c)| if mode & bit != 0 { c } else { '-' })
+    .collect();
+
+    format!("{symbolic} ({mode:04o})")
+}
+
+fn directory_row(current_path: &str, item: &DirectoryItemInfo, name: &str) -> Markup {
+    let is_hidden = name.starts_with('.');
+    let icon = match item.kind {
+        FileKind::TextFile => "fa6-solid-file-lines",
+        FileKind::BinaryFile => "fa6-solid-file",
+        FileKind::Image => "fa6-solid-file-image",
+        FileKind::Directory => "fa6-solid-folder",
+        FileKind::Special => "fa6-solid-cube",
+    };
+    let pretty_size = item
+        .size
+        .map(|size| pretty_bytes(size, Some(0)).to_string())
+        .unwrap_or_else(|| "--".into());
+    let dblclick = match item.kind {
+        FileKind::TextFile => "get('browser/file', {path});",
+        FileKind::Directory => "get('browser', {path});",
+        FileKind::BinaryFile => "window.open(`browser/actions/download?path=${path}`)",
+        FileKind::Image => "window.open(`browser/actions/preview?path=${path}`)",
+        FileKind::Special => "",
+    };
+
+    html! {
+        tr
+            data-current-path=(current_path)
+            data-path=(item.path)
+            data-kind=(serde_plain::to_string(&item.kind).unwrap())
+            data-hidden[is_hidden]
+            nm-bind={"
+                ariaCurrent: () => selectedRow === this,
+                onclick: () => {
+                    let {currentPath, path, kind} = this.dataset;
+                    selectedRow = this;
+                    get('browser/actions', {currentPath, path, kind})
+                },
+                ondblclick: () => {
+                    let {path} = this.dataset;
+                    "(dblclick)"
+                },
+                hidden: () => this.hasAttribute('data-hidden') && !viewHidden
+            "}
+        {
+            td {
+                (Icon::new(icon).size(18)) " " (name)
+            }
+            td { (pretty_size) }
+            td { (pretty_modified(item.modified)) }
+            td { (pretty_mode(item.mode)) }
+            td { (item.owner) ":" (item.group) }
+        }
+    }
 }
 
 pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
@@ -35,58 +169,47 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
 
     let query: BrowserQuery = req.extract_query()?;
 
-    let mut data = send_req!(req, Directory(query.path.clone()))?;
+    let action = DirectoryAction {
+        path: query.path.clone(),
+        sort: query.sort,
+        reverse: query.reverse,
+        offset: query.page.saturating_mul(DIRECTORY_PAGE_SIZE),
+        limit: DIRECTORY_PAGE_SIZE,
+    };
+    let data = send_req!(req, Directory(action))?;
 
-    data.dir_list.sort_by(|a, b| a.path.cmp(&b.path));
+    let page_count = data.total.div_ceil(DIRECTORY_PAGE_SIZE).max(1);
 
     let content = html! {
         #browser-swap nm-data="selectedRow: null, viewHidden: false" {
             (path_display(&query.path))
-
-            table #browser-inner {
-                tr {
-                    th { "File Name" }
-                    th { "File Size" }
+            p nm-bind="textContent: () => nmError" {}
+
+            #browser-inner {
+                table {
+                    tr {
+                        (table_header("File Name", DirectorySort::Name, &query))
+                        (table_header("File Size", DirectorySort::Size, &query))
+                        (table_header("Modified", DirectorySort::Modified, &query))
+                        th { "Permissions" }
+                        th { "Owner" }
+                    }
+                    @for item in &data.dir_list {
+                        @let name = item.path.rsplit_once('/').map(|(_, name)| name).unwrap_or(&item.path);
+                        (directory_row(&query.path, item, name))
+                    }
                 }
-                @for item in data.dir_list {
-                    @let name = item.path.rsplit_once('/').map(|(_, name)| name).unwrap_or(&item.path);
-                    @let is_hidden = name.starts_with('.');
-                    @let icon = match item.kind {
-                        FileKind::TextFile => "fa6-solid-file-lines",
-                        FileKind::BinaryFile => "fa6-solid-file",
-                        FileKind::Directory => "fa6-solid-folder",
-                        FileKind::Special => "fa6-solid-cube",
-                    };
-                    @let pretty_size = item.size.map(|size| pretty_bytes(size, Some(0)).to_string()).unwrap_or_else(|| "--".into());
-                    @let dblclick = match item.kind {
-                        FileKind::TextFile => "get('/browser/file', {path});",
-                        FileKind::Directory => "get('/browser', {path});",
-                        FileKind::BinaryFile => "window.open(`/browser/actions/download?path=${path}`)",
-                        FileKind::Special => "",
-                    };
-                    tr
-                        data-current-path=(query.path)
-                        data-path=(item.path)
-                        data-kind=(serde_plain::to_string(&item.kind).unwrap())
-                        data-hidden[is_hidden]
-                        nm-bind={"
-                            ariaCurrent: () => selectedRow === this,
-                            onclick: () => {
-                                let {currentPath, path, kind} = this.dataset;
-                                selectedRow = this;
-                                get('/browser/actions', {currentPath, path, kind})
-                            },
-                            ondblclick: () => {
-                                let {path} = this.dataset;
-                                "(dblclick)"
-                            },
-                            hidden: () => this.hasAttribute('data-hidden') && !viewHidden
-                        "}
-                    {
-                        td {
-                            (Icon::new(icon).size(18)) " " (name)
+                @if page_count > 1 {
+                    @let prev_url = browser_url(&query.path, query.sort, query.reverse, query.page.saturating_sub(1));
+                    @let next_url = browser_url(&query.path, query.sort, query.reverse, query.page + 1);
+                    .pagination {
+                        button disabled[query.page == 0] nm-bind={ "onclick: () => get("(prev_url)")" } {
+                            "Previous"
+                        }
+                        span { "Page " (query.page + 1) " of " (page_count) }
+                        button disabled[query.page + 1 >= page_count] nm-bind={ "onclick: () => get("(next_url)")" } {
+                            "Next"
                         }
-                        td { (pretty_size) }
                     }
                 }
             }
@@ -99,16 +222,67 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
     template(&req, content)
 }
 
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    path: String,
+    pattern: String,
+}
+
+pub async fn search(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: SearchQuery = req.extract_query()?;
+
+    let action = SearchAction {
+        root: query.path.clone(),
+        pattern: query.pattern.clone(),
+    };
+    let data = send_req!(req, Search(action))?;
+
+    let content = html! {
+        #browser-swap nm-data="selectedRow: null, viewHidden: true" {
+            (path_display(&query.path))
+            p {
+                (data.results.len()) " results for \"" (query.pattern) "\""
+                @if data.truncated {
+                    " (search stopped early, try a narrower folder or pattern)"
+                }
+            }
+
+            #browser-inner {
+                table {
+                    tr {
+                        th { "File Path" }
+                        th { "File Size" }
+                        th { "Modified" }
+                        th { "Permissions" }
+                        th { "Owner" }
+                    }
+                    @for item in &data.results {
+                        (directory_row(&query.path, item, &item.path))
+                    }
+                }
+            }
+            #actions-list {
+                button title="Back" nm-bind={ "onclick: () => get('browser', {path: '"(query.path)"'})" } {
+                    (Icon::new("fa6-solid-folder"))
+                }
+            }
+        }
+    };
+
+    template(&req, content)
+}
+
 pub async fn file(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
     req.check_login()?;
 
     let query: BrowserQuery = req.extract_query()?;
 
-    let data = send_req!(req, Download(query.path.clone()))?;
-    let data = String::from_utf8(data).map_err(|_| {
+    let data = send_req!(req, ReadFile(query.path.clone()))?.map_err(|err| {
         ServerResponse::new()
             .status(StatusCode::BAD_REQUEST)
-            .body("not a text file")
+            .body(err)
     })?;
 
     let content = html! {
@@ -124,7 +298,7 @@ pub async fn file(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
             #actions-list {
                 button title="Save" nm-bind={"
                     onclick: () => {
-                        post('/browser/file/save', {path: '"(query.path)"', data});
+                        post('browser/file/save', {path: '"(query.path)"', data});
                     }
                 "} {
                     (Icon::new("fa6-solid-floppy-disk"))
@@ -151,7 +325,7 @@ fn path_display(path: &str) -> Markup {
                 @if paths.peek().is_none() {
                     span { (path_segment) }
                 } @else {
-                    button data-path=(full_path) nm-bind={"onclick: () => get('/browser', {path: this.dataset.path})"} { (path_segment) }
+                    button data-path=(full_path) nm-bind={"onclick: () => get('browser', {path: this.dataset.path})"} { (path_segment) }
                 }
             }
         }
@@ -171,14 +345,34 @@ pub async fn actions(req: ServerRequest) -> Result<ServerResponse, ServerRespons
 
     let query: BrowserActionsQuery = req.extract_query()?;
 
+    let info = send_req!(req, Stat(query.path.clone()))?.map(|item| {
+        format!(
+            "{}\nSize: {}\nModified: {}\nPermissions: {}\nOwner: {}\nGroup: {}",
+            item.path,
+            item.size.map_or_else(
+                || "--".into(),
+                |size| pretty_bytes(size, Some(2)).to_string()
+            ),
+            pretty_modified(item.modified),
+            pretty_mode(item.mode),
+            item.owner,
+            item.group,
+        )
+    });
+
     let content = html! {
         div #actions-list {
             (default_actions(&query.current_path))
-            @if matches!(query.kind, FileKind::TextFile | FileKind::BinaryFile | FileKind::Directory) {
+            @if let Some(info) = info {
+                button title="Properties" data-info=(info) nm-bind="onclick: () => alert(this.dataset.info)" {
+                    (Icon::new("fa6-solid-circle-info"))
+                }
+            }
+            @if matches!(query.kind, FileKind::TextFile | FileKind::BinaryFile | FileKind::Image | FileKind::Directory) {
                 button title="Rename" nm-bind={"
                     onclick: () => {
                         let new_name = prompt('Enter a new name:');
-                        if (new_name) post('/browser/actions/rename', {
+                        if (new_name) post('browser/actions/rename', {
                             path: '"(query.path)"',
                             new_name
                         });
@@ -186,27 +380,90 @@ pub async fn actions(req: ServerRequest) -> Result<ServerResponse, ServerRespons
                 "} {
                     (Icon::new("fa6-solid-i-cursor"))
                 }
+                button title="Change Permissions" nm-bind={"
+                    onclick: () => {
+                        let mode = prompt('Enter a new mode in octal, like 644:');
+                        if (mode) post('browser/actions/chmod', {
+                            path: '"(query.path)"',
+                            mode
+                        });
+                    }
+                "} {
+                    (Icon::new("fa6-solid-lock"))
+                }
+                button title="Change Owner" nm-bind={"
+                    onclick: () => {
+                        let owner = prompt('Enter a new owner, like user or user:group:');
+                        if (owner) post('browser/actions/chown', {
+                            path: '"(query.path)"',
+                            owner
+                        });
+                    }
+                "} {
+                    (Icon::new("fa6-solid-user"))
+                }
+                button title="Move" nm-bind={"
+                    onclick: () => {
+                        let dest = prompt('Enter a new path:', '"(query.path)"');
+                        if (dest) post('browser/actions/move', {
+                            path: '"(query.path)"',
+                            dest
+                        });
+                    }
+                "} {
+                    (Icon::new("fa6-solid-arrows-up-down-left-right"))
+                }
+                button title="Copy" nm-bind={"
+                    onclick: () => {
+                        let dest = prompt('Enter a path to copy to:', '"(query.path)"');
+                        if (dest) post('browser/actions/copy', {
+                            path: '"(query.path)"',
+                            dest
+                        });
+                    }
+                "} {
+                    (Icon::new("fa6-solid-copy"))
+                }
             }
-            @if matches!(query.kind, FileKind::TextFile | FileKind::BinaryFile)  {
+            @if matches!(query.kind, FileKind::Image) {
+                button .thumbnail-button title="Preview" nm-bind={"
+                    onclick: () => {
+                        window.open('browser/actions/preview?path="(query.path)"')
+                    }
+                "} {
+                    img src={ "browser/actions/thumbnail?size=64&path=" (query.path) } alt="";
+                }
+            }
+            @if matches!(query.kind, FileKind::TextFile | FileKind::BinaryFile | FileKind::Image)  {
                 button title="Delete" nm-bind={"
                     onclick: () => { 
                         if (confirm('Are you sure you want to delete this file?'))
-                            post('/browser/actions/delete-file', {path: '"(query.path)"'});
+                            post('browser/actions/delete-file', {path: '"(query.path)"'});
                     }
                 "} { (Icon::new("fa6-solid-trash")) }
                 button title="Download" nm-bind={"
                     onclick: () => { 
-                        window.open('/browser/actions/download?path="(query.path)"')
+                        window.open('browser/actions/download?path="(query.path)"')
                     }
                 "} { (Icon::new("fa6-solid-file-arrow-down")) }
+                button title="Checksum" nm-bind={"
+                    onclick: () => {
+                        window.open('browser/actions/checksum?path="(query.path)"')
+                    }
+                "} { (Icon::new("fa6-solid-hashtag")) }
             }
             @if matches!(query.kind, FileKind::Directory)  {
                 button title="Delete" nm-bind={"
                     onclick: () => { 
                         if (confirm('Are you sure you want to delete this folder?'))
-                            post('/browser/actions/delete-folder', {path: '"(query.path)"'});
+                            post('browser/actions/delete-folder', {path: '"(query.path)"'});
                     }
                 "} { (Icon::new("fa6-solid-trash")) }
+                button title="Download as .tar.gz" nm-bind={"
+                    onclick: () => {
+                        window.open('browser/actions/download-folder?path="(query.path)"')
+                    }
+                "} { (Icon::new("fa6-solid-file-arrow-down")) }
             }
         }
     };
@@ -216,9 +473,15 @@ pub async fn actions(req: ServerRequest) -> Result<ServerResponse, ServerRespons
 
 fn default_actions(current_path: &str) -> Markup {
     html! {
-        button title="Refresh" nm-bind={ "onclick: () => get('/browser', {path: '"(current_path)"'})" } {
+        button title="Refresh" nm-bind={ "onclick: () => get('browser', {path: '"(current_path)"'})" } {
             (Icon::new("fa6-solid-rotate"))
         }
+        button title="Search" nm-bind={"
+            onclick: () => {
+                let pattern = prompt('Enter a file name or pattern to search for:');
+                if (pattern) get('browser/search', {path: '"(current_path)"', pattern});
+            }
+        "} { (Icon::new("fa6-solid-magnifying-glass")) }
         button title="Hide Hidden Files" nm-bind="
             onclick: () => viewHidden = true,
             hidden: () => viewHidden,
@@ -230,32 +493,32 @@ fn default_actions(current_path: &str) -> Markup {
         button title="New File" nm-bind={"
             onclick: () => { 
                 let name = prompt('Enter a file name:');
-                if (name) post('/browser/actions/new-file', {parent: '"(current_path)"', name});
+                if (name) post('browser/actions/new-file', {parent: '"(current_path)"', name});
             }
         "} { (Icon::new("fa6-solid-file-medical")) }
         button title="New Folder" nm-bind={"
             onclick: () => { 
                 let name = prompt('Enter a folder name:');
-                if (name) post('/browser/actions/new-folder', {parent: '"(current_path)"', name});
+                if (name) post('browser/actions/new-folder', {parent: '"(current_path)"', name});
             }
         "} { (Icon::new("fa6-solid-folder-plus")) }
         button title="Upload" onclick="this.firstChild.click()" {
             input type="file" hidden nm-bind={"
-                onchange: () => {
+                onchange: async () => {
                     let file = this.files[0];
                     if (!file) return;
-    
-                    let reader = new FileReader();
-                    reader.readAsDataURL(file);
-                    reader.addEventListener('load', () => {
-                        let data = reader.result.replace(/^data:.*;base64,/, '');
-    
-                        post('/browser/actions/upload', {
-                            parent: '"(current_path)"',
-                            name: file.name,
-                            data
-                        })
-                    });
+
+                    let query = {parent: '"(current_path)"', name: file.name, size: file.size, offset: 0};
+
+                    // Offer to pick up where an interrupted upload of the same name left off
+                    let res = await fetch(`browser/actions/upload-status?${new URLSearchParams(query)}`);
+                    let received = res.ok ? Number(await res.text()) : 0;
+                    if (received > 0 && received < file.size
+                        && confirm(`Resume the previous upload of ${file.name}?`)) {
+                        query.offset = received;
+                    }
+
+                    post(`browser/actions/upload?${new URLSearchParams(query)}`, file.slice(query.offset));
                 }
             "};
             (Icon::new("fa6-solid-file-arrow-up"))
@@ -263,6 +526,29 @@ fn default_actions(current_path: &str) -> Markup {
     }
 }
 
+macro_rules! send_file_act {
+    ($req:expr, $variant:ident($data:expr)) => {{
+        use proto::frontend::FileAction;
+
+        send_req!($req, FileAction(FileAction::$variant($data)))?.map_err(|err| {
+            ServerResponse::new()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(format!("file action failed: {err}"))
+        })
+    }};
+}
+
+fn get_parent(path: &str) -> Result<&str, ServerResponse> {
+    let parent = Path::new(path).parent().ok_or(
+        ServerResponse::new()
+            .status(StatusCode::BAD_REQUEST)
+            .body("can't get parent of path"),
+    )?;
+
+    // Parent of a valid string is also a valid string
+    Ok(parent.to_str().unwrap())
+}
+
 #[derive(Deserialize)]
 pub struct NewQuery {
     parent: String,
@@ -278,7 +564,9 @@ pub async fn new_file(mut req: ServerRequest) -> Result<ServerResponse, ServerRe
     path.push(query.name);
     let path = path.to_str().unwrap();
 
-    send_act!(req, NewFile(path.into()))?;
+    req.audit(&format!("created file {path}"));
+
+    send_file_act!(req, NewFile(path.into()))?;
 
     Ok(ServerResponse::new().redirect(
         RedirectType::SeeOther,
@@ -295,7 +583,9 @@ pub async fn new_folder(mut req: ServerRequest) -> Result<ServerResponse, Server
     let path = Path::new(&query.parent).join(Path::new(&query.name));
     let path = path.into_os_string().into_string().unwrap();
 
-    send_act!(req, NewFolder(path))?;
+    req.audit(&format!("created folder {path}"));
+
+    send_file_act!(req, NewFolder(path))?;
 
     Ok(ServerResponse::new().redirect(
         RedirectType::SeeOther,
@@ -314,22 +604,136 @@ pub async fn rename(mut req: ServerRequest) -> Result<ServerResponse, ServerResp
 
     let query: RenameQuery = req.extract_form().await?;
 
-    let parent = Path::new(&query.path).parent().ok_or(
-        ServerResponse::new()
-            .status(StatusCode::BAD_REQUEST)
-            .body("can't get parent of path"),
-    )?;
-    let parent = parent.to_str().unwrap();
+    let parent = get_parent(&query.path)?;
 
     let new_path = Path::new(&query.path).with_file_name(&query.new_name);
     let new_path = new_path.into_os_string().into_string().unwrap();
 
-    let action = RenameAction {
+    let action = TransferAction {
         from: query.path.clone(),
         to: new_path,
     };
 
-    send_act!(req, Rename(action))?;
+    req.audit(&format!("renamed {} to {}", action.from, action.to));
+
+    send_file_act!(req, Rename(action))?;
+
+    Ok(ServerResponse::new().redirect(RedirectType::SeeOther, &format!("/browser?path={parent}")))
+}
+
+#[derive(Deserialize)]
+pub struct ChmodQuery {
+    path: String,
+    mode: String,
+    #[serde(default)]
+    force: bool,
+}
+
+pub async fn chmod(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: ChmodQuery = req.extract_form().await?;
+
+    let parent = get_parent(&query.path)?;
+
+    let mode = u32::from_str_radix(&query.mode, 8)
+        .ok()
+        .filter(|&mode| mode <= 0o7777)
+        .ok_or_else(|| {
+            ServerResponse::new()
+                .status(StatusCode::BAD_REQUEST)
+                .body("mode must be an octal number, like 644")
+        })?;
+
+    req.audit(&format!("changed mode of {} to {mode:04o}", query.path));
+
+    let action = ChmodAction {
+        path: query.path.clone(),
+        mode,
+        force: query.force,
+    };
+
+    send_file_act!(req, Chmod(action))?;
+
+    Ok(ServerResponse::new().redirect(RedirectType::SeeOther, &format!("/browser?path={parent}")))
+}
+
+#[derive(Deserialize)]
+pub struct ChownQuery {
+    path: String,
+    // Either "owner", "owner:group", or ":group"
+    owner: String,
+    #[serde(default)]
+    force: bool,
+}
+
+pub async fn chown(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: ChownQuery = req.extract_form().await?;
+
+    let parent = get_parent(&query.path)?;
+
+    let (owner, group) = query.owner.split_once(':').unwrap_or((&query.owner, ""));
+    let non_empty = |name: &str| (!name.is_empty()).then(|| name.to_string());
+
+    req.audit(&format!(
+        "changed ownership of {} to {}",
+        query.path, query.owner
+    ));
+
+    let action = ChownAction {
+        path: query.path.clone(),
+        owner: non_empty(owner),
+        group: non_empty(group),
+        force: query.force,
+    };
+
+    send_file_act!(req, Chown(action))?;
+
+    Ok(ServerResponse::new().redirect(RedirectType::SeeOther, &format!("/browser?path={parent}")))
+}
+
+#[derive(Deserialize)]
+pub struct TransferQuery {
+    path: String,
+    dest: String,
+}
+
+pub async fn move_path(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: TransferQuery = req.extract_form().await?;
+
+    let parent = get_parent(&query.path)?;
+
+    let action = TransferAction {
+        from: query.path.clone(),
+        to: query.dest,
+    };
+
+    req.audit(&format!("moved {} to {}", action.from, action.to));
+
+    send_file_act!(req, Move(action))?;
+
+    Ok(ServerResponse::new().redirect(RedirectType::SeeOther, &format!("/browser?path={parent}")))
+}
+
+pub async fn copy(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: TransferQuery = req.extract_form().await?;
+
+    let parent = get_parent(&query.path)?;
+
+    let action = TransferAction {
+        from: query.path.clone(),
+        to: query.dest,
+    };
+
+    req.audit(&format!("copied {} to {}", action.from, action.to));
+
+    send_file_act!(req, Copy(action))?;
 
     Ok(ServerResponse::new().redirect(RedirectType::SeeOther, &format!("/browser?path={parent}")))
 }
@@ -344,14 +748,11 @@ pub async fn delete_file(mut req: ServerRequest) -> Result<ServerResponse, Serve
 
     let query: FileQuery = req.extract_form().await?;
 
-    let parent = Path::new(&query.path).parent().ok_or(
-        ServerResponse::new()
-            .status(StatusCode::BAD_REQUEST)
-            .body("can't get parent of path"),
-    )?;
-    let parent = parent.to_str().unwrap();
+    let parent = get_parent(&query.path)?;
+
+    req.audit(&format!("deleted file {}", query.path));
 
-    send_act!(req, DeleteFile(query.path.clone()))?;
+    send_file_act!(req, DeleteFile(query.path.clone()))?;
 
     Ok(ServerResponse::new().redirect(RedirectType::SeeOther, &format!("/browser?path={parent}")))
 }
@@ -361,14 +762,11 @@ pub async fn delete_folder(mut req: ServerRequest) -> Result<ServerResponse, Ser
 
     let query: FileQuery = req.extract_form().await?;
 
-    let parent = Path::new(&query.path).parent().ok_or(
-        ServerResponse::new()
-            .status(StatusCode::BAD_REQUEST)
-            .body("can't get parent of path"),
-    )?;
-    let parent = parent.to_str().unwrap();
+    let parent = get_parent(&query.path)?;
 
-    send_act!(req, DeleteFolder(query.path.clone()))?;
+    req.audit(&format!("deleted folder {}", query.path));
+
+    send_file_act!(req, DeleteFolder(query.path.clone()))?;
 
     Ok(ServerResponse::new().redirect(RedirectType::SeeOther, &format!("/browser?path={parent}")))
 }
@@ -378,9 +776,120 @@ pub async fn download(req: ServerRequest) -> Result<ServerResponse, ServerRespon
 
     let query: FileQuery = req.extract_query()?;
 
-    let data = send_req!(req, Download(query.path))?;
+    let backend = req.extract_backends()?.current_backend.handle;
 
-    Ok(ServerResponse::new().body(data))
+    let id = send_req!(req, Download(query.path))?.ok_or_else(|| {
+        ServerResponse::new()
+            .status(StatusCode::NOT_FOUND)
+            .body("failed to open file")
+    })?;
+
+    Ok(ServerResponse::new().stream_body(backend.read_stream(id)))
+}
+
+pub async fn preview(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: FileQuery = req.extract_query()?;
+
+    let backend = req.extract_backends()?.current_backend.handle;
+
+    let data = send_req!(req, Preview(query.path))?.map_err(|err| {
+        ServerResponse::new()
+            .status(StatusCode::BAD_REQUEST)
+            .body(err)
+    })?;
+
+    Ok(ServerResponse::new()
+        .header(header::CONTENT_TYPE, data.mime)
+        .stream_body(backend.read_stream(data.stream_id)))
+}
+
+fn default_thumbnail_size() -> u32 {
+    256
+}
+
+#[derive(Deserialize)]
+pub struct ThumbnailQuery {
+    path: String,
+    #[serde(default = "default_thumbnail_size")]
+    size: u32,
+}
+
+pub async fn thumbnail(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: ThumbnailQuery = req.extract_query()?;
+
+    let action = ThumbnailAction {
+        path: query.path,
+        size: query.size,
+    };
+
+    let data = send_req!(req, Thumbnail(action))?.map_err(|err| {
+        ServerResponse::new()
+            .status(StatusCode::BAD_REQUEST)
+            .body(err)
+    })?;
+
+    Ok(ServerResponse::new()
+        .header(header::CONTENT_TYPE, "image/jpeg")
+        .body(data))
+}
+
+pub async fn checksum(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: FileQuery = req.extract_query()?;
+
+    let data = send_req!(req, Checksum(query.path.clone()))?.ok_or_else(|| {
+        ServerResponse::new()
+            .status(StatusCode::NOT_FOUND)
+            .body("failed to read file")
+    })?;
+
+    // Same format as sha256sum, so the output can be checked with `sha256sum -c`
+    let body = format!(
+        "{}  {}\n\nSize: {} bytes\n",
+        data.sha256, query.path, data.size
+    );
+
+    Ok(ServerResponse::new()
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(body))
+}
+
+pub async fn download_folder(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: FileQuery = req.extract_query()?;
+
+    let backend = req.extract_backends()?.current_backend.handle;
+
+    // Keep the header value plain ASCII, so that it doesn't need any quoting or encoding
+    let name: String = Path::new(&query.path)
+        .file_name()
+        .map_or("root".into(), |name| name.to_string_lossy())
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect();
+
+    let id = send_req!(req, DownloadArchive(query.path))?.ok_or_else(|| {
+        ServerResponse::new()
+            .status(StatusCode::NOT_FOUND)
+            .body("failed to open folder")
+    })?;
+
+    Ok(ServerResponse::new()
+        .header(header::CONTENT_TYPE, "application/gzip")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{name}.tar.gz\""),
+        )
+        .stream_body(backend.read_stream(id)))
 }
 
 #[derive(Deserialize)]
@@ -394,42 +903,132 @@ pub async fn save(mut req: ServerRequest) -> Result<ServerResponse, ServerRespon
 
     let query: SaveForm = req.extract_form().await?;
 
-    let action = UploadAction {
+    req.audit(&format!("saved file {}", query.path));
+
+    let action = SaveFileAction {
         path: query.path,
         data: query.data.into_bytes(),
     };
-    send_act!(req, Upload(action))?;
+    send_req!(req, SaveFile(action))?.map_err(|err| {
+        ServerResponse::new()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(format!("couldn't save file: {err}"))
+    })?;
 
     Ok(ServerResponse::new())
 }
 
+pub async fn upload_status(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: NewQuery = req.extract_query()?;
+
+    let path = Path::new(&query.parent).join(&query.name);
+    let path = path.into_os_string().into_string().unwrap();
+
+    let received = send_req!(req, UploadStatus(path))?.unwrap_or(0);
+
+    Ok(ServerResponse::new()
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(received.to_string()))
+}
+
 #[derive(Deserialize)]
-pub struct UploadForm {
-    name: String,
+pub struct UploadQuery {
     parent: String,
-    data: String,
+    name: String,
+    // Size of the whole file, not just the part being sent
+    size: u64,
+    // Where to resume an interrupted upload from, the body only contains the rest of the file
+    #[serde(default)]
+    offset: u64,
 }
 
 pub async fn upload(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
     req.check_login()?;
 
-    let query: UploadForm = req.extract_form().await?;
+    let query: UploadQuery = req.extract_query()?;
+
+    let max_size = req.config().max_upload_size;
+    if max_size > 0 && query.size > max_size {
+        return Err(ServerResponse::new()
+            .status(StatusCode::PAYLOAD_TOO_LARGE)
+            .body(format!(
+                "file is larger than the maximum upload size of {}",
+                pretty_bytes(max_size, Some(2))
+            )));
+    }
 
     let mut path = PathBuf::from(&query.parent);
     path.push(query.name);
     let path = path.to_str().unwrap();
 
-    let data = BASE64.decode(query.data.as_bytes()).map_err(|_| {
-        ServerResponse::new()
-            .status(StatusCode::BAD_REQUEST)
-            .body("invalid base64")
-    })?;
+    if query.offset > 0 {
+        req.audit(&format!(
+            "resumed upload of {path} from byte {}",
+            query.offset
+        ));
+    } else {
+        req.audit(&format!("uploaded file {path}"));
+    }
 
-    let action = UploadAction {
-        path: path.into(),
-        data,
-    };
-    send_act!(req, Upload(action))?;
+    let mut body = req.extract_body()?;
+    let mut buf = Vec::with_capacity(UPLOAD_CHUNK_SIZE);
+    let mut offset = query.offset;
+
+    // Forward the upload in chunks as it comes in, instead of collecting it all into memory
+    loop {
+        let frame = req.with_read_timeout(body.frame()).await?;
+        let frame = frame.transpose().map_err(|_| {
+            ServerResponse::new()
+                .status(StatusCode::BAD_REQUEST)
+                .body("failed to read upload")
+        })?;
+        let last = frame.is_none();
+
+        if let Some(data) = frame.and_then(|frame| frame.into_data().ok()) {
+            buf.extend_from_slice(&data);
+        }
+
+        // The announced size is what was checked against the limit, so don't write past it
+        if offset + buf.len() as u64 > query.size {
+            return Err(ServerResponse::new()
+                .status(StatusCode::BAD_REQUEST)
+                .body("upload is larger than the announced size"));
+        }
+
+        if buf.len() >= UPLOAD_CHUNK_SIZE || last {
+            let data = std::mem::replace(&mut buf, Vec::with_capacity(UPLOAD_CHUNK_SIZE));
+            let len = data.len() as u64;
+            // Leave an upload that ended early as a partial file, so that it can be resumed
+            let complete = last && offset + len == query.size;
+
+            let action = UploadChunkAction {
+                path: path.into(),
+                offset,
+                data,
+                last: complete,
+            };
+
+            if !send_req!(req, UploadChunk(action))? {
+                return Err(ServerResponse::new()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body("failed to write upload"));
+            }
+
+            offset += len;
+        }
+
+        if last {
+            break;
+        }
+    }
+
+    if offset < query.size {
+        return Err(ServerResponse::new()
+            .status(StatusCode::BAD_REQUEST)
+            .body("upload ended before the whole file was received"));
+    }
 
     Ok(ServerResponse::new().redirect(
         RedirectType::SeeOther,
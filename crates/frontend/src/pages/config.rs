@@ -1,5 +1,6 @@
 use hyper::StatusCode;
 use maud::html;
+use serde::Deserialize;
 use tokio::fs;
 
 use crate::http::{request::ServerRequest, response::ServerResponse};
@@ -31,9 +32,20 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
         section {
             h2 { "Frontend Config" }
 
-            pre {
-                (frontend_cfg)
+            code-editor {
+                textarea #frontend-config spellcheck="false" {
+                    (frontend_cfg)
+                }
+                pre {}
             }
+            br;
+            button nm-bind="
+                onclick: () => {
+                    if (confirm('Save the config and restart the frontend?'))
+                        post('config/frontend', {data: document.getElementById('frontend-config').value});
+                }
+            " { "Save and Restart" }
+            p #config-status {}
         }
         br;
         section {
@@ -47,3 +59,32 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
 
     template(&req, content)
 }
+
+#[derive(Deserialize)]
+pub struct ConfigForm {
+    data: String,
+}
+
+pub async fn save(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let form: ConfigForm = req.extract_form().await?;
+
+    req.audit("edited the frontend config");
+
+    config::frontend::save_config_file(&form.data).map_err(|err| {
+        ServerResponse::new()
+            .status(StatusCode::BAD_REQUEST)
+            .body(format!("couldn't save config: {err:#}"))
+    })?;
+
+    req.shutdown().restart();
+
+    let content = html! {
+        p #config-status nm-bind="_: () => setTimeout(() => location.reload(), 5000)" {
+            "Saved, the frontend is restarting..."
+        }
+    };
+
+    template(&req, content)
+}
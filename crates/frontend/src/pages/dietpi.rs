@@ -0,0 +1,126 @@
+use hyper::StatusCode;
+use maud::html;
+use pretty_bytes_typed::pretty_bytes;
+use proto::frontend::DietPiSettingAction;
+
+use crate::http::{
+    request::ServerRequest,
+    response::{RedirectType, ServerResponse},
+};
+
+use super::template::{send_req, template};
+
+pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let data = send_req!(req, DietPiSettings)?;
+
+    let content = html! {
+        section {
+            h2 { "DietPi Settings" }
+
+            table .management-table {
+                tr {
+                    td { "Swap File Size" }
+                    td {
+                        @if let Some(swap_size) = data.swap_size {
+                            form .setting-form method="POST" action="dietpi" {
+                                input type="hidden" name="setting" value="swap_size";
+                                input type="number" name="value" min="0" value=(swap_size);
+                                " MiB "
+                                input type="submit" value="Apply";
+                            }
+                            small { "0 disables the swap file, 1 sizes it automatically" }
+                        } @else {
+                            "Unavailable"
+                        }
+                    }
+                }
+                tr {
+                    td { "Swap Location" }
+                    td {
+                        @if let Some(swap_location) = &data.swap_location {
+                            form .setting-form method="POST" action="dietpi" {
+                                input type="hidden" name="setting" value="swap_location";
+                                input type="text" name="value" required value=(swap_location);
+                                " "
+                                input type="submit" value="Apply";
+                            }
+                            small { "A path for the swap file, or \"zram\" to swap to compressed memory instead" }
+                        } @else {
+                            "Unavailable"
+                        }
+                    }
+                }
+                tr {
+                    td { "CPU Governor" }
+                    td {
+                        @if let Some(governor) = &data.cpu_governor && !data.available_governors.is_empty() {
+                            form .setting-form method="POST" action="dietpi" {
+                                input type="hidden" name="setting" value="cpu_governor";
+                                select name="value" {
+                                    @for option in &data.available_governors {
+                                        option value=(option) selected[option == governor] { (option) }
+                                    }
+                                }
+                                " "
+                                input type="submit" value="Apply";
+                            }
+                        } @else {
+                            "Unavailable"
+                        }
+                    }
+                }
+            }
+        }
+        br;
+        section {
+            h2 { "Active Swap" }
+
+            @if data.swap_devices.is_empty() {
+                p { "No swap is in use" }
+            } @else {
+                table {
+                    tr {
+                        th { "Device" }
+                        th { "Type" }
+                        th { "Usage" }
+                        th { "Priority" }
+                    }
+                    @for device in &data.swap_devices {
+                        @let pretty_used = pretty_bytes(device.used, Some(2));
+                        @let pretty_size = pretty_bytes(device.size, Some(2));
+
+                        tr {
+                            td { (device.path) }
+                            td { (device.kind) }
+                            td { (pretty_used) " / " (pretty_size) }
+                            td { (device.priority) }
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    template(&req, content)
+}
+
+pub async fn form(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let action: DietPiSettingAction = req.extract_form().await?;
+
+    req.audit(&format!(
+        "set DietPi setting {:?} to {}",
+        action.setting, action.value
+    ));
+
+    send_req!(req, SetDietPiSetting(action))?.map_err(|err| {
+        ServerResponse::new()
+            .status(StatusCode::BAD_REQUEST)
+            .body(format!("failed to change setting: {err}"))
+    })?;
+
+    Ok(ServerResponse::new().redirect(RedirectType::SeeOther, "/dietpi"))
+}
@@ -0,0 +1,47 @@
+use maud::html;
+use pretty_bytes_typed::pretty_bytes_binary;
+
+use crate::http::{request::ServerRequest, response::ServerResponse};
+
+use super::template::{send_req, template};
+
+pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let data = send_req!(req, Docker)?;
+
+    let content = html! {
+        section {
+            h2 { "Docker Containers" }
+            @if let Some(data) = data {
+                table {
+                    tr {
+                        th { "Name" }
+                        th { "Image" }
+                        th { "Status" }
+                        th { "CPU Usage" }
+                        th { "RAM Usage" }
+                    }
+                    @for container in data.containers {
+                        @let is_running = container.state == "running";
+                        tr {
+                            td { (container.name) }
+                            td { (container.image) }
+                            td { (container.status) }
+                            td {
+                                @if is_running { (container.cpu) "%" } @else { "--" }
+                            }
+                            td {
+                                @if is_running { (pretty_bytes_binary(container.mem, Some(0))) } @else { "--" }
+                            }
+                        }
+                    }
+                }
+            } @else {
+                p { "Docker isn't installed or running on this system." }
+            }
+        }
+    };
+
+    template(&req, content)
+}
@@ -0,0 +1,26 @@
+use hyper::{StatusCode, header};
+
+use crate::http::{request::ServerRequest, response::ServerResponse};
+
+// Probes don't log in, and are made often, so these skip authentication and don't ask the backends
+// for anything
+
+pub async fn healthz(_req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    Ok(ServerResponse::new()
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body("ok"))
+}
+
+// Only ready once there's a backend connected, since otherwise there's nothing to show
+pub async fn readyz(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    if req.all_backends().is_empty() {
+        return Err(ServerResponse::new()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body("no backends connected"));
+    }
+
+    Ok(ServerResponse::new()
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body("ok"))
+}
@@ -4,17 +4,28 @@ use ring::digest::{SHA512, digest};
 use serde::Deserialize;
 
 use crate::http::{
+    audit,
     request::ServerRequest,
     response::{RedirectType, ServerResponse},
 };
 
 use super::template::template;
 
+pub fn hash_password(pass: &str) -> String {
+    let hash = digest(&SHA512, pass.as_bytes());
+    data_encoding::HEXLOWER.encode(hash.as_ref())
+}
+
 pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
     if !req.config().enable_login {
         return Err(ServerResponse::new().redirect(RedirectType::SeeOther, "/"));
     }
 
+    // Nothing could ever match, so a password has to be set first
+    if req.config().hash.is_empty() {
+        return Err(ServerResponse::new().redirect(RedirectType::SeeOther, "/setup"));
+    }
+
     let content = html! {
         section {
             h2 { "Login" }
@@ -43,22 +54,23 @@ pub async fn form(mut req: ServerRequest) -> Result<ServerResponse, ServerRespon
     let hash = req.config().hash.clone();
     let form: LoginForm = req.extract_form().await?;
 
-    let form_hash = digest(&SHA512, form.pass.as_bytes());
-    let form_hash = data_encoding::HEXLOWER.encode(form_hash.as_ref());
-
-    if form_hash == hash {
+    if !hash.is_empty() && hash_password(&form.pass) == hash {
         let logins = req.extract_logins();
         let mut logins = logins.get();
 
         let token = logins.new_token();
 
+        req.audit(&format!("logged in as {}", audit::session_subject(&token)));
+
         Ok(ServerResponse::new()
             .redirect(RedirectType::SeeOther, "/")
             .header(
                 header::SET_COOKIE,
-                format!("token={token}; Max-Age=3600; Path=/; HttpOnly"),
+                format!("token={token}; Max-Age=3600; Path=/; HttpOnly; SameSite=Lax"),
             ))
     } else {
+        req.audit("failed to log in");
+
         Err(ServerResponse::new().redirect(RedirectType::SeeOther, "/login"))
     }
 }
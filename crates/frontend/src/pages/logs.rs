@@ -0,0 +1,116 @@
+use futures_util::{SinkExt, StreamExt};
+use hyper::StatusCode;
+use maud::html;
+use proto::frontend::TailAction;
+use serde::{Deserialize, Serialize};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::http::{
+    compression::MessageCompressor, heartbeat::Heartbeat, request::ServerRequest,
+    response::ServerResponse,
+};
+
+use super::template::{send_req, template};
+
+fn default_lines() -> u32 {
+    100
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LogQuery {
+    #[serde(default)]
+    path: String,
+    #[serde(default = "default_lines")]
+    lines: u32,
+}
+
+pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: LogQuery = req.extract_query()?;
+
+    let content = html! {
+        section {
+            h2 { "Logs" }
+            form .log-form method="GET" action="logs" {
+                label {
+                    "File "
+                    input type="text" name="path" placeholder="e.g. /var/log/nginx/error.log" value=(query.path);
+                }
+                label {
+                    "Lines "
+                    input type="number" name="lines" min="0" value=(query.lines);
+                }
+                input type="submit" value="Follow";
+            }
+            @if !query.path.is_empty() {
+                log-viewer src={ "logs/ws?" (serde_urlencoded::to_string(&query).unwrap()) } {}
+            }
+        }
+    };
+
+    template(&req, content)
+}
+
+pub async fn socket(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    let req = req.check_socket_login()?;
+
+    let query: LogQuery = req.extract_query()?;
+
+    let backend = req.extract_backends()?.current_backend.handle;
+
+    req.audit(&format!("followed log {}", query.path));
+
+    let action = TailAction {
+        path: query.path,
+        lines: query.lines,
+    };
+
+    let id = send_req!(req, TailLog(action))?.ok_or_else(|| {
+        ServerResponse::new()
+            .status(StatusCode::FORBIDDEN)
+            .body("file is outside of the configured log paths")
+    })?;
+
+    // Created before upgrading, so that the backend stops following if the upgrade fails
+    let mut stream = Box::pin(backend.read_stream(id));
+    let mut shutdown = req.shutdown();
+    let mut compressor = MessageCompressor::new(req.websocket_compression());
+
+    let ws_idle_timeout = req.websocket_idle_timeout();
+
+    req.extract_websocket(async move |mut ws| {
+        let mut heartbeat = Heartbeat::new(ws_idle_timeout);
+
+        loop {
+            tokio::select! {
+                chunk = stream.next() => {
+                    let Some(Ok(chunk)) = chunk else {
+                        break;
+                    };
+
+                    if ws.send(Message::binary(compressor.compress(chunk))).await.is_err() {
+                        break;
+                    }
+                }
+                msg = ws.next() => {
+                    // Nothing is expected from the client, other than it disconnecting
+                    if !matches!(msg, Some(Ok(_))) {
+                        break;
+                    }
+
+                    heartbeat.seen();
+                }
+                alive = heartbeat.tick() => {
+                    if !alive || ws.send(Message::Ping(Default::default())).await.is_err() {
+                        break;
+                    }
+                }
+                _ = shutdown.wait() => {
+                    let _ = ws.close(None).await;
+                    break;
+                }
+            }
+        }
+    })
+}
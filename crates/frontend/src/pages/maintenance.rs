@@ -0,0 +1,63 @@
+use hyper::{StatusCode, header};
+use maud::{DOCTYPE, html};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+use crate::http::{request::ServerRequest, response::ServerResponse};
+
+// Seconds for clients to wait before trying again
+const RETRY_AFTER: &str = "60";
+const DEFAULT_MESSAGE: &str = "The dashboard is down for maintenance, and will be back shortly.";
+
+pub fn page(req: ServerRequest, message: &str) -> ServerResponse {
+    let message = match message.trim() {
+        "" => DEFAULT_MESSAGE,
+        message => message,
+    };
+
+    // Sessions that are already open keep going, only new ones are turned away
+    if req.is_websocket() {
+        return req.close_websocket(CloseCode::Again, "down for maintenance");
+    }
+
+    let resp = ServerResponse::new()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .header(header::RETRY_AFTER, RETRY_AFTER);
+
+    // Shown as an error in place of the content that was requested
+    if req.is_fixi() {
+        return resp
+            .header(header::CONTENT_TYPE, "text/plain;charset=UTF-8")
+            .body(message.to_string());
+    }
+
+    let page = html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1";
+                meta http-equiv="refresh" content=(RETRY_AFTER);
+
+                title { "DietPi Dashboard" }
+
+                base href={ (req.base_path()) "/" };
+
+                link rel="icon" href="favicon.svg" type="image/svg+xml";
+                link rel="stylesheet" href="static/main.css";
+            }
+            body .maintenance {
+                h1 { "DietPi Dashboard" }
+
+                main {
+                    section {
+                        h2 { "Be Right Back" }
+                        p { (message) }
+                    }
+                }
+            }
+        }
+    };
+
+    resp.header(header::CONTENT_TYPE, "text/html;charset=UTF-8")
+        .body(page.into_string())
+}
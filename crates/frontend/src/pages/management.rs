@@ -1,17 +1,110 @@
-use std::time::Duration;
+use std::time::{Duration, UNIX_EPOCH};
 
-use maud::html;
+use hyper::StatusCode;
+use maud::{Markup, html};
+use pretty_bytes_typed::pretty_bytes;
+use proto::{
+    backend::NetworkUsageResponse,
+    frontend::{MountAction, PowerAction},
+};
+use serde::Deserialize;
 
-use crate::http::{request::ServerRequest, response::ServerResponse};
+use crate::http::{
+    request::ServerRequest,
+    response::{RedirectType, ServerResponse},
+};
 
 use super::template::{send_req, template};
 
+// Lists the flags that are set, in the same order as the firmware's bits
+fn describe_throttling(flags: [bool; 4]) -> String {
+    let names = [
+        "under-voltage",
+        "frequency capped",
+        "throttled",
+        "soft temperature limit",
+    ];
+
+    let set: Vec<_> = names
+        .into_iter()
+        .zip(flags)
+        .filter(|(_, set)| *set)
+        .map(|(name, _)| name)
+        .collect();
+
+    if set.is_empty() {
+        "none".into()
+    } else {
+        set.join(", ")
+    }
+}
+
+fn network_usage(usage: &NetworkUsageResponse) -> Markup {
+    let since = humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(usage.since));
+
+    html! {
+        table #network-usage .management-table {
+            tr {
+                td { "Since" }
+                td { (since) }
+            }
+            tr {
+                td { "Sent" }
+                td { (pretty_bytes(usage.sent, Some(2))) }
+            }
+            tr {
+                td { "Received" }
+                td { (pretty_bytes(usage.recv, Some(2))) }
+            }
+            tr {
+                td { "Total" }
+                td { (pretty_bytes(usage.sent + usage.recv, Some(2))) }
+            }
+        }
+    }
+}
+
 pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
     req.check_login()?;
 
     let data = send_req!(req, Host)?;
+    let mounts = send_req!(req, Mounts)?;
+    let block_devices = send_req!(req, BlockDevices)?;
+    let usage = send_req!(req, NetworkUsage)?;
 
     let pretty_time = humantime::format_duration(Duration::from_secs(data.uptime));
+    let boot_time =
+        humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(data.boot_time));
+    // Worked out from the backend's own timestamps, so that a clock difference between it and the
+    // frontend doesn't matter
+    let backend_uptime = data
+        .uptime
+        .saturating_sub(data.backend_start.saturating_sub(data.boot_time));
+    let backend_uptime = humantime::format_duration(Duration::from_secs(backend_uptime));
+
+    let throttling = data.throttling.as_ref().map(|flags| {
+        let now = describe_throttling([
+            flags.under_voltage,
+            flags.freq_capped,
+            flags.throttled,
+            flags.soft_temp_limit,
+        ]);
+        let occurred = describe_throttling([
+            flags.under_voltage_occurred,
+            flags.freq_capped_occurred,
+            flags.throttled_occurred,
+            flags.soft_temp_limit_occurred,
+        ]);
+
+        format!("Now: {now}; since boot: {occurred}")
+    });
+
+    let load_avg = data.load_avg.map(|x| format!("{x:.2}")).join(", ");
+    // Divide by the number of CPUs, so that a load of 1.00 means the system is fully utilized
+    let norm_load_avg = data
+        .load_avg
+        .map(|x| format!("{:.2}", x / data.num_cpus.max(1) as f64))
+        .join(", ");
 
     let content = html! {
         section {
@@ -30,6 +123,18 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
                     td { "Uptime" }
                     td { (pretty_time) }
                 }
+                tr {
+                    td { "Booted" }
+                    td { (boot_time) }
+                }
+                tr {
+                    td { "Backend Uptime" }
+                    td { (backend_uptime) }
+                }
+                tr {
+                    td { "Load Average" }
+                    td { (load_avg) " (per CPU: " (norm_load_avg) ")" }
+                }
                 tr {
                     td { "Installed Packages" }
                     td { (data.num_pkgs) }
@@ -50,9 +155,274 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
                     td { "Architecture" }
                     td { (data.arch) }
                 }
+                @if let Some(throttling) = throttling {
+                    tr {
+                        td { "Throttling" }
+                        td { (throttling) }
+                    }
+                }
+            }
+        }
+        br;
+        section {
+            h2 { "Network Interfaces" }
+
+            table {
+                tr {
+                    th { "Name" }
+                    th { "Addresses" }
+                    th { "MAC Address" }
+                    th { "MTU" }
+                    th { "State" }
+                }
+                @for iface in &data.interfaces {
+                    tr {
+                        td { (iface.name) }
+                        td {
+                            @for (i, addr) in iface.addresses.iter().enumerate() {
+                                @if i > 0 { br; }
+                                (addr)
+                            }
+                        }
+                        td { (iface.mac) }
+                        td { (iface.mtu) }
+                        td { @if iface.up { "Up" } @else { "Down" } }
+                    }
+                }
+            }
+        }
+        br;
+        section {
+            h2 { "Filesystems" }
+
+            table {
+                tr {
+                    th { "Device" }
+                    th { "Mount Point" }
+                    th { "Type" }
+                    th { "Usage" }
+                }
+                @for disk in mounts.disks {
+                    @let pretty_used = pretty_bytes(disk.usage.used, Some(2));
+                    @let pretty_total = pretty_bytes(disk.usage.total, Some(2));
+                    @let percent = (disk.usage.used * 100).checked_div(disk.usage.total).unwrap_or(0);
+
+                    tr {
+                        td { (disk.name) }
+                        td { (disk.mnt_point) }
+                        td { (disk.fs_type) }
+                        td { (pretty_used) " / " (pretty_total) " (" (percent) "%)" }
+                    }
+                }
+            }
+        }
+        br;
+        section {
+            h2 { "Drives" }
+
+            @if block_devices.is_empty() {
+                p { "No drives found." }
+            } @else {
+                table .block-devices {
+                    tr {
+                        th { "Device" }
+                        th { "Model" }
+                        th { "Size" }
+                        th { "Filesystem" }
+                        th { "Mount Point" }
+                        th {}
+                    }
+                    @for device in block_devices {
+                        @let connection = if device.usb {
+                            " (USB)"
+                        } else if device.removable {
+                            " (removable)"
+                        } else {
+                            ""
+                        };
+
+                        tr {
+                            td { (device.name) (connection) }
+                            td { (device.model) }
+                            td { (pretty_bytes(device.size, Some(2))) }
+                            td {}
+                            td {}
+                            td {}
+                        }
+                        @for part in device.partitions {
+                            tr .partition {
+                                td { (part.name) }
+                                td {}
+                                td { (pretty_bytes(part.size, Some(2))) }
+                                td { (part.fs_type) }
+                                td { (part.mnt_point) }
+                                td {
+                                    @if !part.mnt_point.is_empty() {
+                                        form .setting-form
+                                            method="POST"
+                                            action="management/unmount"
+                                            onsubmit={"return confirm('Are you sure you want to unmount " (part.name) "?')"}
+                                        {
+                                            input type="hidden" name="device" value=(part.name);
+                                            input type="submit" value="Unmount";
+                                        }
+                                    } @else if !part.fs_type.is_empty() {
+                                        form .setting-form method="POST" action="management/mount" {
+                                            input type="hidden" name="device" value=(part.name);
+                                            input type="text" name="target" required value={"/mnt/" (part.name)};
+                                            input type="submit" value="Mount";
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        br;
+        section {
+            h2 { "Network Usage" }
+
+            p { "Data sent and received over all interfaces but loopback, kept across reboots. Reset it at the start of each billing period." }
+            (network_usage(&usage))
+            button nm-bind="
+                onclick: () => {
+                    if (confirm('Are you sure you want to reset the network usage?'))
+                        post('management/network-usage/reset');
+                }
+            " { "Reset" }
+        }
+        br;
+        section {
+            h2 { "Page Cache" }
+
+            p { "Writes out any pending changes, then drops cached files from memory to show how much of it is really in use." }
+            button nm-bind="onclick: () => post('management/drop-caches')" { "Drop Caches" }
+            p #drop-caches-status {}
+        }
+        br;
+        section {
+            h2 { "Power" }
+
+            .power-actions {
+                @for (action, label) in [("reboot", "Reboot"), ("poweroff", "Power Off"), ("halt", "Halt")] {
+                    button nm-bind={"
+                        onclick: () => {
+                            if (confirm('Are you sure you want to "(label.to_lowercase())" the system?'))
+                                post('management/power', {action: '"(action)"'});
+                        }
+                    "} { (label) }
+                }
             }
+            p #power-status {}
+        }
+    };
+
+    template(&req, content)
+}
+
+pub async fn mount(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let action: MountAction = req.extract_form().await?;
+
+    req.audit(&format!("mounted {} at {}", action.device, action.target));
+
+    send_req!(req, Mount(action))?.map_err(|err| {
+        ServerResponse::new()
+            .status(StatusCode::BAD_REQUEST)
+            .body(format!("couldn't mount drive: {err}"))
+    })?;
+
+    Ok(ServerResponse::new().redirect(RedirectType::SeeOther, "/management"))
+}
+
+#[derive(Deserialize)]
+pub struct UnmountForm {
+    device: String,
+}
+
+pub async fn unmount(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let form: UnmountForm = req.extract_form().await?;
+
+    req.audit(&format!("unmounted {}", form.device));
+
+    send_req!(req, Unmount(form.device))?.map_err(|err| {
+        ServerResponse::new()
+            .status(StatusCode::BAD_REQUEST)
+            .body(format!("couldn't unmount drive: {err}"))
+    })?;
+
+    Ok(ServerResponse::new().redirect(RedirectType::SeeOther, "/management"))
+}
+
+#[derive(Deserialize)]
+pub struct PowerForm {
+    action: PowerAction,
+}
+
+pub async fn power(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let form: PowerForm = req.extract_form().await?;
+
+    req.audit(&format!("ran {:?} on the system", form.action));
+
+    let delay = send_req!(req, Power(form.action))?.map_err(|err| {
+        ServerResponse::new()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(format!("power action failed: {err}"))
+    })?;
+
+    let verb = match form.action {
+        PowerAction::Reboot => "reboot",
+        PowerAction::Poweroff => "power off",
+        PowerAction::Halt => "halt",
+    };
+
+    let content = html! {
+        p #power-status { "The system will " (verb) " in " (delay) " seconds." }
+    };
+
+    template(&req, content)
+}
+
+pub async fn drop_caches(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    req.audit("dropped the page cache");
+
+    let data = send_req!(req, DropCaches)?.map_err(|err| {
+        ServerResponse::new()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(format!("couldn't drop caches: {err}"))
+    })?;
+
+    let pretty = |bytes| pretty_bytes(bytes, Some(2));
+
+    let content = html! {
+        p #drop-caches-status {
+            "Free memory went from " (pretty(data.before.free)) " to " (pretty(data.after.free))
+            ", and cached files from " (pretty(data.before.cached)) " to " (pretty(data.after.cached)) "."
         }
     };
 
     template(&req, content)
 }
+
+pub async fn reset_network_usage(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    req.audit("reset the network usage");
+
+    let usage = send_req!(req, ResetNetworkUsage)?.map_err(|err| {
+        ServerResponse::new()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(format!("couldn't reset network usage: {err}"))
+    })?;
+
+    template(&req, network_usage(&usage))
+}
@@ -0,0 +1,222 @@
+use std::fmt::{Display, Write};
+
+use hyper::{StatusCode, header};
+use proto::{backend::ResponseBackendMessage, frontend::RequestFrontendMessage};
+
+use crate::{
+    backend::BackendHandle,
+    http::{request::ServerRequest, response::ServerResponse},
+};
+
+struct MetricFamily {
+    name: &'static str,
+    kind: &'static str,
+    help: &'static str,
+    samples: String,
+}
+
+// Prometheus expects all samples of a metric to be grouped under a single HELP/TYPE header, so
+// samples are collected per metric and only rendered at the end
+#[derive(Default)]
+struct Metrics {
+    families: Vec<MetricFamily>,
+}
+
+impl Metrics {
+    fn add(
+        &mut self,
+        name: &'static str,
+        kind: &'static str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        value: impl Display,
+    ) {
+        let idx = match self.families.iter().position(|x| x.name == name) {
+            Some(idx) => idx,
+            None => {
+                self.families.push(MetricFamily {
+                    name,
+                    kind,
+                    help,
+                    samples: String::new(),
+                });
+                self.families.len() - 1
+            }
+        };
+        let samples = &mut self.families[idx].samples;
+
+        let labels: Vec<_> = labels
+            .iter()
+            .map(|(key, value)| format!("{key}=\"{}\"", escape_label(value)))
+            .collect();
+
+        let _ = writeln!(samples, "{name}{{{}}} {value}", labels.join(","));
+    }
+
+    fn render(self) -> String {
+        let mut out = String::new();
+
+        for family in self.families {
+            let _ = writeln!(out, "# HELP {} {}", family.name, family.help);
+            let _ = writeln!(out, "# TYPE {} {}", family.name, family.kind);
+            out.push_str(&family.samples);
+        }
+
+        out
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+macro_rules! backend_req {
+    ($handle:expr, $variant:ident) => {
+        match $handle.send_req(RequestFrontendMessage::$variant).await {
+            Ok(ResponseBackendMessage::$variant(resp)) => Some(resp),
+            _ => None,
+        }
+    };
+}
+
+async fn collect(metrics: &mut Metrics, node: &str, handle: &BackendHandle) {
+    let node_label = [("node", node)];
+
+    // Responses come from the backend cache, so scraping doesn't cause any extra refreshes
+    if let Some(cpu) = backend_req!(handle, Cpu) {
+        metrics.add(
+            "dietpi_cpu_percent",
+            "gauge",
+            "Global CPU usage in percent",
+            &node_label,
+            cpu.global_cpu,
+        );
+        for (i, usage) in cpu.cpus.iter().enumerate() {
+            metrics.add(
+                "dietpi_cpu_core_percent",
+                "gauge",
+                "Per-core CPU usage in percent",
+                &[("node", node), ("core", &i.to_string())],
+                usage,
+            );
+        }
+    }
+
+    if let Some(temp) = backend_req!(handle, Temp)
+        && let Some(temp) = temp.temp
+    {
+        metrics.add(
+            "dietpi_temperature_celsius",
+            "gauge",
+            "CPU temperature in degrees Celsius",
+            &node_label,
+            temp,
+        );
+    }
+
+    if let Some(mem) = backend_req!(handle, Mem) {
+        metrics.add(
+            "dietpi_memory_used_bytes",
+            "gauge",
+            "Used RAM in bytes",
+            &node_label,
+            mem.ram.used,
+        );
+        metrics.add(
+            "dietpi_memory_total_bytes",
+            "gauge",
+            "Total RAM in bytes",
+            &node_label,
+            mem.ram.total,
+        );
+        metrics.add(
+            "dietpi_memory_available_bytes",
+            "gauge",
+            "Available RAM in bytes, including reclaimable cache",
+            &node_label,
+            mem.ram_details.available,
+        );
+        metrics.add(
+            "dietpi_memory_cached_bytes",
+            "gauge",
+            "RAM used for buffers and cache in bytes",
+            &node_label,
+            mem.ram_details.buffers + mem.ram_details.cached,
+        );
+        metrics.add(
+            "dietpi_swap_used_bytes",
+            "gauge",
+            "Used swap in bytes",
+            &node_label,
+            mem.swap.used,
+        );
+        metrics.add(
+            "dietpi_swap_total_bytes",
+            "gauge",
+            "Total swap in bytes",
+            &node_label,
+            mem.swap.total,
+        );
+    }
+
+    if let Some(disk) = backend_req!(handle, Disk) {
+        for disk in &disk.disks {
+            let labels = [("node", node), ("mountpoint", disk.mnt_point.as_str())];
+            metrics.add(
+                "dietpi_disk_used_bytes",
+                "gauge",
+                "Used disk space in bytes",
+                &labels,
+                disk.usage.used,
+            );
+            metrics.add(
+                "dietpi_disk_total_bytes",
+                "gauge",
+                "Total disk space in bytes",
+                &labels,
+                disk.usage.total,
+            );
+        }
+    }
+
+    if let Some(net) = backend_req!(handle, NetIO) {
+        metrics.add(
+            "dietpi_net_sent_bytes_total",
+            "counter",
+            "Total bytes sent over all network interfaces",
+            &node_label,
+            net.total_sent,
+        );
+        metrics.add(
+            "dietpi_net_recv_bytes_total",
+            "counter",
+            "Total bytes received over all network interfaces",
+            &node_label,
+            net.total_recv,
+        );
+    }
+}
+
+pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    if !req.config().enable_metrics {
+        return Err(ServerResponse::new()
+            .status(StatusCode::NOT_FOUND)
+            .body("page not found"));
+    }
+
+    let mut metrics = Metrics::default();
+
+    for (node, handle) in req.all_backends() {
+        collect(&mut metrics, &node, &handle).await;
+    }
+
+    Ok(ServerResponse::new()
+        .header(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )
+        .body(metrics.render()))
+}
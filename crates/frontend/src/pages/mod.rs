@@ -1,9 +1,17 @@
+pub mod api;
 pub mod browser;
 pub mod config;
+pub mod dietpi;
+pub mod docker;
+pub mod health;
 pub mod login;
+pub mod logs;
+pub mod maintenance;
 pub mod management;
+pub mod metrics;
 pub mod process;
 pub mod service;
+pub mod setup;
 pub mod software;
 pub mod system;
 mod template;
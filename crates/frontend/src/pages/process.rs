@@ -1,41 +1,45 @@
+use std::collections::{HashMap, HashSet};
+
+use hyper::StatusCode;
 use maud::{Markup, html};
 use pretty_bytes_typed::pretty_bytes_binary;
-use proto::{backend::ProcessStatus, frontend::SignalAction};
+use proto::{
+    backend::{ProcessInfo, ProcessStatus},
+    frontend::{ProcessAction, ProcessSort, SignalAction},
+};
 use serde::{Deserialize, Serialize};
 
 use crate::http::{request::ServerRequest, response::ServerResponse};
 
-use super::template::{Icon, send_act, send_req, template};
+use super::template::{Icon, send_req, template};
+
+// Choices for how many processes to show, where 0 shows all of them
+const LIMITS: &[u32] = &[0, 10, 20, 50, 100];
 
-#[derive(Default, Deserialize, Serialize)]
+#[derive(Default, Deserialize, Serialize, Clone, Copy)]
 #[serde(default)]
 pub struct ProcessQuery {
-    sort: ColumnSort,
+    sort: ProcessSort,
     reverse: bool,
+    tree: bool,
+    limit: u32,
 }
 
-#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Default)]
-#[serde(rename_all = "lowercase")]
-pub enum ColumnSort {
-    #[default]
-    Pid,
-    Name,
-    Status,
-    Cpu,
-    Ram,
-}
-
-fn table_header(name: &str, sort: ColumnSort, query: &ProcessQuery) -> Markup {
+fn table_header(name: &str, sort: ProcessSort, query: &ProcessQuery) -> Markup {
     let reverse = if query.sort == sort {
         !query.reverse
     } else {
         false
     };
 
-    let new_query = ProcessQuery { sort, reverse };
-    let new_query = serde_urlencoded::to_string(&new_query).unwrap();
+    let new_query = ProcessQuery {
+        sort,
+        reverse,
+        ..*query
+    };
+    let new_query = serde_urlencoded::to_string(new_query).unwrap();
 
-    let url = format!("'/process?{new_query}'");
+    let url = format!("'process?{new_query}'");
 
     html! {
         th {
@@ -53,62 +57,134 @@ fn table_header(name: &str, sort: ColumnSort, query: &ProcessQuery) -> Markup {
     }
 }
 
+// Orders processes so that children come right after their parent, keeping the existing order
+// between siblings, and pairs each one with how deeply it's nested
+fn tree_order(processes: Vec<ProcessInfo>) -> Vec<(usize, ProcessInfo)> {
+    let pids: HashSet<u32> = processes.iter().map(|proc| proc.pid).collect();
+
+    let mut roots = Vec::new();
+    let mut children: HashMap<u32, Vec<usize>> = HashMap::new();
+
+    for (idx, proc) in processes.iter().enumerate() {
+        // Parents may have been filtered out or exited while listing, so show those as roots
+        match proc
+            .ppid
+            .filter(|ppid| pids.contains(ppid) && *ppid != proc.pid)
+        {
+            Some(ppid) => children.entry(ppid).or_default().push(idx),
+            None => roots.push(idx),
+        }
+    }
+
+    let mut slots: Vec<Option<ProcessInfo>> = processes.into_iter().map(Some).collect();
+    let mut ordered = Vec::with_capacity(slots.len());
+
+    let mut stack: Vec<(usize, usize)> = roots.into_iter().rev().map(|idx| (idx, 0)).collect();
+    while let Some((idx, depth)) = stack.pop() {
+        let Some(proc) = slots[idx].take() else {
+            continue;
+        };
+
+        if let Some(kids) = children.get(&proc.pid) {
+            stack.extend(kids.iter().rev().map(|&kid| (kid, depth + 1)));
+        }
+
+        ordered.push((depth, proc));
+    }
+
+    // Anything in a parent loop can't be reached from a root, but should still be shown
+    ordered.extend(slots.into_iter().flatten().map(|proc| (0, proc)));
+
+    ordered
+}
+
 pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
     req.check_login()?;
 
     let query: ProcessQuery = req.extract_query()?;
 
-    let mut processes = send_req!(req, Processes)?.processes;
-    match query.sort {
-        ColumnSort::Pid => processes.sort_by_key(|a| a.pid),
-        ColumnSort::Name => processes.sort_by(|a, b| a.name.cmp(&b.name)),
-        ColumnSort::Status => processes.sort_by_key(|a| a.status),
-        ColumnSort::Cpu => processes.sort_by(|a, b| a.cpu.total_cmp(&b.cpu)),
-        ColumnSort::Ram => processes.sort_by_key(|a| a.mem),
-    }
-    if query.reverse {
-        processes.reverse();
-    }
+    let action = ProcessAction {
+        sort: query.sort,
+        reverse: query.reverse,
+        limit: query.limit,
+    };
+    let processes = send_req!(req, Processes(action))?.processes;
 
-    let query_str = serde_urlencoded::to_string(&query).unwrap();
-    let url = format!("'/process?{query_str}'",);
+    let processes = if query.tree {
+        tree_order(processes)
+    } else {
+        processes.into_iter().map(|proc| (0, proc)).collect()
+    };
+
+    let toggled_query = ProcessQuery {
+        tree: !query.tree,
+        ..query
+    };
+    let toggled_query = serde_urlencoded::to_string(toggled_query).unwrap();
+
+    let query_str = serde_urlencoded::to_string(query).unwrap();
+    let url = format!("'process?{query_str}'",);
 
     let content = html! {
-        section #process-swap nm-bind={ "_: () => debounce(() => get("(url)"), 2000)" } {
+        section #process-swap nm-bind={ "_: () => poll("(url)", 2000)" } {
             h2 { "Processes" }
 
+            .process-controls {
+                button nm-bind={ "onclick: () => get('process?"(toggled_query)"')" } {
+                    @if query.tree { "Show as List" } @else { "Show as Tree" }
+                }
+                label {
+                    "Show "
+                    select nm-bind="onchange: () => get('process?' + this.value)" {
+                        @for &limit in LIMITS {
+                            @let limit_query = ProcessQuery { limit, ..query };
+
+                            option value=(serde_urlencoded::to_string(limit_query).unwrap()) selected[limit == query.limit] {
+                                @if limit == 0 { "all" } @else { "top " (limit) }
+                            }
+                        }
+                    }
+                }
+            }
+            br;
+            br;
+
             table .process-table {
                 tr {
-                    (table_header("PID", ColumnSort::Pid, &query))
-                    (table_header("Name", ColumnSort::Name, &query))
-                    (table_header("Status", ColumnSort::Status, &query))
-                    (table_header("CPU Usage", ColumnSort::Cpu, &query))
-                    (table_header("RAM Usage", ColumnSort::Ram, &query))
+                    (table_header("PID", ProcessSort::Pid, &query))
+                    (table_header("Name", ProcessSort::Name, &query))
+                    (table_header("Status", ProcessSort::Status, &query))
+                    (table_header("CPU Usage", ProcessSort::Cpu, &query))
+                    (table_header("RAM Usage", ProcessSort::Ram, &query))
                     th { "Actions" }
                 }
-                @for proc in processes {
+                @for (depth, proc) in processes {
                     @let pretty_mem = pretty_bytes_binary(proc.mem, Some(0));
 
                     tr {
                         td { (proc.pid) }
-                        td { (proc.name) }
+                        @if query.tree {
+                            td .tree-name style={ "--depth: " (depth) } { (proc.name) }
+                        } @else {
+                            td { (proc.name) }
+                        }
                         td { (format!("{:?}", proc.status)) }
                         td { (proc.cpu) "%" }
                         td { (pretty_mem) }
                         td {
                             .actions-cell {
-                                button nm-bind={ "onclick: () => post('/process/signal?signal=kill&pid="(proc.pid)"')" } {
+                                button nm-bind={ "onclick: () => post('process/signal?signal=kill&pid="(proc.pid)"')" } {
                                     (Icon::new("fa6-solid-skull"))
                                 }
-                                button nm-bind={ "onclick: () => post('/process/signal?signal=term&pid="(proc.pid)"')" } {
+                                button nm-bind={ "onclick: () => post('process/signal?signal=term&pid="(proc.pid)"')" } {
                                     (Icon::new("fa6-solid-ban"))
                                 }
                                 @if proc.status == ProcessStatus::Paused {
-                                    button nm-bind={ "onclick: () => post('/process/signal?signal=resume&pid="(proc.pid)"')" } {
+                                    button nm-bind={ "onclick: () => post('process/signal?signal=resume&pid="(proc.pid)"')" } {
                                         (Icon::new("fa6-solid-play"))
                                     }
                                 } @else {
-                                    button nm-bind={ "onclick: () => post('/process/signal?signal=pause&pid="(proc.pid)"')" } {
+                                    button nm-bind={ "onclick: () => post('process/signal?signal=pause&pid="(proc.pid)"')" } {
                                         (Icon::new("fa6-solid-pause"))
                                     }
                                 }
@@ -128,7 +204,22 @@ pub async fn signal(req: ServerRequest) -> Result<ServerResponse, ServerResponse
 
     let signal: SignalAction = req.extract_query()?;
 
-    send_act!(req, Signal(signal))?;
+    if signal.pid == 1 {
+        return Err(ServerResponse::new()
+            .status(StatusCode::BAD_REQUEST)
+            .body("refusing to send signal to init process"));
+    }
+
+    req.audit(&format!(
+        "sent {:?} to process {}",
+        signal.signal, signal.pid
+    ));
+
+    send_req!(req, Signal(signal))?.map_err(|err| {
+        ServerResponse::new()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(format!("couldn't send signal: {err}"))
+    })?;
 
     Ok(ServerResponse::new())
 }
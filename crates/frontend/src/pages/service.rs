@@ -1,24 +1,52 @@
+use futures_util::{SinkExt, StreamExt};
+use hyper::StatusCode;
 use maud::html;
-use proto::backend::ServiceStatus;
+use proto::{
+    backend::ServiceStatus,
+    frontend::{JournalAction, ServiceAction},
+};
+use serde::{Deserialize, Serialize};
+use tokio_tungstenite::tungstenite::Message;
 
-use crate::http::{request::ServerRequest, response::ServerResponse};
+use crate::http::{
+    compression::MessageCompressor,
+    heartbeat::Heartbeat,
+    request::ServerRequest,
+    response::{RedirectType, ServerResponse},
+};
 
 use super::template::{send_req, template};
 
+fn default_lines() -> u32 {
+    100
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct JournalQuery {
+    unit: String,
+    #[serde(default = "default_lines")]
+    lines: u32,
+    #[serde(default)]
+    since: String,
+}
+
 pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
     req.check_login()?;
 
     let data = send_req!(req, Services)?;
 
     let content = html! {
-        section {
+        section #service-swap {
             h2 { "Services" }
+            p nm-bind="textContent: () => nmError" {}
             table {
                 tr {
                     th { "Name" }
                     th { "Status" }
                     th { "Error Log" }
                     th { "Start Time" }
+                    th { "Journal" }
+                    th { "Actions" }
                 }
                 @for service in data.services {
                     tr {
@@ -42,6 +70,18 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
                             }
                         }
                         td { (service.start) }
+                        td {
+                            a href={ "service/journal?unit=" (service.name) } { "View journal" }
+                        }
+                        td {
+                            .service-actions {
+                                @for action in ["start", "stop", "restart", "enable", "disable"] {
+                                    button nm-bind={ "onclick: () => post('service/control', {unit: '"(service.name)"', action: '"(action)"'})" } {
+                                        (action)
+                                    }
+                                }
+                            }
+                        }
                     }
                 }
             }
@@ -50,3 +90,111 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
 
     template(&req, content)
 }
+
+pub async fn control(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let action: ServiceAction = req.extract_form().await?;
+
+    req.audit(&format!(
+        "ran {:?} on service {}",
+        action.action, action.unit
+    ));
+
+    send_req!(req, ServiceControl(action))?.map_err(|err| {
+        ServerResponse::new()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(format!("service action failed: {err}"))
+    })?;
+
+    Ok(ServerResponse::new().redirect(RedirectType::SeeOther, "/service"))
+}
+
+pub async fn journal(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    req.check_login()?;
+
+    let query: JournalQuery = req.extract_query()?;
+
+    let content = html! {
+        section {
+            h2 { "Journal for " (query.unit) }
+            form .log-form method="GET" action="service/journal" {
+                input type="hidden" name="unit" value=(query.unit);
+                label {
+                    "Lines "
+                    input type="number" name="lines" min="0" value=(query.lines);
+                }
+                label {
+                    "Since "
+                    input type="text" name="since" placeholder="e.g. 1 hour ago" value=(query.since);
+                }
+                input type="submit" value="Apply";
+            }
+            log-viewer src={ "service/journal/ws?" (serde_urlencoded::to_string(&query).unwrap()) } {}
+        }
+    };
+
+    template(&req, content)
+}
+
+pub async fn journal_socket(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    let req = req.check_socket_login()?;
+
+    let query: JournalQuery = req.extract_query()?;
+
+    let backend = req.extract_backends()?.current_backend.handle;
+
+    let action = JournalAction {
+        unit: query.unit,
+        lines: query.lines,
+        since: Some(query.since).filter(|since| !since.is_empty()),
+    };
+
+    let id = send_req!(req, Journal(action))?.ok_or_else(|| {
+        ServerResponse::new()
+            .status(StatusCode::NOT_FOUND)
+            .body("unknown service")
+    })?;
+
+    // Created before upgrading, so that the backend stops following if the upgrade fails
+    let mut stream = Box::pin(backend.read_stream(id));
+    let mut shutdown = req.shutdown();
+    let mut compressor = MessageCompressor::new(req.websocket_compression());
+
+    let ws_idle_timeout = req.websocket_idle_timeout();
+
+    req.extract_websocket(async move |mut ws| {
+        let mut heartbeat = Heartbeat::new(ws_idle_timeout);
+
+        loop {
+            tokio::select! {
+                chunk = stream.next() => {
+                    let Some(Ok(chunk)) = chunk else {
+                        break;
+                    };
+
+                    if ws.send(Message::binary(compressor.compress(chunk))).await.is_err() {
+                        break;
+                    }
+                }
+                msg = ws.next() => {
+                    // Nothing is expected from the client, other than it disconnecting
+                    if !matches!(msg, Some(Ok(_))) {
+                        break;
+                    }
+
+                    heartbeat.seen();
+                }
+                alive = heartbeat.tick() => {
+                    if !alive || ws.send(Message::Ping(Default::default())).await.is_err() {
+                        break;
+                    }
+                }
+                _ = shutdown.wait() => {
+                    let _ = ws.close(None).await;
+                    break;
+                }
+            }
+        }
+    })
+}
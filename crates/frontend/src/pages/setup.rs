@@ -0,0 +1,91 @@
+use hyper::StatusCode;
+use maud::html;
+use serde::Deserialize;
+
+use crate::http::{
+    request::ServerRequest,
+    response::{RedirectType, ServerResponse},
+};
+
+use super::{login::hash_password, template::template};
+
+const MIN_PASSWORD_LEN: usize = 8;
+
+// Setup is only needed, and only allowed, while login is enabled without a password. Anyone who can
+// reach the dashboard before then could set one, which allowed_ips can be used to guard against.
+fn check_needs_setup(req: &ServerRequest) -> Result<(), ServerResponse> {
+    if !req.config().enable_login || !req.config().hash.is_empty() {
+        return Err(ServerResponse::new().redirect(RedirectType::SeeOther, "/"));
+    }
+
+    Ok(())
+}
+
+pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    check_needs_setup(&req)?;
+
+    let content = html! {
+        section {
+            h2 { "Set Password" }
+
+            p { "Login is enabled, but no password has been set yet. Choose one to finish setting up the dashboard." }
+            form method="POST" {
+                input name="pass" type="password" placeholder="Password" minlength=(MIN_PASSWORD_LEN) required {}
+                br; br;
+                input name="confirm" type="password" placeholder="Confirm Password" minlength=(MIN_PASSWORD_LEN) required {}
+                br; br;
+                input type="submit" {}
+            }
+        }
+    };
+
+    template(&req, content)
+}
+
+#[derive(Deserialize)]
+pub struct SetupForm {
+    pass: String,
+    confirm: String,
+}
+
+pub async fn form(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    check_needs_setup(&req)?;
+
+    let form: SetupForm = req.extract_form().await?;
+
+    if form.pass.len() < MIN_PASSWORD_LEN {
+        return Err(ServerResponse::new()
+            .status(StatusCode::BAD_REQUEST)
+            .body(format!(
+                "password must be at least {MIN_PASSWORD_LEN} characters"
+            )));
+    }
+
+    if form.pass != form.confirm {
+        return Err(ServerResponse::new()
+            .status(StatusCode::BAD_REQUEST)
+            .body("passwords don't match"));
+    }
+
+    req.audit("set the initial password");
+
+    config::frontend::set_initial_hash(&hash_password(&form.pass)).map_err(|err| {
+        ServerResponse::new()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(format!("couldn't set password: {err:#}"))
+    })?;
+
+    // The password is only picked up on startup
+    req.shutdown().restart();
+
+    let content = html! {
+        section {
+            h2 { "Set Password" }
+
+            p { "The password has been set, and the dashboard is restarting. You can log in once it's back." }
+            meta http-equiv="refresh" content="5; url=login";
+        }
+    };
+
+    template(&req, content)
+}
@@ -1,12 +1,18 @@
+use futures_util::{SinkExt, StreamExt};
+use hyper::StatusCode;
 use maud::{Markup, html};
 use proto::{
     backend::{SoftwareInfo, SoftwareResponse},
-    frontend::CommandAction,
+    frontend::{SoftwareAction, SoftwareCommand},
 };
 use serde::Deserialize;
+use tokio_tungstenite::tungstenite::Message;
 
 use crate::{
-    http::{query_array::QueryArray, request::ServerRequest, response::ServerResponse},
+    http::{
+        compression::MessageCompressor, heartbeat::Heartbeat, query_array::QueryArray,
+        request::ServerRequest, response::ServerResponse,
+    },
     pages::template::Icon,
 };
 
@@ -51,7 +57,7 @@ fn software_table(list: &[SoftwareInfo], idx: u8, pretty_action: &str, action: &
                 button .software-input
                     value=(action)
                     nm-bind="
-                        onclick: () => post('/software', { software: [...software.keys()].join(','), action: this.value }),
+                        onclick: () => post('software', { software: [...software.keys()].join(','), action: this.value }),
                         disabled: () => nmFetching
                     "
                 {
@@ -100,17 +106,10 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
     template(&req, content)
 }
 
-#[derive(Deserialize)]
-#[serde(rename_all = "lowercase")]
-enum SoftwareAction {
-    Install,
-    Uninstall,
-}
-
 #[derive(Deserialize)]
 struct SoftwareForm {
     software: QueryArray,
-    action: SoftwareAction,
+    action: SoftwareCommand,
 }
 
 pub async fn form(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
@@ -118,24 +117,23 @@ pub async fn form(mut req: ServerRequest) -> Result<ServerResponse, ServerRespon
 
     let form: SoftwareForm = req.extract_form().await?;
 
-    let action = match form.action {
-        SoftwareAction::Install => "install",
-        SoftwareAction::Uninstall => "uninstall",
-    }
-    .into();
-
-    let mut args = vec![action];
-    for id in form.software.iter::<u16>() {
-        args.push(id.to_string());
-    }
+    let action = SoftwareAction {
+        action: form.action,
+        software: form.software.iter::<u16>().collect(),
+    };
 
-    let msg = CommandAction {
-        cmd: "/boot/dietpi/dietpi-software".into(),
-        args,
+    let verb = match action.action {
+        SoftwareCommand::Install => "install",
+        SoftwareCommand::Uninstall => "uninstall",
     };
+    let ids: Vec<_> = action.software.iter().map(u16::to_string).collect();
+    req.audit(&format!("ran dietpi-software {verb} {}", ids.join(" ")));
 
-    let resp = send_req!(req, Command(msg))?;
-    let output = String::from_utf8_lossy(&resp.output);
+    let id = send_req!(req, SoftwareAction(action))?.ok_or_else(|| {
+        ServerResponse::new()
+            .status(StatusCode::BAD_REQUEST)
+            .body("invalid software selection")
+    })?;
 
     let data = send_req!(req, Software)?;
 
@@ -143,12 +141,66 @@ pub async fn form(mut req: ServerRequest) -> Result<ServerResponse, ServerRespon
         (card(&data))
         br;
         section #output nm-bind="_: () => this.scrollIntoView()" {
-            h2 { "Install Summary" }
-            pre {
-                (output)
-            }
+            h2 { "Progress" }
+            software-output stream=(id) {}
         }
     };
 
     template(&req, content)
 }
+
+#[derive(Deserialize)]
+pub struct SoftwareStreamQuery {
+    stream: u32,
+}
+
+pub async fn socket(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    let req = req.check_socket_login()?;
+
+    let query: SoftwareStreamQuery = req.extract_query()?;
+
+    let backend = req.extract_backends()?.current_backend.handle;
+
+    let mut stream = Box::pin(backend.read_stream(query.stream));
+    let mut shutdown = req.shutdown();
+    let mut compressor = MessageCompressor::new(req.websocket_compression());
+
+    let ws_idle_timeout = req.websocket_idle_timeout();
+
+    req.extract_websocket(async move |mut ws| {
+        let mut heartbeat = Heartbeat::new(ws_idle_timeout);
+
+        loop {
+            tokio::select! {
+                chunk = stream.next() => {
+                    // The stream ends once dietpi-software has finished
+                    let Some(Ok(chunk)) = chunk else {
+                        let _ = ws.close(None).await;
+                        break;
+                    };
+
+                    if ws.send(Message::binary(compressor.compress(chunk))).await.is_err() {
+                        break;
+                    }
+                }
+                msg = ws.next() => {
+                    // Nothing is expected from the client, other than it disconnecting
+                    if !matches!(msg, Some(Ok(_))) {
+                        break;
+                    }
+
+                    heartbeat.seen();
+                }
+                alive = heartbeat.tick() => {
+                    if !alive || ws.send(Message::Ping(Default::default())).await.is_err() {
+                        break;
+                    }
+                }
+                _ = shutdown.wait() => {
+                    let _ = ws.close(None).await;
+                    break;
+                }
+            }
+        }
+    })
+}
@@ -1,6 +1,8 @@
 use maud::{Markup, html};
 use pretty_bytes_typed::{pretty_bytes, pretty_bytes_binary};
-use proto::backend::{CpuResponse, DiskResponse, MemResponse, NetworkResponse, TempResponse};
+use proto::backend::{
+    CpuResponse, DiskIoResponse, DiskResponse, MemResponse, NetworkResponse, TempResponse,
+};
 
 use crate::{http::query_array::QueryArray, pages::template::Icon};
 
@@ -47,6 +49,24 @@ pub fn cpu_meters(cpu_data: &CpuResponse, temp_data: &TempResponse) -> Markup {
     }
 }
 
+pub fn temp_sensors(data: &TempResponse) -> Option<Markup> {
+    // The main temperature is already shown with the CPU meters, so only list sensors if there's more than one
+    if data.sensors.len() < 2 {
+        return None;
+    }
+
+    let span = calc_grid_span(data.sensors.len() / 2);
+
+    Some(html! {
+        section .{"span-" (span)} {
+            h2 { "Temperature Sensors" }
+            @for sensor in &data.sensors {
+                p { (sensor.label) ": " (sensor.temp) "ºC" }
+            }
+        }
+    })
+}
+
 pub fn cpu_graph(data: &CpuResponse, points: &mut QueryArray) -> Markup {
     let mut graph = SvgGraph::new(Axis::Percent);
 
@@ -92,6 +112,11 @@ pub fn mem_meters(data: &MemResponse) -> Markup {
     let pretty_ram_total = pretty_bytes_binary(data.ram.total, Some(2));
     let ram_percent = calc_percent(data.ram.used, data.ram.total);
 
+    let details = &data.ram_details;
+    let pretty_available = pretty_bytes_binary(details.available, Some(2));
+    let pretty_cache = pretty_bytes_binary(details.buffers + details.cached, Some(2));
+    let pretty_free = pretty_bytes_binary(details.free, Some(2));
+
     let pretty_swap_used = pretty_bytes_binary(data.swap.used, Some(2));
     let pretty_swap_total = pretty_bytes_binary(data.swap.total, Some(2));
     let swap_percent = calc_percent(data.swap.used, data.swap.total);
@@ -104,6 +129,10 @@ pub fn mem_meters(data: &MemResponse) -> Markup {
             div .meter-container {
                 div .bar.ram style={"--scale:"(ram_percent / 100.)} {}
             }
+            p {
+                "Available: " (pretty_available)
+                " (Buffers/Cache: " (pretty_cache) ", Free: " (pretty_free) ")"
+            }
 
             p { "Swap Usage: " (pretty_swap_used) " / " (pretty_swap_total) }
             div .meter-container {
@@ -175,6 +204,74 @@ pub fn disk_meters(data: &DiskResponse) -> Markup {
     }
 }
 
+pub fn disk_io(data: &DiskIoResponse) -> Option<Markup> {
+    if data.devices.is_empty() {
+        return None;
+    }
+
+    let span = calc_grid_span(data.devices.len());
+
+    Some(html! {
+        section .{"span-" (span)} {
+            h2 { "Disk I/O" }
+            table {
+                tr {
+                    th { "Device" }
+                    th { "Read" }
+                    th { "Written" }
+                }
+                @for device in &data.devices {
+                    tr {
+                        td { (device.name) }
+                        td { (pretty_bytes(device.read, Some(2))) "/s" }
+                        td { (pretty_bytes(device.write, Some(2))) "/s" }
+                    }
+                }
+            }
+        }
+    })
+}
+
+pub fn net_interfaces(data: &NetworkResponse) -> Option<Markup> {
+    // Loopback and down interfaces don't carry any interesting traffic
+    let interfaces: Vec<_> = data
+        .interfaces
+        .iter()
+        .filter(|iface| iface.up && !iface.loopback)
+        .collect();
+
+    // The totals are already shown in the network graph, so only list interfaces if there's more than one
+    if interfaces.len() < 2 {
+        return None;
+    }
+
+    let span = calc_grid_span(interfaces.len());
+
+    Some(html! {
+        section .{"span-" (span)} {
+            h2 { "Network Interfaces" }
+            table {
+                tr {
+                    th { "Interface" }
+                    th { "Sent" }
+                    th { "Received" }
+                    th { "Total Sent" }
+                    th { "Total Received" }
+                }
+                @for iface in interfaces {
+                    tr {
+                        td { (iface.name) }
+                        td { (pretty_bytes(iface.sent, Some(2))) }
+                        td { (pretty_bytes(iface.recv, Some(2))) }
+                        td { (pretty_bytes(iface.total_sent, Some(2))) }
+                        td { (pretty_bytes(iface.total_recv, Some(2))) }
+                    }
+                }
+            }
+        }
+    })
+}
+
 pub fn net_graph(
     data: &NetworkResponse,
     sent_points: &mut QueryArray,
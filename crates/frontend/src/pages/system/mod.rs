@@ -1,3 +1,5 @@
+use std::net::IpAddr;
+
 use maud::html;
 use serde::{Deserialize, Serialize};
 
@@ -11,6 +13,8 @@ mod graph;
 #[derive(Default, Serialize, Deserialize)]
 #[serde(default)]
 pub struct SystemQuery {
+    // The node the history below was collected from
+    backend: Option<IpAddr>,
     cpu_points: QueryArray,
     temp_points: QueryArray,
     ram_points: QueryArray,
@@ -24,35 +28,62 @@ pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse>
 
     let mut query: SystemQuery = req.extract_query()?;
 
+    // Graphs would otherwise mix readings from different nodes after switching between them
+    let backend = req.extract_backends()?.current_backend.addr;
+    if query.backend != Some(backend) {
+        // Start off with what the node has recorded, so that a new tab doesn't begin with empty graphs
+        let history = send_req!(req, History)?;
+
+        query = SystemQuery {
+            backend: Some(backend),
+            cpu_points: history.cpu.iter().collect(),
+            temp_points: history.temp.iter().collect(),
+            ..Default::default()
+        };
+    }
+
     let cpu_data = send_req!(req, Cpu)?;
     let temp_data = send_req!(req, Temp)?;
     let mem_data = send_req!(req, Mem)?;
     let disk_data = send_req!(req, Disk)?;
+    let disk_io_data = send_req!(req, DiskIO)?;
     let net_data = send_req!(req, NetIO)?;
 
     let cpu_meters = fragments::cpu_meters(&cpu_data, &temp_data);
+    let temp_sensors = fragments::temp_sensors(&temp_data);
     let mem_meters = fragments::mem_meters(&mem_data);
     let disk_meters = fragments::disk_meters(&disk_data);
+    let disk_io = fragments::disk_io(&disk_io_data);
 
     let cpu_graph = fragments::cpu_graph(&cpu_data, &mut query.cpu_points);
     let temp_graph = fragments::temp_graph(&temp_data, &mut query.temp_points);
     let mem_graph = fragments::mem_graph(&mem_data, &mut query.ram_points, &mut query.swap_points);
+    let net_interfaces = fragments::net_interfaces(&net_data);
     let net_graph = fragments::net_graph(&net_data, &mut query.sent_points, &mut query.recv_points);
 
     let new_query = serde_urlencoded::to_string(&query).unwrap();
-    let url = format!("'/system?{new_query}'",);
+    let url = format!("'system?{new_query}'",);
 
     let content = html! {
-        div #system-swap .card-grid nm-bind={ "_: () => debounce(() => get("(url)"), 2000)" } {
+        div #system-swap .card-grid nm-bind={ "_: () => poll("(url)", 2000)" } {
             (cpu_meters)
             (cpu_graph)
             @if let Some(temp_graph) = temp_graph {
                 (temp_graph)
             }
+            @if let Some(temp_sensors) = temp_sensors {
+                (temp_sensors)
+            }
             (mem_meters)
             (mem_graph)
             (disk_meters)
+            @if let Some(disk_io) = disk_io {
+                (disk_io)
+            }
             (net_graph)
+            @if let Some(net_interfaces) = net_interfaces {
+                (net_interfaces)
+            }
         }
     };
 
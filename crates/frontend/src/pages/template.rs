@@ -22,10 +22,10 @@ macro_rules! send_req {
 pub(crate) use send_req;
 
 macro_rules! send_act {
-    ($req:expr, $variant:ident $(($data:expr))?) => {{
+    ($req:expr, $variant:ident $(($($data:expr),+))?) => {{
         use proto::frontend::ActionFrontendMessage;
 
-        $req.send_backend_action(ActionFrontendMessage::$variant $(($data))?).await
+        $req.send_backend_action(ActionFrontendMessage::$variant $(($($data),+))?).await
     }};
 }
 
@@ -34,9 +34,15 @@ pub(crate) use send_act;
 fn header(req: &ServerRequest) -> Result<Markup, ServerResponse> {
     let BackendData {
         backend_list,
+        offline_list,
         current_backend,
     } = req.extract_backends()?;
 
+    let backend_status = match current_backend.latency {
+        Some(latency) => format!("Online, {} ms round trip", latency.as_millis()),
+        None => "Not responding".to_string(),
+    };
+
     Ok(html! {
         header {
             button
@@ -57,6 +63,15 @@ fn header(req: &ServerRequest) -> Result<Markup, ServerResponse> {
                             (backend.1) " (" (backend.0) ")"
                         }
                     }
+                    @for backend in offline_list {
+                        option value=(backend.0) disabled {
+                            (backend.1) " (" (backend.0) ") - offline since "
+                            (humantime::format_rfc3339_seconds(backend.2))
+                        }
+                    }
+                }
+                span .backend-status .offline[current_backend.latency.is_none()] title=(backend_status) {
+                    (Icon::new("fa6-solid-circle").size(12))
                 }
             }
 
@@ -96,7 +111,12 @@ fn header(req: &ServerRequest) -> Result<Markup, ServerResponse> {
                     return msg;
                 }"} {}
                 @if let Some(update) = current_backend.update {
-                    li nm-bind="_: () => newMsg = true" { "DietPi Update Available: " (update) }
+                    li nm-bind="_: () => newMsg = true" {
+                        "DietPi Update Available: " (update.current_version) " → " (update.new_version) " "
+                        a href="https://github.com/MichaIng/DietPi/blob/master/CHANGELOG.md" target="_blank" {
+                            "(changelog)"
+                        }
+                    }
                 }
             }
         }
@@ -106,35 +126,47 @@ fn header(req: &ServerRequest) -> Result<Markup, ServerResponse> {
 fn nav() -> Markup {
     html! {
         nav #nav {
-            a href="/system" {
+            a href="system" {
                 (Icon::new("fa6-solid-gauge"))
                 "System"
             }
-            a href="/process" {
+            a href="process" {
                 (Icon::new("fa6-solid-microchip"))
                 "Processes"
             }
-            a href="/software" {
+            a href="software" {
                 (Icon::new("fa6-solid-database"))
                 "Software"
             }
-            a href="/service" {
+            a href="service" {
                 (Icon::new("fa6-solid-list"))
                 "Services"
             }
-            a href="/management" {
+            a href="logs" {
+                (Icon::new("fa6-solid-file-lines"))
+                "Logs"
+            }
+            a href="docker" {
+                (Icon::new("fa6-solid-cube"))
+                "Docker"
+            }
+            a href="management" {
                 (Icon::new("fa6-solid-user"))
                 "Management"
             }
-            a href="/terminal" {
+            a href="terminal" {
                 (Icon::new("fa6-solid-terminal"))
                 "Terminal"
             }
-            a href="/browser" {
+            a href="browser" {
                 (Icon::new("fa6-solid-folder"))
                 "File Browser"
             }
-            a href="/config" {
+            a href="dietpi" {
+                (Icon::new("fa6-solid-sliders"))
+                "DietPi Settings"
+            }
+            a href="config" {
                 (Icon::new("fa6-solid-gear"))
                 "Config"
             }
@@ -142,10 +174,21 @@ fn nav() -> Markup {
     }
 }
 
-fn footer() -> Markup {
+fn footer(req: &ServerRequest) -> Markup {
+    // Mismatched versions can cause strange bugs, so make them easy to spot
+    let backend_version = req
+        .extract_backends()
+        .ok()
+        .map(|backends| backends.current_backend.app_version)
+        .filter(|version| version != config::APP_VERSION);
+
     html! {
         footer {
-            "DietPi Dashboard v" (config::APP_VERSION) " by ravenclaw900"
+            "DietPi Dashboard v" (config::APP_VERSION)
+            @if let Some(version) = backend_version {
+                " (backend v" (version) ")"
+            }
+            " by ravenclaw900"
             a href="https://github.com/ravenclaw900/DietPi-Dashboard" target="_blank" {
                 (Icon::new("cib-github").size(32))
             }
@@ -166,8 +209,11 @@ pub fn template(req: &ServerRequest, content: Markup) -> Result<ServerResponse,
 
                     title { "DietPi Dashboard" }
 
-                    link rel="icon" href="/favicon.svg" type="image/svg+xml";
-                    link rel="stylesheet" href="/static/main.css";
+                    // All links are relative, so that the dashboard works behind a reverse proxy
+                    base href={ (req.base_path()) "/" };
+
+                    link rel="icon" href="favicon.svg" type="image/svg+xml";
+                    link rel="stylesheet" href="static/main.css";
                 }
                 body
                     nm-data="navOpen: true, msgsOpen: false, newMsg: false,"
@@ -185,9 +231,9 @@ pub fn template(req: &ServerRequest, content: Markup) -> Result<ServerResponse,
                         (content)
                     }
 
-                    (footer())
+                    (footer(req))
 
-                    script src="/static/main.js" {}
+                    script src="static/main.js" {}
                 }
             }
         }
@@ -218,7 +264,7 @@ impl Render for Icon {
     fn render(&self) -> Markup {
         html! {
             svg width=(self.size) height=(self.size) {
-                use href={"/static/icons.svg#" (self.name)} {}
+                use href={"static/icons.svg#" (self.name)} {}
             }
         }
     }
@@ -1,33 +1,147 @@
+use std::time::Duration;
+
 use maud::html;
 
 use futures_util::{SinkExt, StreamExt};
+use hyper::StatusCode;
 use proto::frontend::ActionFrontendMessage;
-use tokio_tungstenite::tungstenite::Message;
+use serde::Deserialize;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::{
+    Error as WsError, Message,
+    protocol::{CloseFrame, frame::coding::CloseCode},
+};
+
+use crate::http::{
+    heartbeat::Heartbeat,
+    request::{ServerRequest, UNAUTHORIZED_CLOSE_CODE},
+    response::{RedirectType, ServerResponse},
+};
+
+use super::template::{send_act, send_req, template};
+
+// Each session is a shell left running on the backend, so only so many can be opened
+const MAX_SESSIONS: u32 = 16;
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct TerminalQuery {
+    session: u32,
+}
 
-use crate::http::{request::ServerRequest, response::ServerResponse};
+impl TerminalQuery {
+    fn check_session(&self) -> Result<(), ServerResponse> {
+        if self.session >= MAX_SESSIONS {
+            return Err(ServerResponse::new()
+                .status(StatusCode::BAD_REQUEST)
+                .body(format!("only {MAX_SESSIONS} terminal sessions can be open")));
+        }
 
-use super::template::template;
+        Ok(())
+    }
+}
 
 pub async fn page(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
     req.check_login()?;
 
+    let query: TerminalQuery = req.extract_query()?;
+    query.check_session()?;
+
+    // The current session won't be listed if it's about to be started
+    let mut sessions = send_req!(req, Terminals)?;
+    if !sessions.contains(&query.session) {
+        sessions.push(query.session);
+        sessions.sort_unstable();
+    }
+
+    let new_session = sessions
+        .last()
+        .map_or(Some(0), |x| x.checked_add(1))
+        .filter(|&x| x < MAX_SESSIONS);
+
     let content = html! {
         section {
             h2 { "Terminal" }
-            web-terminal {}
+            .tab-container role="tablist" {
+                .tabs {
+                    @for id in sessions {
+                        button
+                            role="tab"
+                            aria-selected=(id == query.session)
+                            onclick={ "location.href = 'terminal?session=" (id) "'" }
+                        { "Session " (id + 1) }
+                    }
+                    @if let Some(new_session) = new_session {
+                        button onclick={ "location.href = 'terminal?session=" (new_session) "'" } {
+                            "New Session"
+                        }
+                    }
+                }
+                web-terminal session=(query.session) {}
+            }
+            br;
+            form method="POST" action="terminal/close" {
+                input type="hidden" name="session" value=(query.session);
+                input type="submit" value="Close Session";
+            }
         }
     };
 
     template(&req, content)
 }
 
-pub async fn socket(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+pub async fn close(mut req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
     req.check_login()?;
 
+    let form: TerminalQuery = req.extract_form().await?;
+
+    req.audit(&format!("closed terminal session {}", form.session));
+
+    send_act!(req, CloseTerminal(form.session))?;
+
+    Ok(ServerResponse::new().redirect(RedirectType::SeeOther, "/terminal"))
+}
+
+// Never finishes if there's no timeout
+async fn idle_expired(last_input: Instant, timeout: Option<Duration>) {
+    match timeout {
+        Some(timeout) => tokio::time::sleep_until(last_input + timeout).await,
+        None => std::future::pending().await,
+    }
+}
+
+pub async fn socket(req: ServerRequest) -> Result<ServerResponse, ServerResponse> {
+    let req = req.check_socket_login()?.check_terminal_unlocked()?;
+
+    let query: TerminalQuery = req.extract_query()?;
+    query.check_session()?;
+    let id = query.session;
+
     let backend = req.extract_backends()?.current_backend.handle;
+    let mut shutdown = req.shutdown();
+    let idle_timeout = Some(req.config().idle_timeout)
+        .filter(|&secs| secs != 0)
+        .map(Duration::from_secs);
+
+    // Locking goes by the login token, so there's nothing to lock without one
+    let logins = req.extract_logins();
+    let token = req.login_token().map(str::to_string);
+    let lock_timeout = Some(req.config().terminal_lock_timeout)
+        .filter(|&secs| secs != 0 && req.config().enable_login && token.is_some())
+        .map(Duration::from_secs);
+
+    req.audit(&format!("opened terminal session {id}"));
+
+    // Make sure the session is running, so that there's something to attach to
+    send_act!(req, Terminal(id, Vec::new()))?;
+
+    let ws_idle_timeout = req.websocket_idle_timeout();
 
     req.extract_websocket(async move |mut ws| {
-        let mut term_rx = backend.get_terminal_handle().await.unwrap();
+        let mut term_rx = backend.get_terminal_handle(id).await.unwrap();
+        let mut heartbeat = Heartbeat::new(ws_idle_timeout);
+        // Unlike the heartbeat, only input counts here, so that a tab left open still times out
+        let mut last_input = Instant::now();
 
         loop {
             tokio::select! {
@@ -41,17 +155,66 @@ pub async fn socket(req: ServerRequest) -> Result<ServerResponse, ServerResponse
                     }
                 }
                 data = ws.next() => {
-                    let Some(Ok(data)) = data else {
-                        break;
+                    let data = match data {
+                        Some(Ok(data)) => data,
+                        // The socket can't be read from after this, but the client should still
+                        // find out why its input stopped going through
+                        Some(Err(WsError::Capacity(_))) => {
+                            let frame = CloseFrame {
+                                code: CloseCode::Size,
+                                reason: "Input too large".into(),
+                            };
+                            let _ = ws.close(Some(frame)).await;
+                            break;
+                        }
+                        _ => break,
                     };
+
+                    heartbeat.seen();
+
+                    // Pings and pongs aren't terminal input
+                    if !data.is_binary() && !data.is_text() {
+                        continue;
+                    }
+                    last_input = Instant::now();
                     let data = data.into_data().to_vec();
 
-                    let msg = ActionFrontendMessage::Terminal(data);
+                    let msg = ActionFrontendMessage::Terminal(id, data);
 
                     if backend.send_action(msg).await.is_err() {
                         break;
                     }
                 }
+                alive = heartbeat.tick() => {
+                    if !alive || ws.send(Message::Ping(Default::default())).await.is_err() {
+                        break;
+                    }
+                }
+                _ = idle_expired(last_input, idle_timeout) => {
+                    let frame = CloseFrame {
+                        code: CloseCode::Policy,
+                        reason: "Closed after being idle".into(),
+                    };
+                    let _ = ws.close(Some(frame)).await;
+                    break;
+                }
+                _ = idle_expired(last_input, lock_timeout) => {
+                    if let Some(token) = &token {
+                        logins.get().lock_terminal(token);
+                    }
+
+                    // Closing only detaches from the session, so the shell keeps running
+                    let frame = CloseFrame {
+                        code: CloseCode::from(UNAUTHORIZED_CLOSE_CODE),
+                        reason: "Locked after being idle".into(),
+                    };
+                    let _ = ws.close(Some(frame)).await;
+                    break;
+                }
+                _ = shutdown.wait() => {
+                    let _ = ws.close(None).await;
+                    break;
+                }
             }
         }
     })
@@ -10,72 +10,213 @@ pub enum BackendMessage {
 #[derive(Debug, Clone, Encode, Decode)]
 pub enum ActionBackendMessage {
     Handshake(Handshake),
-    Terminal(Vec<u8>),
+    Terminal(u32, Vec<u8>),
 }
 
 #[derive(Debug, Clone, Encode, Decode)]
 pub enum ResponseBackendMessage {
     Cpu(CpuResponse),
     Temp(TempResponse),
+    History(HistoryResponse),
     Mem(MemResponse),
     Disk(DiskResponse),
+    Mounts(DiskResponse),
+    BlockDevices(Vec<BlockDevice>),
+    Mount(Result<(), String>),
+    Unmount(Result<(), String>),
     NetIO(NetworkResponse),
+    DiskIO(DiskIoResponse),
+    NetworkUsage(NetworkUsageResponse),
+    // The usage after resetting, which can fail to be saved
+    ResetNetworkUsage(Result<NetworkUsageResponse, String>),
     Processes(ProcessResponse),
+    Signal(Result<(), String>),
     Host(HostResponse),
     Software(SoftwareResponse),
-    Command(CommandResponse),
+    // `None` if any of the software can't be installed or uninstalled
+    SoftwareAction(Option<u32>),
+    // `None` if there's no command with that name
+    Command(Option<CommandResponse>),
     Services(ServiceResponse),
+    // `None` if the unit isn't a known service
+    Journal(Option<u32>),
+    // `None` if the file isn't in the configured log paths
+    TailLog(Option<u32>),
+    ServiceControl(Result<(), String>),
+    // Seconds until the action is carried out
+    Power(Result<u64, String>),
+    DropCaches(Result<DropCachesResponse, String>),
+    // `None` if Docker isn't installed or running
+    Docker(Option<DockerResponse>),
     Directory(DirectoryResponse),
-    Download(Vec<u8>),
+    Search(SearchResponse),
+    Stat(Option<DirectoryItemInfo>),
+    Checksum(Option<ChecksumResponse>),
+    Download(Option<u32>),
+    DownloadArchive(Option<u32>),
+    Preview(Result<PreviewResponse, String>),
+    // A JPEG
+    Thumbnail(Result<Vec<u8>, String>),
+    ReadStream(Option<Vec<u8>>),
+    ReadFile(Result<String, String>),
+    SaveFile(Result<(), String>),
+    UploadChunk(bool),
+    UploadStatus(Option<u64>),
+    FileAction(Result<(), String>),
     ReadConfig(String),
+    DietPiSettings(DietPiSettingsResponse),
+    SetDietPiSetting(Result<(), String>),
+    Terminals(Vec<u32>),
+    Ping(()),
 }
 
 #[derive(Debug, Clone, Encode, Decode)]
 pub struct Handshake {
     pub nickname: String,
-    pub update: Option<String>,
-    pub version: u32,
+    // `None` if there's no DietPi update available
+    pub update: Option<UpdateInfo>,
+    // Only informational, compatibility is decided by the protocol version exchanged beforehand
+    pub app_version: String,
 }
 
 #[derive(Debug, Clone, Encode, Decode)]
+pub struct UpdateInfo {
+    pub current_version: String,
+    pub new_version: String,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct CpuResponse {
     pub global_cpu: f32,
     pub cpus: Vec<f32>,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct HistoryResponse {
+    // Seconds between samples
+    pub interval: u32,
+    // Newest first
+    pub cpu: Vec<f32>,
+    // Empty if there's no temperature sensor
+    pub temp: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct TempResponse {
     pub temp: Option<f32>,
+    pub sensors: Vec<SensorInfo>,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct SensorInfo {
+    pub label: String,
+    pub temp: f32,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct MemResponse {
     pub ram: UsageData,
     pub swap: UsageData,
+    pub ram_details: RamDetails,
+}
+
+// Cache can be reclaimed when needed, so the available memory is the real headroom, not what's free
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct RamDetails {
+    pub available: u64,
+    pub free: u64,
+    pub buffers: u64,
+    pub cached: u64,
+}
+
+// Bytes sent and received over every interface but loopback, since the last reset
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct NetworkUsageResponse {
+    // Unix timestamp
+    pub since: u64,
+    pub sent: u64,
+    pub recv: u64,
 }
 
 #[derive(Debug, Clone, Encode, Decode)]
+pub struct DropCachesResponse {
+    pub before: RamDetails,
+    pub after: RamDetails,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct UsageData {
     pub used: u64,
     pub total: u64,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct DiskResponse {
     pub disks: Vec<DiskInfo>,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct DiskInfo {
     pub name: String,
     pub mnt_point: String,
+    pub fs_type: String,
     pub usage: UsageData,
 }
 
 #[derive(Debug, Clone, Encode, Decode)]
+pub struct BlockDevice {
+    pub name: String,
+    // Empty if the device doesn't report one
+    pub model: String,
+    // In bytes
+    pub size: u64,
+    pub removable: bool,
+    pub usb: bool,
+    // A filesystem on the whole device, without a partition table, is listed as its only partition
+    pub partitions: Vec<BlockPartition>,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct BlockPartition {
+    pub name: String,
+    pub size: u64,
+    // Empty if there's no filesystem, or it isn't known
+    pub fs_type: String,
+    // Empty if it isn't mounted
+    pub mnt_point: String,
+}
+
+// Rates are in bytes per second since the previous request
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct DiskIoResponse {
+    pub devices: Vec<DiskIoInfo>,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct DiskIoInfo {
+    pub name: String,
+    pub read: u64,
+    pub write: u64,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct NetworkResponse {
     pub sent: u64,
     pub recv: u64,
+    pub total_sent: u64,
+    pub total_recv: u64,
+    pub interfaces: Vec<InterfaceInfo>,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct InterfaceInfo {
+    pub name: String,
+    pub sent: u64,
+    pub recv: u64,
+    pub total_sent: u64,
+    pub total_recv: u64,
+    pub loopback: bool,
+    pub up: bool,
 }
 
 #[derive(Debug, Clone, Encode, Decode)]
@@ -86,6 +227,7 @@ pub struct ProcessResponse {
 #[derive(Debug, Clone, Encode, Decode)]
 pub struct ProcessInfo {
     pub pid: u32,
+    pub ppid: Option<u32>,
     pub name: String,
     pub cpu: f32,
     pub mem: u64,
@@ -100,16 +242,47 @@ pub enum ProcessStatus {
     Other,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct HostResponse {
     pub hostname: String,
     pub nic: String,
     pub arch: String,
     pub uptime: u64,
+    // Unix timestamps
+    pub boot_time: u64,
+    pub backend_start: u64,
+    pub load_avg: [f64; 3],
+    pub num_cpus: usize,
     pub kernel: String,
     pub os_version: String,
     pub dp_version: String,
     pub num_pkgs: usize,
+    pub interfaces: Vec<InterfaceAddresses>,
+    // `None` if not running on a Raspberry Pi
+    pub throttling: Option<ThrottleFlags>,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct ThrottleFlags {
+    pub under_voltage: bool,
+    pub freq_capped: bool,
+    pub throttled: bool,
+    pub soft_temp_limit: bool,
+    // Whether each of the above has happened at any point since boot
+    pub under_voltage_occurred: bool,
+    pub freq_capped_occurred: bool,
+    pub throttled_occurred: bool,
+    pub soft_temp_limit_occurred: bool,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
+pub struct InterfaceAddresses {
+    pub name: String,
+    pub mac: String,
+    // In CIDR notation, like "192.168.1.2/24"
+    pub addresses: Vec<String>,
+    pub up: bool,
+    pub mtu: u64,
 }
 
 #[derive(Debug, Clone, Encode, Decode)]
@@ -118,6 +291,30 @@ pub struct SoftwareResponse {
     pub uninstalled: Vec<SoftwareInfo>,
 }
 
+// Settings are `None` if they aren't in dietpi.txt, like on systems that aren't running DietPi
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct DietPiSettingsResponse {
+    // In MiB, where 0 means disabled and 1 means sized automatically
+    pub swap_size: Option<u32>,
+    // A path for a swap file, or "zram" to swap to compressed memory instead
+    pub swap_location: Option<String>,
+    pub swap_devices: Vec<SwapDevice>,
+    pub cpu_governor: Option<String>,
+    pub available_governors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct SwapDevice {
+    pub path: String,
+    // "file", "partition" or "zram"
+    pub kind: String,
+    // In bytes
+    pub size: u64,
+    pub used: u64,
+    // Higher priority devices are used first
+    pub priority: i32,
+}
+
 #[derive(Debug, Clone, Encode, Decode)]
 pub struct SoftwareInfo {
     pub id: u16,
@@ -127,17 +324,19 @@ pub struct SoftwareInfo {
     pub docs: String,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct CommandResponse {
-    pub output: Vec<u8>,
+    pub output: String,
+    // `None` if the command couldn't be started or was killed by a signal
+    pub exit_code: Option<i32>,
 }
 
-#[derive(Debug, Clone, Encode, Decode, Default)]
+#[derive(Debug, Clone, Encode, Decode, Default, Serialize)]
 pub struct ServiceResponse {
     pub services: Vec<ServiceInfo>,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode, Serialize)]
 pub struct ServiceInfo {
     pub name: String,
     pub status: ServiceStatus,
@@ -145,7 +344,26 @@ pub struct ServiceInfo {
     pub err_log: String,
 }
 
-#[derive(Debug, Clone, Copy, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct DockerResponse {
+    pub containers: Vec<ContainerInfo>,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct ContainerInfo {
+    pub name: String,
+    pub image: String,
+    // Machine-readable state, like "running" or "exited"
+    pub state: String,
+    // Human-readable status, like "Up 2 hours"
+    pub status: String,
+    // Only measured for running containers
+    pub cpu: f32,
+    pub mem: u64,
+}
+
+#[derive(Debug, Clone, Copy, Encode, Decode, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ServiceStatus {
     Active,
     Inactive,
@@ -156,6 +374,28 @@ pub enum ServiceStatus {
 #[derive(Debug, Clone, Encode, Decode, Default)]
 pub struct DirectoryResponse {
     pub dir_list: Vec<DirectoryItemInfo>,
+    // Number of items in the whole directory, not just the requested page
+    pub total: u32,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct PreviewResponse {
+    pub mime: String,
+    pub stream_id: u32,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct ChecksumResponse {
+    // Hex encoded SHA-256 digest
+    pub sha256: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Default)]
+pub struct SearchResponse {
+    pub results: Vec<DirectoryItemInfo>,
+    // Whether the search stopped early from hitting the result cap or timing out
+    pub truncated: bool,
 }
 
 #[derive(Debug, Clone, Encode, Decode)]
@@ -163,12 +403,21 @@ pub struct DirectoryItemInfo {
     pub path: String,
     pub kind: FileKind,
     pub size: Option<u64>,
+    // Seconds since the Unix epoch
+    pub modified: Option<u64>,
+    // Permission bits, without the file type
+    pub mode: u32,
+    // Names of the owning user and group, or their IDs if they don't have a name
+    pub owner: String,
+    pub group: String,
 }
 
 #[derive(Debug, Clone, Copy, Encode, Decode, Deserialize, Serialize, PartialEq, Eq)]
 pub enum FileKind {
     TextFile,
     BinaryFile,
+    // An image that can be previewed
+    Image,
     Directory,
     Special,
 }
@@ -1,5 +1,5 @@
 use bitcode::{Decode, Encode};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Encode, Decode)]
 pub enum FrontendMessage {
@@ -11,29 +11,58 @@ pub enum FrontendMessage {
 pub enum RequestFrontendMessage {
     Cpu,
     Temp,
+    History,
     Mem,
     Disk,
+    Mounts,
+    BlockDevices,
+    Mount(MountAction),
+    // The name of a mounted partition, like "sda1"
+    Unmount(String),
     NetIO,
-    Processes,
+    DiskIO,
+    NetworkUsage,
+    ResetNetworkUsage,
+    Processes(ProcessAction),
+    Signal(SignalAction),
     Host,
     Software,
-    Command(CommandAction),
+    SoftwareAction(SoftwareAction),
+    // The name of a command from the backend's config
+    Command(String),
     Services,
-    Directory(String),
+    Journal(JournalAction),
+    TailLog(TailAction),
+    ServiceControl(ServiceAction),
+    Power(PowerAction),
+    DropCaches,
+    Docker,
+    Directory(DirectoryAction),
+    Search(SearchAction),
+    Stat(String),
+    Checksum(String),
     Download(String),
+    DownloadArchive(String),
+    Preview(String),
+    Thumbnail(ThumbnailAction),
+    ReadStream(u32),
+    ReadFile(String),
+    SaveFile(SaveFileAction),
+    UploadChunk(UploadChunkAction),
+    UploadStatus(String),
+    FileAction(FileAction),
     ReadConfig,
+    DietPiSettings,
+    SetDietPiSetting(DietPiSettingAction),
+    Terminals,
+    Ping,
 }
 
 #[derive(Debug, Encode, Decode)]
 pub enum ActionFrontendMessage {
-    Terminal(Vec<u8>),
-    Signal(SignalAction),
-    NewFile(String),
-    NewFolder(String),
-    Rename(RenameAction),
-    DeleteFile(String),
-    DeleteFolder(String),
-    Upload(UploadAction),
+    Terminal(u32, Vec<u8>),
+    CloseTerminal(u32),
+    CloseStream(u32),
 }
 
 #[derive(Debug, Encode, Decode, Deserialize)]
@@ -52,19 +81,178 @@ pub enum Signal {
 }
 
 #[derive(Debug, Encode, Decode)]
-pub struct CommandAction {
-    pub cmd: String,
-    pub args: Vec<String>,
+pub struct SoftwareAction {
+    pub action: SoftwareCommand,
+    pub software: Vec<u16>,
+}
+
+#[derive(Debug, Encode, Decode, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SoftwareCommand {
+    Install,
+    Uninstall,
+}
+
+#[derive(Debug, Encode, Decode, Deserialize)]
+pub struct ServiceAction {
+    pub unit: String,
+    pub action: ServiceCommand,
+}
+
+#[derive(Debug, Encode, Decode, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceCommand {
+    Start,
+    Stop,
+    Restart,
+    Enable,
+    Disable,
+}
+
+#[derive(Debug, Encode, Decode, Deserialize)]
+pub struct MountAction {
+    // The name of a partition from the block device list, like "sda1"
+    pub device: String,
+    pub target: String,
+}
+
+#[derive(Debug, Encode, Decode, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerAction {
+    Reboot,
+    Poweroff,
+    Halt,
+}
+
+#[derive(Debug, Encode, Decode, Deserialize)]
+pub struct DietPiSettingAction {
+    pub setting: DietPiSetting,
+    pub value: String,
+}
+
+// The settings that can be changed from the dashboard, rather than any key in dietpi.txt
+#[derive(Debug, Encode, Decode, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum DietPiSetting {
+    SwapSize,
+    SwapLocation,
+    CpuGovernor,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct JournalAction {
+    pub unit: String,
+    // Number of past lines to show before following new ones
+    pub lines: u32,
+    // Anything `journalctl --since` accepts, like "1 hour ago" or "2025-01-01 12:00"
+    pub since: Option<String>,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct ThumbnailAction {
+    pub path: String,
+    // Largest width or height in pixels, the aspect ratio is kept
+    pub size: u32,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct TailAction {
+    pub path: String,
+    // Number of past lines to show before following new ones
+    pub lines: u32,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct DirectoryAction {
+    pub path: String,
+    pub sort: DirectorySort,
+    pub reverse: bool,
+    pub offset: u32,
+    pub limit: u32,
+}
+
+#[derive(Debug, Encode, Decode, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DirectorySort {
+    #[default]
+    Name,
+    Size,
+    Modified,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct ProcessAction {
+    pub sort: ProcessSort,
+    pub reverse: bool,
+    // Only the first this many processes after sorting are sent, or all of them if 0
+    pub limit: u32,
+}
+
+#[derive(Debug, Encode, Decode, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessSort {
+    #[default]
+    Pid,
+    Name,
+    Status,
+    Cpu,
+    Ram,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct SearchAction {
+    pub root: String,
+    // Matched against file names, either as a glob if it contains `*` or `?`, or as a substring
+    pub pattern: String,
 }
 
 #[derive(Debug, Encode, Decode)]
-pub struct RenameAction {
+pub enum FileAction {
+    NewFile(String),
+    NewFolder(String),
+    Rename(TransferAction),
+    Move(TransferAction),
+    Copy(TransferAction),
+    DeleteFile(String),
+    // Recursive, so kept separate from deleting a file
+    DeleteFolder(String),
+    Chmod(ChmodAction),
+    Chown(ChownAction),
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct TransferAction {
     pub from: String,
     pub to: String,
 }
 
+// Changing protected system paths is refused unless `force` is set
+#[derive(Debug, Encode, Decode)]
+pub struct ChmodAction {
+    pub path: String,
+    pub mode: u32,
+    pub force: bool,
+}
+
+// The owner and group can be names or numeric IDs, and are left unchanged if `None`
+#[derive(Debug, Encode, Decode)]
+pub struct ChownAction {
+    pub path: String,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    pub force: bool,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct SaveFileAction {
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
 #[derive(Debug, Encode, Decode)]
-pub struct UploadAction {
+pub struct UploadChunkAction {
     pub path: String,
+    pub offset: u64,
     pub data: Vec<u8>,
+    pub last: bool,
 }
@@ -1,51 +1,112 @@
 use futures_util::{SinkExt, StreamExt};
-use ring::aead::{Aad, CHACHA20_POLY1305, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::{
+    aead::{Aad, CHACHA20_POLY1305, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    hkdf::{HKDF_SHA256, Salt},
+};
 use std::{fmt::Debug, io};
-use tokio::net::TcpStream;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
 pub mod backend;
 pub mod frontend;
 
+const SALT_LEN: usize = 32;
+
+// One direction of the connection, with frames numbered in the order they're sent
+struct Cipher {
+    key: LessSafeKey,
+    counter: u64,
+}
+
+impl Cipher {
+    fn new(secret: &[u8; 32], sender_salt: &[u8], receiver_salt: &[u8]) -> Self {
+        let prk = Salt::new(HKDF_SHA256, &[sender_salt, receiver_salt].concat()).extract(secret);
+        // Only fails if the key is too long for the hash, which it isn't
+        let okm = prk
+            .expand(&[b"dietpi-dashboard"], &CHACHA20_POLY1305)
+            .unwrap();
+
+        Self {
+            key: LessSafeKey::new(UnboundKey::from(okm)),
+            counter: 0,
+        }
+    }
+
+    // A frame that's replayed, dropped, or reordered won't be opened with the nonce it was sealed
+    // with, so it fails to decrypt
+    fn next_nonce(&mut self) -> io::Result<Nonce> {
+        let mut nonce = [0; NONCE_LEN];
+        nonce[NONCE_LEN - 8..].copy_from_slice(&self.counter.to_be_bytes());
+
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| io::Error::other("too many frames sent on one connection"))?;
+
+        Ok(Nonce::assume_unique_for_key(nonce))
+    }
+}
+
 pub struct DashboardSocket {
     framed: Framed<TcpStream, LengthDelimitedCodec>,
-    key: LessSafeKey,
+    sealing: Cipher,
+    opening: Cipher,
 }
 
 impl DashboardSocket {
-    pub fn new(stream: TcpStream, key: [u8; 32]) -> Self {
+    pub async fn new(mut stream: TcpStream, secret: [u8; 32]) -> Result<Self, io::Error> {
+        // Both sides pick a salt for each connection, so the keys are never the same twice and
+        // frames captured from another connection can't be replayed into this one
+        let local_salt: [u8; SALT_LEN] = rand::random();
+        stream.write_all(&local_salt).await?;
+
+        let mut peer_salt = [0; SALT_LEN];
+        stream.read_exact(&mut peer_salt).await?;
+
+        // Each direction has its own key, so frames can't be sent back to the side they came from.
+        // A peer that sends our own salt back would make both keys the same.
+        if peer_salt == local_salt {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "peer sent back our own salt",
+            ));
+        }
+
+        let sealing = Cipher::new(&secret, &local_salt, &peer_salt);
+        let opening = Cipher::new(&secret, &peer_salt, &local_salt);
+
         let framed = LengthDelimitedCodec::builder()
             .length_field_type::<u32>()
             .new_framed(stream);
 
-        // Length of buffer is guaranteed to be 32 bytes
-        let key = UnboundKey::new(&CHACHA20_POLY1305, &key).unwrap();
-        let key = LessSafeKey::new(key);
-
-        Self { framed, key }
+        Ok(Self {
+            framed,
+            sealing,
+            opening,
+        })
     }
 
     pub async fn read_frame<F: bitcode::DecodeOwned + Debug>(
         &mut self,
     ) -> Result<Option<F>, io::Error> {
-        self.framed
-            .next()
-            .await
-            .map(|x| {
-                x.and_then(|mut data| {
-                    let nonce = data.split_off(data.len() - NONCE_LEN);
-                    let nonce = Nonce::assume_unique_for_key((*nonce).try_into().unwrap());
-
-                    let data = self
-                        .key
-                        .open_in_place(nonce, Aad::empty(), &mut data)
-                        .map_err(|_| io::Error::other("decryption failed"))?;
-
-                    bitcode::decode(data)
-                        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
-                })
-            })
-            .transpose()
+        let Some(mut data) = self.framed.next().await.transpose()? else {
+            return Ok(None);
+        };
+
+        let nonce = self.opening.next_nonce()?;
+
+        let data = self
+            .opening
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut data)
+            .map_err(|_| io::Error::other("decryption failed"))?;
+
+        bitcode::decode(data)
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
     }
 
     pub async fn write_frame<F: bitcode::Encode + Debug>(
@@ -54,15 +115,13 @@ impl DashboardSocket {
     ) -> Result<(), io::Error> {
         let mut data = bitcode::encode(&frame);
 
-        // Random nonces start to become unsafe after about 2^30 messages sent with the same key
-        let nonce: [u8; 12] = rand::random();
+        let nonce = self.sealing.next_nonce()?;
 
-        self.key
-            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+        self.sealing
+            .key
+            .seal_in_place_append_tag(nonce, Aad::empty(), &mut data)
             .map_err(|_| io::Error::other("encryption failed"))?;
 
-        data.extend(nonce);
-
         self.framed.send(data.into()).await
     }
 }
@@ -17,6 +17,7 @@ css_assets=(
   "$asset_path/css/process.css"
   "$asset_path/css/management.css"
   "$asset_path/css/software.css"
+  "$asset_path/css/service.css"
   "$asset_path/css/browser.css"
   "$asset_path/css/xterm-5.5.0.css"
 )
@@ -35,9 +36,12 @@ mkdir -p "$dist_path"
 
 cat "${js_assets[@]}" | gzip -9c > "$js_out"
 cat "${css_assets[@]}" | gzip -9c > "$css_out"
+cat "${js_assets[@]}" | brotli -Zc > "$js_out.br"
+cat "${css_assets[@]}" | brotli -Zc > "$css_out.br"
 
 for svg in "${svg_assets[@]}"; do
   gzip -9c "$svg" > "$dist_path/$(basename "$svg")"
+  brotli -Zc "$svg" > "$dist_path/$(basename "$svg").br"
 done
 
 rm "${css_assets[0]}"